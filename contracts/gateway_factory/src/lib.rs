@@ -0,0 +1,93 @@
+#![no_std]
+use soroban_sdk::{
+    contract, contractimpl, contractmeta, symbol_short, xdr::ToXdr, Address, BytesN, Env, IntoVal,
+    Map, Symbol, Vec,
+};
+
+contractmeta!(key = "name", val = "gateway-factory");
+contractmeta!(key = "version", val = "0.1.0");
+contractmeta!(
+    key = "repo",
+    val = "https://github.com/Adi9876/Stellar-Contract"
+);
+
+// Storage Keys (all <=9 chars)
+const ADMIN: Symbol = symbol_short!("ADMIN");
+// wasm hash new gateway instances are deployed from
+const WASM: Symbol = symbol_short!("WASM");
+const TOKEN: Symbol = symbol_short!("TOKEN");
+// merchant -> its gateway instance
+const INST: Symbol = symbol_short!("INST");
+
+#[contract]
+pub struct GatewayFactory;
+
+#[contractimpl]
+impl GatewayFactory {
+    pub fn init(env: Env, admin: Address, wasm_hash: BytesN<32>, token: Address) {
+        assert!(!env.storage().instance().has(&ADMIN), "already init");
+        admin.require_auth();
+        env.storage().instance().set(&ADMIN, &admin);
+        env.storage().instance().set(&WASM, &wasm_hash);
+        env.storage().instance().set(&TOKEN, &token);
+    }
+
+    // Only affects instances deployed from now on; existing ones upgrade on their own cadence.
+    pub fn set_wasm_hash(env: Env, wasm_hash: BytesN<32>) {
+        Self::admin(env.clone()).require_auth();
+        env.storage().instance().set(&WASM, &wasm_hash);
+        env.events().publish((symbol_short!("WasmSet"),), wasm_hash);
+    }
+
+    pub fn admin(env: Env) -> Address {
+        env.storage().instance().get(&ADMIN).expect("ADMIN not set")
+    }
+
+    // Deploys a gateway owned by the merchant, with the merchant already registered on it.
+    // The address is derived from the merchant, so each merchant gets at most one instance.
+    pub fn deploy(env: Env, merchant: Address) -> Address {
+        merchant.require_auth();
+        let mut instances = Self::instances(&env);
+        assert!(
+            !instances.contains_key(merchant.clone()),
+            "already deployed"
+        );
+        let wasm_hash: BytesN<32> = env.storage().instance().get(&WASM).expect("WASM not set");
+        let token: Address = env.storage().instance().get(&TOKEN).expect("TOKEN not set");
+        let salt: BytesN<32> = env.crypto().sha256(&merchant.clone().to_xdr(&env)).into();
+        let gateway = env
+            .deployer()
+            .with_current_contract(salt)
+            .deploy_v2(wasm_hash, ());
+        env.invoke_contract::<()>(
+            &gateway,
+            &Symbol::new(&env, "init"),
+            Vec::from_array(&env, [merchant.to_val(), token.to_val()]),
+        );
+        env.invoke_contract::<()>(
+            &gateway,
+            &Symbol::new(&env, "add_merchant"),
+            (merchant.clone(), merchant.clone()).into_val(&env),
+        );
+        instances.set(merchant.clone(), gateway.clone());
+        env.storage().instance().set(&INST, &instances);
+        env.events()
+            .publish((symbol_short!("Deployed"), merchant), gateway.clone());
+        gateway
+    }
+
+    pub fn get_instance(env: Env, merchant: Address) -> Option<Address> {
+        Self::instances(&env).get(merchant)
+    }
+
+    pub fn list_instances(env: Env) -> Map<Address, Address> {
+        Self::instances(&env)
+    }
+
+    fn instances(env: &Env) -> Map<Address, Address> {
+        env.storage().instance().get(&INST).unwrap_or(Map::new(env))
+    }
+}
+
+#[cfg(test)]
+mod test;
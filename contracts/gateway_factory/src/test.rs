@@ -0,0 +1,56 @@
+#![cfg(test)]
+
+use soroban_sdk::{testutils::Address as _, Address, BytesN, Env};
+
+use crate::{GatewayFactory, GatewayFactoryClient};
+
+// Build the gateway first: `cargo build --target wasm32v1-none --release -p payment-gateway`.
+mod gateway {
+    soroban_sdk::contractimport!(file = "../../target/wasm32v1-none/release/payment_gateway.wasm");
+}
+
+fn setup() -> (Env, Address, GatewayFactoryClient<'static>) {
+    let env = Env::default();
+    env.mock_all_auths();
+    // instantiating the full gateway wasm runs well past the default budget
+    env.cost_estimate().budget().reset_unlimited();
+    let admin = Address::generate(&env);
+    let token = Address::generate(&env);
+    let wasm_hash = env.deployer().upload_contract_wasm(gateway::WASM);
+    let factory = GatewayFactoryClient::new(&env, &env.register(GatewayFactory, ()));
+    factory.init(&admin, &wasm_hash, &token);
+    (env, token, factory)
+}
+
+#[test]
+fn each_merchant_gets_one_gateway_it_owns() {
+    let (env, token, factory) = setup();
+    let merchant = Address::generate(&env);
+
+    let instance = factory.deploy(&merchant);
+    assert_eq!(factory.get_instance(&merchant), Some(instance.clone()));
+    assert_eq!(factory.list_instances().len(), 1);
+    let gateway = gateway::Client::new(&env, &instance);
+    let config = gateway.config();
+    assert_eq!(config.owner, merchant);
+    assert_eq!(config.token, token);
+    assert!(gateway.get_merchant_record(&merchant).is_some());
+    assert!(factory.try_deploy(&merchant).is_err());
+
+    let other = Address::generate(&env);
+    assert_ne!(factory.deploy(&other), instance);
+    assert_eq!(factory.list_instances().len(), 2);
+}
+
+#[test]
+fn init_is_once_and_the_wasm_hash_is_the_admins() {
+    let (env, token, factory) = setup();
+    let hash = BytesN::from_array(&env, &[7; 32]);
+    assert!(factory
+        .try_init(&Address::generate(&env), &hash, &token)
+        .is_err());
+
+    env.mock_auths(&[]);
+    assert!(factory.try_set_wasm_hash(&hash).is_err());
+    assert!(factory.try_deploy(&Address::generate(&env)).is_err());
+}
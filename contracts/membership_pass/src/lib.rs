@@ -0,0 +1,149 @@
+#![no_std]
+use soroban_sdk::{
+    contract, contractimpl, contractmeta, contracttype, symbol_short, Address, Env, IntoVal,
+    Symbol, Timepoint, TryFromVal, Val, Vec,
+};
+
+contractmeta!(key = "name", val = "membership-pass");
+contractmeta!(key = "version", val = "0.1.0");
+contractmeta!(
+    key = "repo",
+    val = "https://github.com/Adi9876/Stellar-Contract"
+);
+
+// Time-bound pass for one subscription, issued and revoked by the gateway only. A holder can
+// have several subscriptions to the same plan, each with its own pass.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MembershipPass {
+    merchant: Address,
+    plan_id: u64,
+    expires_at: Timepoint,
+}
+
+// Storage Keys (all <=9 chars)
+const GATEWAY: Symbol = symbol_short!("GATEWAY");
+// (PASSES, holder, subscription_id) -> MembershipPass, in persistent storage
+const PASSES: Symbol = symbol_short!("PASSES");
+// (HELD, holder, plan_id) -> Vec<u64> of the holder's subscription ids with a pass for the plan
+const HELD: Symbol = symbol_short!("HELD");
+
+// kept alive for about a month past the last read or write
+const RECORD_TTL: u32 = 535_680;
+const RECORD_TTL_THRESHOLD: u32 = RECORD_TTL / 2;
+
+#[contract]
+pub struct MembershipPassContract;
+
+#[contractimpl]
+impl MembershipPassContract {
+    pub fn init(env: Env, gateway: Address) {
+        assert!(!env.storage().instance().has(&GATEWAY), "already init");
+        env.storage().instance().set(&GATEWAY, &gateway);
+    }
+
+    pub fn gateway(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&GATEWAY)
+            .expect("GATEWAY not set")
+    }
+
+    // Issuing over an existing pass only ever pushes its expiry later.
+    pub fn issue(
+        env: Env,
+        holder: Address,
+        subscription_id: u64,
+        merchant: Address,
+        plan_id: u64,
+        expires_at: Timepoint,
+    ) {
+        Self::gateway(env.clone()).require_auth();
+        let expires_at = match Self::get_pass(env.clone(), holder.clone(), subscription_id) {
+            Some(p) if p.expires_at.to_unix() > expires_at.to_unix() => p.expires_at,
+            Some(_) => expires_at,
+            None => {
+                let key = (HELD, holder.clone(), plan_id);
+                let mut held: Vec<u64> = Self::get(&env, &key).unwrap_or(Vec::new(&env));
+                held.push_back(subscription_id);
+                Self::put(&env, &key, &held);
+                expires_at
+            }
+        };
+        Self::put(
+            &env,
+            &(PASSES, holder.clone(), subscription_id),
+            &MembershipPass {
+                merchant,
+                plan_id,
+                expires_at: expires_at.clone(),
+            },
+        );
+        env.events().publish(
+            (symbol_short!("Issue"), holder, subscription_id),
+            (plan_id, expires_at),
+        );
+    }
+
+    pub fn revoke(env: Env, holder: Address, subscription_id: u64) {
+        Self::gateway(env.clone()).require_auth();
+        let pass = match Self::get_pass(env.clone(), holder.clone(), subscription_id) {
+            Some(p) => p,
+            None => return,
+        };
+        env.storage()
+            .persistent()
+            .remove(&(PASSES, holder.clone(), subscription_id));
+        let key = (HELD, holder.clone(), pass.plan_id);
+        let mut held: Vec<u64> = Self::get(&env, &key).unwrap_or(Vec::new(&env));
+        if let Some(i) = held.first_index_of(subscription_id) {
+            held.remove(i);
+        }
+        if held.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else {
+            Self::put(&env, &key, &held);
+        }
+        env.events().publish(
+            (symbol_short!("Revoke"), holder, subscription_id),
+            pass.plan_id,
+        );
+    }
+
+    pub fn get_pass(env: Env, holder: Address, subscription_id: u64) -> Option<MembershipPass> {
+        Self::get(&env, &(PASSES, holder, subscription_id))
+    }
+
+    // A holder is a member while any of their subscriptions to the plan has an unexpired pass.
+    pub fn is_member(env: Env, holder: Address, plan_id: u64) -> bool {
+        let held: Vec<u64> =
+            Self::get(&env, &(HELD, holder.clone(), plan_id)).unwrap_or(Vec::new(&env));
+        let now = env.ledger().timestamp();
+        held.iter().any(|id| {
+            Self::get_pass(env.clone(), holder.clone(), id)
+                .is_some_and(|p| p.expires_at.to_unix() > now)
+        })
+    }
+
+    fn get<K, V>(env: &Env, key: &K) -> Option<V>
+    where
+        K: IntoVal<Env, Val>,
+        V: TryFromVal<Env, Val>,
+    {
+        let value = env.storage().persistent().get(key)?;
+        env.storage()
+            .persistent()
+            .extend_ttl(key, RECORD_TTL_THRESHOLD, RECORD_TTL);
+        Some(value)
+    }
+
+    fn put<K: IntoVal<Env, Val>, V: IntoVal<Env, Val>>(env: &Env, key: &K, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage()
+            .persistent()
+            .extend_ttl(key, RECORD_TTL_THRESHOLD, RECORD_TTL);
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,67 @@
+#![cfg(test)]
+
+use soroban_sdk::{
+    testutils::{Address as _, Ledger},
+    Address, Env, Timepoint,
+};
+
+use crate::{MembershipPassContract, MembershipPassContractClient};
+
+fn setup() -> (Env, MembershipPassContractClient<'static>) {
+    let env = Env::default();
+    let pass = MembershipPassContractClient::new(&env, &env.register(MembershipPassContract, ()));
+    pass.init(&Address::generate(&env));
+    (env, pass)
+}
+
+#[test]
+fn revoking_one_subscription_keeps_the_other_pass_for_the_plan() {
+    let (env, pass) = setup();
+    env.mock_all_auths();
+    let holder = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let until = Timepoint::from_unix(&env, 1_000);
+    pass.issue(&holder, &1, &merchant, &7, &until);
+    pass.issue(&holder, &2, &merchant, &7, &until);
+    assert!(pass.is_member(&holder, &7));
+
+    pass.revoke(&holder, &1);
+    assert!(pass.get_pass(&holder, &1).is_none());
+    assert!(pass.get_pass(&holder, &2).is_some());
+    assert!(pass.is_member(&holder, &7));
+
+    pass.revoke(&holder, &2);
+    assert!(!pass.is_member(&holder, &7));
+}
+
+#[test]
+fn passes_lapse_at_expiry_and_never_shorten() {
+    let (env, pass) = setup();
+    env.mock_all_auths();
+    let holder = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    pass.issue(
+        &holder,
+        &1,
+        &merchant,
+        &7,
+        &Timepoint::from_unix(&env, 1_000),
+    );
+    // reissuing with an earlier expiry keeps the later one
+    pass.issue(&holder, &1, &merchant, &7, &Timepoint::from_unix(&env, 500));
+    env.ledger().with_mut(|l| l.timestamp = 999);
+    assert!(pass.is_member(&holder, &7));
+    assert!(!pass.is_member(&holder, &8));
+    env.ledger().with_mut(|l| l.timestamp = 1_000);
+    assert!(!pass.is_member(&holder, &7));
+}
+
+#[test]
+fn only_the_gateway_issues_passes() {
+    let (env, pass) = setup();
+    let holder = Address::generate(&env);
+    let until = Timepoint::from_unix(&env, 1_000);
+    assert!(pass.try_issue(&holder, &1, &holder, &7, &until).is_err());
+    assert!(pass.try_revoke(&holder, &1).is_err());
+    assert!(pass.try_init(&holder).is_err());
+}
@@ -0,0 +1,146 @@
+use soroban_sdk::{contractimpl, panic_with_error, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{Error, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PLINK};
+
+// (BLOCK, merchant, payer) -> true while the merchant refuses the payer
+const BLOCK: Symbol = symbol_short!("BLOCK");
+// link ids restricted to an allowlist, and (ALLOW, link, payer) -> true for each listed payer
+const PRIV: Symbol = symbol_short!("PRIV");
+const ALLOW: Symbol = symbol_short!("ALLOW");
+// per-link cap on purchases by one address, and (BUYS, link, payer) -> purchases made
+const LIMIT: Symbol = symbol_short!("LIMIT");
+const BUYS: Symbol = symbol_short!("BUYS");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn block_payer(env: Env, invoker: Address, payer: Address) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        let key = (BLOCK, invoker.clone(), payer.clone());
+        assert!(!env.storage().persistent().has(&key), "already blocked");
+        Self::put_record(&env, &key, &true);
+        Self::emit(&env, (symbol_short!("Blocked"), invoker), &payer);
+    }
+
+    pub fn unblock_payer(env: Env, invoker: Address, payer: Address) {
+        invoker.require_auth();
+        let key = (BLOCK, invoker.clone(), payer.clone());
+        assert!(env.storage().persistent().has(&key), "not blocked");
+        Self::drop_record(&env, &key);
+        Self::emit(&env, (symbol_short!("Unblocked"), invoker), &payer);
+    }
+
+    pub fn is_payer_blocked(env: Env, merchant: Address, payer: Address) -> bool {
+        Self::get_record::<_, bool>(&env, &(BLOCK, merchant, payer)).is_some()
+    }
+
+    pub fn set_link_private(env: Env, invoker: Address, link_id: u64, private: bool) {
+        invoker.require_auth();
+        Self::assert_link_manager(&env, &invoker, link_id);
+        let mut private_links: Map<u64, bool> = env
+            .storage()
+            .instance()
+            .get(&PRIV)
+            .unwrap_or(Map::new(&env));
+        if private {
+            private_links.set(link_id, true);
+        } else {
+            private_links.remove(link_id);
+        }
+        env.storage().instance().set(&PRIV, &private_links);
+        Self::emit(&env, (symbol_short!("LinkPriv"), link_id), private);
+    }
+
+    pub fn allow_payer(env: Env, invoker: Address, link_id: u64, payer: Address) {
+        invoker.require_auth();
+        Self::assert_link_manager(&env, &invoker, link_id);
+        Self::put_record(&env, &(ALLOW, link_id, payer.clone()), &true);
+        Self::emit(&env, (symbol_short!("Allowed"), link_id), &payer);
+    }
+
+    pub fn disallow_payer(env: Env, invoker: Address, link_id: u64, payer: Address) {
+        invoker.require_auth();
+        Self::assert_link_manager(&env, &invoker, link_id);
+        let key = (ALLOW, link_id, payer.clone());
+        assert!(env.storage().persistent().has(&key), "not listed");
+        Self::drop_record(&env, &key);
+        Self::emit(&env, (symbol_short!("Disallow"), link_id), &payer);
+    }
+
+    // True for any payer on a public link, and for allowlisted payers on a private one.
+    pub fn is_payer_allowed(env: Env, link_id: u64, payer: Address) -> bool {
+        let private_links: Map<u64, bool> = env
+            .storage()
+            .instance()
+            .get(&PRIV)
+            .unwrap_or(Map::new(&env));
+        if !private_links.contains_key(link_id) {
+            return true;
+        }
+        Self::get_record::<_, bool>(&env, &(ALLOW, link_id, payer)).is_some()
+    }
+
+    // A limit of 0 removes the cap.
+    pub fn set_purchase_limit(env: Env, invoker: Address, link_id: u64, limit: u32) {
+        invoker.require_auth();
+        Self::assert_link_manager(&env, &invoker, link_id);
+        let mut limits: Map<u64, u32> = env
+            .storage()
+            .instance()
+            .get(&LIMIT)
+            .unwrap_or(Map::new(&env));
+        if limit == 0 {
+            limits.remove(link_id);
+        } else {
+            limits.set(link_id, limit);
+        }
+        env.storage().instance().set(&LIMIT, &limits);
+        Self::emit(&env, (symbol_short!("BuyLimit"), link_id), limit);
+    }
+
+    pub fn get_purchase_limit(env: Env, link_id: u64) -> u32 {
+        let limits: Map<u64, u32> = env
+            .storage()
+            .instance()
+            .get(&LIMIT)
+            .unwrap_or(Map::new(&env));
+        limits.get(link_id).unwrap_or(0)
+    }
+
+    pub fn purchase_count(env: Env, link_id: u64, payer: Address) -> u32 {
+        Self::get_record(&env, &(BUYS, link_id, payer)).unwrap_or(0)
+    }
+
+    pub(crate) fn count_purchase(env: &Env, link_id: u64, payer: &Address) {
+        let limit = Self::get_purchase_limit(env.clone(), link_id);
+        let count = Self::purchase_count(env.clone(), link_id, payer.clone());
+        if limit > 0 && count >= limit {
+            panic_with_error!(env, Error::PurchaseLimitReached);
+        }
+        Self::put_record(env, &(BUYS, link_id, payer.clone()), &(count + 1));
+    }
+
+    pub(crate) fn assert_allowed(env: &Env, link_id: u64, payer: &Address) {
+        if !Self::is_payer_allowed(env.clone(), link_id, payer.clone()) {
+            panic_with_error!(env, Error::PayerNotAllowed);
+        }
+    }
+
+    pub(crate) fn assert_link_manager(env: &Env, invoker: &Address, link_id: u64) {
+        let merchant = Self::acting_merchant(env, invoker);
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(env));
+        let link = links.get(link_id).expect("no link");
+        assert!(link.merchant == merchant, "not merchant");
+    }
+
+    pub(crate) fn assert_not_blocked(env: &Env, merchant: &Address, payer: &Address) {
+        assert!(
+            !Self::is_payer_blocked(env.clone(), merchant.clone(), payer.clone()),
+            "payer blocked"
+        );
+    }
+}
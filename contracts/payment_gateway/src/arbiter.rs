@@ -0,0 +1,69 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// owner-registered arbiters -> number of cases each has resolved
+const ARBS: Symbol = symbol_short!("ARBS");
+// share of a disputed amount paid to the arbiter that resolves it
+const ARBFEE: Symbol = symbol_short!("ARBFEE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn register_arbiter(env: Env, invoker: Address, arbiter: Address) {
+        Self::only_owner(&env, &invoker);
+        let mut arbiters = Self::arbiters(&env);
+        assert!(!arbiters.contains_key(arbiter.clone()), "already arbiter");
+        arbiters.set(arbiter.clone(), 0);
+        env.storage().instance().set(&ARBS, &arbiters);
+        Self::emit(&env, (symbol_short!("ArbAdd"),), &arbiter);
+    }
+
+    // Cases already assigned to a removed arbiter can still be resolved by it.
+    pub fn remove_arbiter(env: Env, invoker: Address, arbiter: Address) {
+        Self::only_owner(&env, &invoker);
+        let mut arbiters = Self::arbiters(&env);
+        assert!(arbiters.contains_key(arbiter.clone()), "not arbiter");
+        arbiters.remove(arbiter.clone());
+        env.storage().instance().set(&ARBS, &arbiters);
+        Self::emit(&env, (symbol_short!("ArbRem"),), &arbiter);
+    }
+
+    pub fn is_arbiter(env: Env, who: Address) -> bool {
+        Self::arbiters(&env).contains_key(who)
+    }
+
+    pub fn arbiter_cases(env: Env, arbiter: Address) -> u32 {
+        Self::arbiters(&env).get(arbiter).unwrap_or(0)
+    }
+
+    pub fn set_arbitration_fee(env: Env, invoker: Address, bps: u32) {
+        Self::only_owner(&env, &invoker);
+        assert!(bps <= 10_000, "bps<=10000");
+        env.storage().instance().set(&ARBFEE, &bps);
+        Self::emit(&env, (symbol_short!("ArbFee"),), bps);
+    }
+
+    pub fn get_arbitration_fee(env: Env) -> u32 {
+        env.storage().instance().get(&ARBFEE).unwrap_or(0)
+    }
+
+    // Pays the arbiter its cut of `disputed` from funds the gateway holds, counts the case and
+    // returns what is left for the parties.
+    pub(crate) fn pay_arbiter(env: &Env, arbiter: &Address, disputed: &i128) -> i128 {
+        let mut arbiters = Self::arbiters(env);
+        if let Some(cases) = arbiters.get(arbiter.clone()) {
+            arbiters.set(arbiter.clone(), cases + 1);
+            env.storage().instance().set(&ARBS, &arbiters);
+        }
+        let fee = Self::bps_of(env, disputed, Self::get_arbitration_fee(env.clone()));
+        if fee > 0 {
+            Self::payout(env, arbiter, &fee);
+            Self::emit(env, (symbol_short!("ArbPaid"), arbiter.clone()), fee);
+        }
+        disputed - fee
+    }
+
+    fn arbiters(env: &Env) -> Map<Address, u32> {
+        env.storage().instance().get(&ARBS).unwrap_or(Map::new(env))
+    }
+}
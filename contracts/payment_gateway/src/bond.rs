@@ -0,0 +1,142 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Timepoint};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BondConfig {
+    amount: i128,
+    cooldown: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantBond {
+    amount: i128,
+    unlock_at: Option<Timepoint>,
+}
+
+const BONDCFG: Symbol = symbol_short!("BONDCFG");
+const BONDS: Symbol = symbol_short!("BONDS");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Enables self-registration; owner-managed `add_merchant` keeps working alongside it.
+    pub fn set_merchant_bond(env: Env, invoker: Address, amount: i128, cooldown: u64) {
+        Self::only_owner(&env, &invoker);
+        assert!(amount > 0, "amount>0");
+        env.storage()
+            .instance()
+            .set(&BONDCFG, &BondConfig { amount, cooldown });
+        Self::emit(&env, (symbol_short!("BondCfg"),), cooldown);
+    }
+
+    pub fn disable_self_registration(env: Env, invoker: Address) {
+        Self::only_owner(&env, &invoker);
+        env.storage().instance().remove(&BONDCFG);
+        Self::emit(&env, (symbol_short!("BondOff"),), ());
+    }
+
+    pub fn get_bond_config(env: Env) -> Option<BondConfig> {
+        env.storage().instance().get(&BONDCFG)
+    }
+
+    pub fn register_merchant(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let cfg = Self::get_bond_config(env.clone()).expect("self registration off");
+        let mut bonds = Self::bonds(&env);
+        assert!(!bonds.contains_key(invoker.clone()), "bond exists");
+        bonds.set(
+            invoker.clone(),
+            MerchantBond {
+                amount: cfg.amount,
+                unlock_at: None,
+            },
+        );
+        env.storage().instance().set(&BONDS, &bonds);
+        Self::insert_merchant(&env, &invoker);
+        Self::transfer_from(
+            &env,
+            &invoker,
+            &invoker,
+            &env.current_contract_address(),
+            &cfg.amount,
+        );
+        Self::emit(&env, (symbol_short!("BondLock"), invoker), cfg.amount);
+    }
+
+    // Leaves the registry immediately; the bond unlocks once the cooldown has elapsed.
+    pub fn deregister_merchant(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let cfg_cooldown = Self::get_bond_config(env.clone())
+            .map(|c| c.cooldown)
+            .unwrap_or(0);
+        let mut bonds = Self::bonds(&env);
+        let mut bond = bonds.get(invoker.clone()).expect("no bond");
+        assert!(bond.unlock_at.is_none(), "already deregistered");
+        let unlock_at = env.ledger().timestamp() + cfg_cooldown;
+        bond.unlock_at = Some(Timepoint::from_unix(&env, unlock_at));
+        bonds.set(invoker.clone(), bond);
+        env.storage().instance().set(&BONDS, &bonds);
+        if Self::is_merchant(&env, &invoker) {
+            Self::drop_merchant(&env, &invoker);
+        }
+        Self::emit(&env, (symbol_short!("BondReq"), invoker), unlock_at);
+    }
+
+    // The bond stays locked while any fraud report against the merchant is undecided, so it is
+    // still there to be slashed if the report is upheld.
+    pub fn withdraw_bond(env: Env, invoker: Address) -> i128 {
+        invoker.require_auth();
+        let mut bonds = Self::bonds(&env);
+        let bond = bonds.get(invoker.clone()).expect("no bond");
+        let unlock_at = bond.unlock_at.expect("still registered");
+        assert!(
+            env.ledger().timestamp() >= unlock_at.to_unix(),
+            "cooldown active"
+        );
+        assert!(Self::open_reports(&env, &invoker) == 0, "fraud report open");
+        bonds.remove(invoker.clone());
+        env.storage().instance().set(&BONDS, &bonds);
+        Self::payout(&env, &invoker, &bond.amount);
+        Self::emit(&env, (symbol_short!("BondRet"), invoker), bond.amount);
+        bond.amount
+    }
+
+    pub fn get_merchant_bond(env: Env, merchant: Address) -> Option<MerchantBond> {
+        Self::bonds(&env).get(merchant)
+    }
+
+    // Takes up to `max` from the merchant's bond and returns the amount actually slashed.
+    pub(crate) fn slash_bond(env: &Env, merchant: &Address, max: &i128) -> i128 {
+        let mut bonds = Self::bonds(env);
+        let mut bond = match bonds.get(merchant.clone()) {
+            Some(b) => b,
+            None => return 0,
+        };
+        let amount = if *max > bond.amount {
+            bond.amount
+        } else {
+            *max
+        };
+        bond.amount -= amount;
+        bonds.set(merchant.clone(), bond);
+        env.storage().instance().set(&BONDS, &bonds);
+        amount
+    }
+
+    fn bonds(env: &Env) -> Map<Address, MerchantBond> {
+        env.storage()
+            .instance()
+            .get(&BONDS)
+            .unwrap_or(Map::new(env))
+    }
+
+    pub(crate) fn bonds_held(env: &Env) -> i128 {
+        let mut total = 0;
+        for (_, bond) in Self::bonds(env).iter() {
+            total += bond.amount;
+        }
+        total
+    }
+}
@@ -0,0 +1,57 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, TOKEN};
+
+// Inclusive range every link and plan price, and every amount a payer picks, must fall in.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AmountBounds {
+    min: i128,
+    max: i128,
+}
+
+// token -> bounds enforced while it is the gateway token
+const AMTBOUND: Symbol = symbol_short!("AMTBOUND");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Bounds are kept per token so they survive a token switch. Existing links, plans and
+    // subscriptions are not rechecked.
+    pub fn set_amount_bounds(env: Env, invoker: Address, token: Address, min: i128, max: i128) {
+        Self::only_owner(&env, &invoker);
+        assert!(min > 0, "min>0");
+        assert!(max >= min, "max<min");
+        let mut all = Self::all_bounds(&env);
+        all.set(token.clone(), AmountBounds { min, max });
+        env.storage().instance().set(&AMTBOUND, &all);
+        Self::emit(&env, (symbol_short!("Bounds"), token), (min, max));
+    }
+
+    pub fn clear_amount_bounds(env: Env, invoker: Address, token: Address) {
+        Self::only_owner(&env, &invoker);
+        let mut all = Self::all_bounds(&env);
+        assert!(all.contains_key(token.clone()), "no bounds");
+        all.remove(token.clone());
+        env.storage().instance().set(&AMTBOUND, &all);
+        Self::emit(&env, (symbol_short!("BoundsClr"), token), ());
+    }
+
+    pub fn get_amount_bounds(env: Env, token: Address) -> Option<AmountBounds> {
+        Self::all_bounds(&env).get(token)
+    }
+
+    pub(crate) fn assert_amount_bounds(env: &Env, amount: &i128) {
+        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        if let Some(bounds) = Self::all_bounds(env).get(token) {
+            assert!(*amount >= bounds.min, "below min amount");
+            assert!(*amount <= bounds.max, "above max amount");
+        }
+    }
+
+    fn all_bounds(env: &Env) -> Map<Address, AmountBounds> {
+        env.storage()
+            .instance()
+            .get(&AMTBOUND)
+            .unwrap_or(Map::new(env))
+    }
+}
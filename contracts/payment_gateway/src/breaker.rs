@@ -0,0 +1,115 @@
+use soroban_sdk::{
+    contractimpl, contracttype, panic_with_error, symbol_short, Address, Env, Map, Symbol,
+};
+
+use crate::{Error, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// Caps on inbound payment volume; `None` leaves that window unlimited.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VolumeLimits {
+    per_ledger: Option<i128>,
+    per_day: Option<i128>,
+}
+
+const VLIMITS: Symbol = symbol_short!("VLIMITS");
+// (ledger sequence, volume) for the ledger that last took a payment
+const LEDGVOL: Symbol = symbol_short!("LEDGVOL");
+// hour -> volume, covering the trailing 24 hours
+const HOURVOL: Symbol = symbol_short!("HOURVOL");
+const HOUR: u64 = 3600;
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_volume_limits(
+        env: Env,
+        invoker: Address,
+        per_ledger: Option<i128>,
+        per_day: Option<i128>,
+    ) {
+        Self::only_owner(&env, &invoker);
+        for cap in [&per_ledger, &per_day].into_iter().flatten() {
+            assert!(*cap > 0, "cap>0");
+        }
+        let limits = VolumeLimits {
+            per_ledger,
+            per_day,
+        };
+        env.storage().instance().set(&VLIMITS, &limits);
+        Self::emit(&env, (symbol_short!("VolLimit"),), limits);
+    }
+
+    pub fn get_volume_limits(env: Env) -> Option<VolumeLimits> {
+        env.storage().instance().get(&VLIMITS)
+    }
+
+    // Volume taken in the current ledger and over the trailing 24 hours.
+    pub fn current_volume(env: Env) -> (i128, i128) {
+        (Self::ledger_volume(&env), Self::day_volume(&env))
+    }
+
+    // Clears both windows once the owner has looked into a tripped breaker.
+    pub fn reset_volume(env: Env, invoker: Address) {
+        Self::only_owner(&env, &invoker);
+        env.storage().instance().remove(&LEDGVOL);
+        env.storage().instance().remove(&HOURVOL);
+        Self::emit(&env, (symbol_short!("VolReset"),), invoker);
+    }
+
+    // Counts an inbound payment, rejecting it if it would push either window over its cap.
+    pub(crate) fn track_volume(env: &Env, amount: &i128) {
+        let limits = Self::get_volume_limits(env.clone());
+        let seq = env.ledger().sequence();
+        let ledger = Self::ledger_volume(env) + amount;
+        let hour = env.ledger().timestamp() / HOUR;
+        let mut hours = Self::recent_hours(env);
+        let day = Self::sum_hours(&hours) + amount;
+        if let Some(limits) = limits {
+            let over_ledger = limits.per_ledger.is_some_and(|cap| ledger > cap);
+            let over_day = limits.per_day.is_some_and(|cap| day > cap);
+            if over_ledger || over_day {
+                panic_with_error!(env, Error::VolumeLimitExceeded);
+            }
+        }
+        env.storage().instance().set(&LEDGVOL, &(seq, ledger));
+        let this_hour = hours.get(hour).unwrap_or(0);
+        hours.set(hour, this_hour + amount);
+        env.storage().instance().set(&HOURVOL, &hours);
+    }
+
+    fn ledger_volume(env: &Env) -> i128 {
+        match env.storage().instance().get::<_, (u32, i128)>(&LEDGVOL) {
+            Some((seq, volume)) if seq == env.ledger().sequence() => volume,
+            _ => 0,
+        }
+    }
+
+    fn day_volume(env: &Env) -> i128 {
+        Self::sum_hours(&Self::recent_hours(env))
+    }
+
+    // Hourly buckets with anything older than 24 hours dropped.
+    fn recent_hours(env: &Env) -> Map<u64, i128> {
+        let hours: Map<u64, i128> = env
+            .storage()
+            .instance()
+            .get(&HOURVOL)
+            .unwrap_or(Map::new(env));
+        let oldest = (env.ledger().timestamp() / HOUR).saturating_sub(23);
+        let mut recent = Map::new(env);
+        for (hour, volume) in hours.iter() {
+            if hour >= oldest {
+                recent.set(hour, volume);
+            }
+        }
+        recent
+    }
+
+    fn sum_hours(hours: &Map<u64, i128>) -> i128 {
+        let mut total = 0;
+        for (_, volume) in hours.iter() {
+            total += volume;
+        }
+        total
+    }
+}
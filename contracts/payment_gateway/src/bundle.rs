@@ -0,0 +1,168 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, RateAction, Subscription,
+    SubscriptionPlan, SPLAN, SUBS,
+};
+
+// Subscribing to a bundle opens one subscription per plan, each priced `discount_bps` below
+// the plan. With `cancel_together`, cancelling any of them cancels the rest.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanBundle {
+    merchant: Address,
+    plan_ids: Vec<u64>,
+    discount_bps: u32,
+    cancel_together: bool,
+    active: bool,
+}
+
+const BCTR: Symbol = symbol_short!("BCTR");
+const BUNDLE: Symbol = symbol_short!("BUNDLE");
+// (subscriber, subscription id) -> bundle id and the ids opened alongside it
+const BSUBS: Symbol = symbol_short!("BSUBS");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn create_bundle(
+        env: Env,
+        invoker: Address,
+        plan_ids: Vec<u64>,
+        discount_bps: u32,
+        cancel_together: bool,
+    ) -> u32 {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(plan_ids.len() > 1, "bundle needs 2+ plans");
+        assert!(discount_bps < 10_000, "bps<10000");
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        for (i, plan_id) in plan_ids.iter().enumerate() {
+            assert!(
+                plan_ids.first_index_of(plan_id) == Some(i as u32),
+                "duplicate plan"
+            );
+            let plan = plans.get(plan_id).expect("plan not found");
+            assert!(plan.merchant == merchant, "not merchant");
+            assert!(plan.active, "plan not active");
+            assert!(!plan.donation, "donation plan");
+            assert!(!Self::is_metered(env.clone(), plan_id), "metered plan");
+        }
+        let ctr = Self::next_id(&env, &BCTR);
+        Self::save_bundle(
+            &env,
+            ctr,
+            &PlanBundle {
+                merchant,
+                plan_ids,
+                discount_bps,
+                cancel_together,
+                active: true,
+            },
+        );
+        Self::emit(&env, (symbol_short!("BundleCr"), ctr), discount_bps);
+        ctr
+    }
+
+    pub fn deactivate_bundle(env: Env, invoker: Address, bundle_id: u32) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let mut bundle = Self::get_bundle(env.clone(), bundle_id);
+        assert!(bundle.merchant == merchant, "not merchant");
+        assert!(bundle.active, "already inactive");
+        bundle.active = false;
+        Self::save_bundle(&env, bundle_id, &bundle);
+        Self::emit(&env, (symbol_short!("BundleOff"), bundle_id), merchant);
+    }
+
+    pub fn get_bundle(env: Env, bundle_id: u32) -> PlanBundle {
+        let bundles: Map<u32, PlanBundle> = env
+            .storage()
+            .instance()
+            .get(&BUNDLE)
+            .unwrap_or(Map::new(&env));
+        bundles.get(bundle_id).expect("no bundle")
+    }
+
+    // Returns the subscription ids in the bundle's plan order.
+    pub fn subscribe_bundle(env: Env, invoker: Address, bundle_id: u32) -> Vec<u64> {
+        invoker.require_auth();
+        let bundle = Self::get_bundle(env.clone(), bundle_id);
+        assert!(bundle.active, "bundle not active");
+        // one call, however many plans the bundle holds
+        Self::rate_limit(&env, RateAction::Subscribe, &invoker);
+        let mut ids = Vec::new(&env);
+        for plan_id in bundle.plan_ids.iter() {
+            ids.push_back(Self::open_subscription(
+                &env,
+                &invoker,
+                &invoker,
+                plan_id,
+                None,
+                1,
+                bundle.discount_bps,
+            ));
+        }
+        let mut members: Map<(Address, u64), (u32, Vec<u64>)> = env
+            .storage()
+            .instance()
+            .get(&BSUBS)
+            .unwrap_or(Map::new(&env));
+        for id in ids.iter() {
+            members.set((invoker.clone(), id), (bundle_id, ids.clone()));
+        }
+        env.storage().instance().set(&BSUBS, &members);
+        Self::emit(&env, (symbol_short!("BSubd"), bundle_id), ids.clone());
+        ids
+    }
+
+    pub fn get_subscription_bundle(
+        env: Env,
+        subscriber: Address,
+        subscription_id: u64,
+    ) -> Option<(u32, Vec<u64>)> {
+        let members: Map<(Address, u64), (u32, Vec<u64>)> = env
+            .storage()
+            .instance()
+            .get(&BSUBS)
+            .unwrap_or(Map::new(&env));
+        members.get((subscriber, subscription_id))
+    }
+
+    pub(crate) fn cancel_bundle_siblings(env: &Env, subscriber: &Address, subscription_id: u64) {
+        let (bundle_id, ids) =
+            match Self::get_subscription_bundle(env.clone(), subscriber.clone(), subscription_id) {
+                Some(entry) => entry,
+                None => return,
+            };
+        if !Self::get_bundle(env.clone(), bundle_id).cancel_together {
+            return;
+        }
+        let subs: Map<(Address, u64), Subscription> =
+            env.storage().instance().get(&SUBS).unwrap_or(Map::new(env));
+        for id in ids.iter() {
+            if id == subscription_id {
+                continue;
+            }
+            // siblings that already ended may have been pruned since
+            if let Some(sub) = subs.get((subscriber.clone(), id)) {
+                if sub.status.is_live() {
+                    Self::end_subscription(env, id, sub);
+                }
+            }
+        }
+    }
+
+    fn save_bundle(env: &Env, bundle_id: u32, bundle: &PlanBundle) {
+        let mut bundles: Map<u32, PlanBundle> = env
+            .storage()
+            .instance()
+            .get(&BUNDLE)
+            .unwrap_or(Map::new(env));
+        bundles.set(bundle_id, bundle.clone());
+        env.storage().instance().set(&BUNDLE, &bundles);
+    }
+}
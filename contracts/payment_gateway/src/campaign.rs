@@ -0,0 +1,165 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Timepoint};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Campaign {
+    merchant: Address,
+    goal: i128,
+    deadline: Timepoint,
+    raised: i128,
+    claimed: bool,
+    description: Symbol,
+}
+
+const CCTR: Symbol = symbol_short!("CCTR");
+const CAMP: Symbol = symbol_short!("CAMP");
+const CONTRIB: Symbol = symbol_short!("CONTRIB");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn create_campaign(
+        env: Env,
+        invoker: Address,
+        goal: i128,
+        deadline: u64,
+        description: Symbol,
+    ) -> u32 {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(goal > 0, "goal>0");
+        assert!(deadline > env.ledger().timestamp(), "deadline in past");
+        let ctr = Self::next_id(&env, &CCTR);
+        Self::save_campaign(
+            &env,
+            ctr,
+            &Campaign {
+                merchant: invoker,
+                goal,
+                deadline: Timepoint::from_unix(&env, deadline),
+                raised: 0,
+                claimed: false,
+                description,
+            },
+        );
+        Self::emit(&env, (symbol_short!("CampCr"), ctr), ctr);
+        ctr
+    }
+
+    pub fn contribute(env: Env, invoker: Address, campaign_id: u32, amount: i128) {
+        invoker.require_auth();
+        let mut campaign = Self::get_campaign(env.clone(), campaign_id);
+        assert!(
+            env.ledger().timestamp() < campaign.deadline.to_unix(),
+            "campaign ended"
+        );
+        assert!(amount > 0, "amount>0");
+        Self::assert_amount_bounds(&env, &amount);
+        campaign.raised += amount;
+        Self::save_campaign(&env, campaign_id, &campaign);
+        let key = (campaign_id, invoker.clone());
+        let mut contribs: Map<(u32, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&CONTRIB)
+            .unwrap_or(Map::new(&env));
+        let prev = contribs.get(key.clone()).unwrap_or(0);
+        contribs.set(key, prev + amount);
+        env.storage().instance().set(&CONTRIB, &contribs);
+        Self::collect(&env, &invoker, &amount);
+        Self::emit(
+            &env,
+            (symbol_short!("Contrib"), campaign_id),
+            (invoker, amount),
+        );
+    }
+
+    // All-or-nothing: funds are released only once the deadline has passed with the goal met.
+    pub fn claim_campaign(env: Env, invoker: Address, campaign_id: u32) {
+        invoker.require_auth();
+        let mut campaign = Self::get_campaign(env.clone(), campaign_id);
+        assert!(campaign.merchant == invoker, "not merchant");
+        assert!(
+            env.ledger().timestamp() >= campaign.deadline.to_unix(),
+            "campaign running"
+        );
+        assert!(campaign.raised >= campaign.goal, "goal not met");
+        assert!(!campaign.claimed, "already claimed");
+        campaign.claimed = true;
+        Self::save_campaign(&env, campaign_id, &campaign);
+        Self::release(&env, &campaign.merchant, &campaign.raised);
+        Self::emit(
+            &env,
+            (symbol_short!("CampClm"), campaign_id),
+            campaign.raised,
+        );
+    }
+
+    pub fn refund_contribution(env: Env, invoker: Address, campaign_id: u32) -> i128 {
+        invoker.require_auth();
+        let campaign = Self::get_campaign(env.clone(), campaign_id);
+        assert!(
+            env.ledger().timestamp() >= campaign.deadline.to_unix(),
+            "campaign running"
+        );
+        assert!(campaign.raised < campaign.goal, "goal met");
+        let key = (campaign_id, invoker.clone());
+        let mut contribs: Map<(u32, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&CONTRIB)
+            .unwrap_or(Map::new(&env));
+        let amount = contribs.get(key.clone()).expect("no contribution");
+        contribs.remove(key);
+        env.storage().instance().set(&CONTRIB, &contribs);
+        Self::payout(&env, &invoker, &amount);
+        Self::emit(
+            &env,
+            (symbol_short!("CampRef"), campaign_id),
+            (invoker, amount),
+        );
+        amount
+    }
+
+    pub fn get_campaign(env: Env, campaign_id: u32) -> Campaign {
+        let campaigns: Map<u32, Campaign> = env
+            .storage()
+            .instance()
+            .get(&CAMP)
+            .unwrap_or(Map::new(&env));
+        campaigns.get(campaign_id).expect("no campaign")
+    }
+
+    pub fn get_contribution(env: Env, campaign_id: u32, contributor: Address) -> i128 {
+        let contribs: Map<(u32, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&CONTRIB)
+            .unwrap_or(Map::new(&env));
+        contribs.get((campaign_id, contributor)).unwrap_or(0)
+    }
+
+    fn save_campaign(env: &Env, campaign_id: u32, campaign: &Campaign) {
+        let mut campaigns: Map<u32, Campaign> =
+            env.storage().instance().get(&CAMP).unwrap_or(Map::new(env));
+        campaigns.set(campaign_id, campaign.clone());
+        env.storage().instance().set(&CAMP, &campaigns);
+    }
+
+    // Contributions to campaigns that have not been claimed, refundable ones included.
+    pub(crate) fn campaigns_held(env: &Env) -> i128 {
+        let contribs: Map<(u32, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&CONTRIB)
+            .unwrap_or(Map::new(env));
+        let mut total = 0;
+        for ((campaign_id, _), amount) in contribs.iter() {
+            if !Self::get_campaign(env.clone(), campaign_id).claimed {
+                total += amount;
+            }
+        }
+        total
+    }
+}
@@ -0,0 +1,77 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Symbol, Vec};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, BATCH_MAX};
+
+// The combined record of a cart; each item also gets its own receipt, listed in cart order.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cart {
+    payer: Address,
+    // set when the cart was restricted to a single merchant's links
+    merchant: Option<Address>,
+    link_ids: Vec<u64>,
+    receipt_ids: Vec<u32>,
+    total: i128,
+}
+
+const CARTCTR: Symbol = symbol_short!("CARTCTR");
+// (CARTS, cart_id) -> Cart
+const CARTS: Symbol = symbol_short!("CARTS");
+
+#[contractimpl]
+impl PaymentGateway {
+    // The payer signs the link ids, the total and the single-merchant restriction once; any item
+    // failing reverts the whole cart. A link listed twice is bought twice.
+    pub fn process_payments(
+        env: Env,
+        invoker: Address,
+        link_ids: Vec<u64>,
+        total: i128,
+        single_merchant: bool,
+    ) -> u32 {
+        invoker.require_auth();
+        assert!(!link_ids.is_empty(), "empty cart");
+        assert!(link_ids.len() <= BATCH_MAX, "batch too large");
+        let mut merchant: Option<Address> = None;
+        let mut sum: i128 = 0;
+        let mut receipt_ids = Vec::new(&env);
+        for link_id in link_ids.iter() {
+            let price = Self::link_price(&env, link_id);
+            let receipt_id = Self::pay_link(
+                &env, &invoker, &invoker, &invoker, link_id, &price, None, None,
+            );
+            if single_merchant {
+                let seller = Self::get_receipt(env.clone(), receipt_id).merchant;
+                match &merchant {
+                    Some(m) => assert!(*m == seller, "mixed merchants"),
+                    None => merchant = Some(seller),
+                }
+            }
+            sum = Self::checked(&env, sum.checked_add(price));
+            receipt_ids.push_back(receipt_id);
+        }
+        assert!(sum == total, "price mismatch");
+        let ctr = Self::next_id(&env, &CARTCTR);
+        Self::put_record(
+            &env,
+            &(CARTS, ctr),
+            &Cart {
+                payer: invoker.clone(),
+                merchant,
+                link_ids,
+                receipt_ids: receipt_ids.clone(),
+                total,
+            },
+        );
+        Self::emit(
+            &env,
+            (symbol_short!("CartPaid"), ctr),
+            (invoker, receipt_ids, total),
+        );
+        ctr
+    }
+
+    pub fn get_cart(env: Env, cart_id: u32) -> Cart {
+        Self::get_record(&env, &(CARTS, cart_id)).expect("no cart")
+    }
+}
@@ -0,0 +1,106 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Receipt};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CashbackCampaign {
+    bps: u32,
+    starts_at: u64,
+    ends_at: u64,
+    budget: i128,
+    paid: i128,
+}
+
+const CASHBACK: Symbol = symbol_short!("CASHBACK");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Cashback is paid from the custodial balance when it covers it, otherwise from the payout
+    // address, which must have granted the gateway an allowance.
+    pub fn start_cashback(
+        env: Env,
+        invoker: Address,
+        bps: u32,
+        starts_at: u64,
+        ends_at: u64,
+        budget: i128,
+    ) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(bps > 0 && bps <= 10_000, "bps 1..=10000");
+        assert!(ends_at > starts_at, "ends before start");
+        assert!(ends_at > env.ledger().timestamp(), "ends in past");
+        assert!(budget > 0, "budget>0");
+        let mut campaigns = Self::cashback_campaigns(&env);
+        campaigns.set(
+            merchant.clone(),
+            CashbackCampaign {
+                bps,
+                starts_at,
+                ends_at,
+                budget,
+                paid: 0,
+            },
+        );
+        env.storage().instance().set(&CASHBACK, &campaigns);
+        Self::emit(&env, (symbol_short!("CbStart"), merchant), (bps, budget));
+    }
+
+    pub fn end_cashback(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let mut campaigns = Self::cashback_campaigns(&env);
+        assert!(campaigns.contains_key(merchant.clone()), "no campaign");
+        campaigns.remove(merchant.clone());
+        env.storage().instance().set(&CASHBACK, &campaigns);
+        Self::emit(&env, (symbol_short!("CbEnd"),), merchant);
+    }
+
+    pub fn get_cashback(env: Env, merchant: Address) -> Option<CashbackCampaign> {
+        Self::cashback_campaigns(&env).get(merchant)
+    }
+
+    // Returns part of a recorded payment to its payer, stopping once the budget is spent.
+    pub(crate) fn apply_cashback(env: &Env, receipt_id: u32, receipt: &Receipt) {
+        let mut campaigns = Self::cashback_campaigns(env);
+        let mut campaign = match campaigns.get(receipt.merchant.clone()) {
+            Some(c) => c,
+            None => return,
+        };
+        let now = env.ledger().timestamp();
+        if now < campaign.starts_at || now >= campaign.ends_at {
+            return;
+        }
+        let mut cashback = Self::bps_of(env, &receipt.amount, campaign.bps);
+        let left = campaign.budget - campaign.paid;
+        if cashback > left {
+            cashback = left;
+        }
+        if cashback <= 0 {
+            return;
+        }
+        campaign.paid += cashback;
+        campaigns.set(receipt.merchant.clone(), campaign);
+        env.storage().instance().set(&CASHBACK, &campaigns);
+        Self::pay_from_merchant(
+            env,
+            &env.current_contract_address(),
+            &receipt.merchant,
+            &receipt.payer,
+            &cashback,
+        );
+        Self::emit(
+            env,
+            (symbol_short!("Cashback"), receipt_id),
+            (receipt.payer.clone(), cashback),
+        );
+    }
+
+    fn cashback_campaigns(env: &Env) -> Map<Address, CashbackCampaign> {
+        env.storage()
+            .instance()
+            .get(&CASHBACK)
+            .unwrap_or(Map::new(env))
+    }
+}
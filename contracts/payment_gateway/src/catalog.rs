@@ -0,0 +1,178 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{
+    MerchantCategory, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink,
+    SubscriptionPlan, PLINK, SPLAN,
+};
+
+// Unlisted offerings stay payable by id but are left out of the public listings.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Visibility {
+    Public,
+    Unlisted,
+}
+
+// merchant -> ids of its active links and plans
+const MLINKS: Symbol = symbol_short!("MLINKS");
+const MPLANS: Symbol = symbol_short!("MPLANS");
+// ids of unlisted links and plans; anything absent is public
+const LHIDE: Symbol = symbol_short!("LHIDE");
+const PHIDE: Symbol = symbol_short!("PHIDE");
+const PAGE_MAX: u32 = 50;
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn merchant_links(env: Env, merchant: Address) -> Vec<u64> {
+        Self::catalog(&env, &MLINKS, &merchant)
+    }
+
+    pub fn merchant_plans(env: Env, merchant: Address) -> Vec<u64> {
+        Self::catalog(&env, &MPLANS, &merchant)
+    }
+
+    pub fn set_link_visibility(env: Env, invoker: Address, link_id: u64, visibility: Visibility) {
+        invoker.require_auth();
+        Self::assert_link_manager(&env, &invoker, link_id);
+        Self::set_visibility(&env, &LHIDE, link_id, visibility);
+        Self::emit(&env, (symbol_short!("LinkVis"), link_id), visibility);
+    }
+
+    pub fn set_plan_visibility(env: Env, invoker: Address, plan_id: u64, visibility: Visibility) {
+        invoker.require_auth();
+        Self::assert_plan_owner(&env, &invoker, plan_id);
+        Self::set_visibility(&env, &PHIDE, plan_id, visibility);
+        Self::emit(&env, (symbol_short!("PlanVis"), plan_id), visibility);
+    }
+
+    pub fn get_link_visibility(env: Env, link_id: u64) -> Visibility {
+        Self::visibility(&env, &LHIDE, link_id)
+    }
+
+    pub fn get_plan_visibility(env: Env, plan_id: u64) -> Visibility {
+        Self::visibility(&env, &PHIDE, plan_id)
+    }
+
+    // Active public links with id >= `start`, at most `limit` of them, optionally narrowed to
+    // one merchant or to merchants whose profile is in `category`.
+    pub fn list_public_links(
+        env: Env,
+        start: u64,
+        limit: u32,
+        merchant: Option<Address>,
+        category: Option<MerchantCategory>,
+    ) -> Vec<u64> {
+        assert!(limit <= PAGE_MAX, "limit too high");
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let mut ids = Vec::new(&env);
+        for (id, link) in links.iter() {
+            if ids.len() >= limit {
+                break;
+            }
+            if id >= start
+                && link.active
+                && Self::visibility(&env, &LHIDE, id) == Visibility::Public
+                && Self::listed_for(&env, &link.merchant, &merchant, &category)
+            {
+                ids.push_back(id);
+            }
+        }
+        ids
+    }
+
+    pub fn list_public_plans(
+        env: Env,
+        start: u64,
+        limit: u32,
+        merchant: Option<Address>,
+        category: Option<MerchantCategory>,
+    ) -> Vec<u64> {
+        assert!(limit <= PAGE_MAX, "limit too high");
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        let mut ids = Vec::new(&env);
+        for (id, plan) in plans.iter() {
+            if ids.len() >= limit {
+                break;
+            }
+            if id >= start
+                && plan.active
+                && Self::visibility(&env, &PHIDE, id) == Visibility::Public
+                && Self::listed_for(&env, &plan.merchant, &merchant, &category)
+            {
+                ids.push_back(id);
+            }
+        }
+        ids
+    }
+
+    pub(crate) fn index_link(env: &Env, merchant: &Address, link_id: u64, active: bool) {
+        Self::update_catalog(env, &MLINKS, merchant, link_id, active);
+    }
+
+    pub(crate) fn index_plan(env: &Env, merchant: &Address, plan_id: u64, active: bool) {
+        Self::update_catalog(env, &MPLANS, merchant, plan_id, active);
+    }
+
+    fn catalog(env: &Env, key: &Symbol, merchant: &Address) -> Vec<u64> {
+        let all: Map<Address, Vec<u64>> =
+            env.storage().instance().get(key).unwrap_or(Map::new(env));
+        all.get(merchant.clone()).unwrap_or(Vec::new(env))
+    }
+
+    fn update_catalog(env: &Env, key: &Symbol, merchant: &Address, id: u64, active: bool) {
+        let mut all: Map<Address, Vec<u64>> =
+            env.storage().instance().get(key).unwrap_or(Map::new(env));
+        let mut ids = all.get(merchant.clone()).unwrap_or(Vec::new(env));
+        if active {
+            ids.push_back(id);
+        } else if let Some(i) = ids.first_index_of(id) {
+            ids.remove(i);
+        }
+        all.set(merchant.clone(), ids);
+        env.storage().instance().set(key, &all);
+    }
+
+    fn visibility(env: &Env, key: &Symbol, id: u64) -> Visibility {
+        let hidden: Map<u64, bool> = env.storage().instance().get(key).unwrap_or(Map::new(env));
+        if hidden.contains_key(id) {
+            Visibility::Unlisted
+        } else {
+            Visibility::Public
+        }
+    }
+
+    fn set_visibility(env: &Env, key: &Symbol, id: u64, visibility: Visibility) {
+        let mut hidden: Map<u64, bool> = env.storage().instance().get(key).unwrap_or(Map::new(env));
+        if visibility == Visibility::Unlisted {
+            hidden.set(id, true);
+        } else {
+            hidden.remove(id);
+        }
+        env.storage().instance().set(key, &hidden);
+    }
+
+    fn listed_for(
+        env: &Env,
+        owner: &Address,
+        merchant: &Option<Address>,
+        category: &Option<MerchantCategory>,
+    ) -> bool {
+        if let Some(m) = merchant {
+            if m != owner {
+                return false;
+            }
+        }
+        match category {
+            Some(c) => Self::merchant_category(env, owner) == Some(*c),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, LEDGER_SECS, PLINK,
+    TOKEN,
+};
+
+// A quote pinned for one payer; lives in temporary storage and disappears on its own.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CheckoutSession {
+    link_id: u64,
+    payer: Address,
+    // the link price at quote time, before the coupon
+    amount: i128,
+    coupon: Option<Symbol>,
+    discount: i128,
+    token: Address,
+    expires_at: u64,
+}
+
+const CHKCTR: Symbol = symbol_short!("CHKCTR");
+const CHKOUT: Symbol = symbol_short!("CHKOUT");
+
+#[contractimpl]
+impl PaymentGateway {
+    // A session cannot outlive the longest TTL a temporary entry can be given.
+    pub fn create_checkout_session(
+        env: Env,
+        link_id: u64,
+        payer: Address,
+        expires_in: u64,
+        coupon: Option<Symbol>,
+    ) -> u32 {
+        assert!(expires_in > 0, "expires_in>0");
+        let ledgers = expires_in.div_ceil(LEDGER_SECS);
+        assert!(
+            ledgers <= env.storage().max_ttl() as u64,
+            "expires_in too long"
+        );
+        let ledgers = ledgers as u32;
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("link not found");
+        assert!(link.active, "inactive link");
+        let discount = match &coupon {
+            Some(code) => Self::coupon_discount(&env, &link.merchant, code, &link.amount),
+            None => 0,
+        };
+        let ctr = Self::next_id(&env, &CHKCTR);
+        let session = CheckoutSession {
+            link_id,
+            payer: payer.clone(),
+            amount: link.amount,
+            coupon,
+            discount,
+            token: env.storage().instance().get(&TOKEN).expect("Token"),
+            expires_at: env.ledger().timestamp() + expires_in,
+        };
+        let key = (CHKOUT, ctr);
+        env.storage().temporary().set(&key, &session);
+        env.storage().temporary().extend_ttl(&key, ledgers, ledgers);
+        Self::emit(&env, (symbol_short!("ChkCr"), ctr), (link_id, payer));
+        ctr
+    }
+
+    pub fn get_checkout_session(env: Env, session_id: u32) -> Option<CheckoutSession> {
+        env.storage().temporary().get(&(CHKOUT, session_id))
+    }
+
+    // Charges exactly the quoted terms; fails if the link's price, the coupon's discount or the
+    // token changed since.
+    pub fn complete_checkout(env: Env, session_id: u32) -> u32 {
+        Self::finish_checkout(&env, session_id, None)
+    }
+
+    // Same as `complete_checkout`, with loyalty points taken off the quoted amount.
+    pub fn complete_checkout_with_points(env: Env, session_id: u32, points: i128) -> u32 {
+        Self::finish_checkout(&env, session_id, Some(points))
+    }
+
+    fn finish_checkout(env: &Env, session_id: u32, points: Option<i128>) -> u32 {
+        let key = (CHKOUT, session_id);
+        let session: CheckoutSession = env.storage().temporary().get(&key).expect("no session");
+        session.payer.require_auth();
+        assert!(
+            env.ledger().timestamp() <= session.expires_at,
+            "session expired"
+        );
+        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        assert!(session.token == token, "token changed");
+        Self::assert_link_price(env, session.link_id, &session.amount);
+        env.storage().temporary().remove(&key);
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(env));
+        let merchant = links.get(session.link_id).expect("link not found").merchant;
+        if let Some(code) = &session.coupon {
+            assert!(
+                Self::coupon_discount(env, &merchant, code, &session.amount) == session.discount,
+                "coupon changed"
+            );
+        }
+        let quoted = session.amount - session.discount;
+        let amount = match points {
+            Some(points) => {
+                quoted - Self::redeem_points(env, &merchant, &session.payer, &quoted, &points)
+            }
+            None => quoted,
+        };
+        let receipt_id = Self::pay_link(
+            env,
+            &session.payer,
+            &session.payer,
+            &session.payer,
+            session.link_id,
+            &amount,
+            None,
+            None,
+        );
+        Self::emit(env, (symbol_short!("ChkDone"), session_id), receipt_id);
+        receipt_id
+    }
+}
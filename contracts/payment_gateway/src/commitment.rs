@@ -0,0 +1,177 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentSource, Receipt, Subscription,
+    SubscriptionPlan, SPLAN,
+};
+
+// Cancelling before `cycles` periods have started costs `exit_fee` plus `remaining_bps`
+// of the price of every committed period not yet paid for.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Commitment {
+    cycles: u32,
+    exit_fee: i128,
+    remaining_bps: u32,
+}
+
+const COMMIT: Symbol = symbol_short!("COMMIT");
+// fixed-term plans: subscriptions expire once this many periods have started
+const TERM: Symbol = symbol_short!("TERM");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_commitment(
+        env: Env,
+        invoker: Address,
+        plan_id: u64,
+        cycles: u32,
+        exit_fee: i128,
+        remaining_bps: u32,
+    ) {
+        invoker.require_auth();
+        Self::assert_plan_owner(&env, &invoker, plan_id);
+        assert!(!Self::is_metered(env.clone(), plan_id), "metered plan");
+        assert!(cycles > 1, "cycles>1");
+        assert!(exit_fee >= 0, "fee>=0");
+        assert!(remaining_bps <= 10_000, "bps<=10000");
+        // existing subscribers signed up without the exit terms
+        assert!(
+            Self::plan_subscribers(env.clone(), plan_id).is_empty(),
+            "plan has subscribers"
+        );
+        let mut all: Map<u64, Commitment> = env
+            .storage()
+            .instance()
+            .get(&COMMIT)
+            .unwrap_or(Map::new(&env));
+        all.set(
+            plan_id,
+            Commitment {
+                cycles,
+                exit_fee,
+                remaining_bps,
+            },
+        );
+        env.storage().instance().set(&COMMIT, &all);
+        Self::emit(&env, (symbol_short!("CommitSet"), plan_id), cycles);
+    }
+
+    pub fn clear_commitment(env: Env, invoker: Address, plan_id: u64) {
+        invoker.require_auth();
+        Self::assert_plan_owner(&env, &invoker, plan_id);
+        let mut all: Map<u64, Commitment> = env
+            .storage()
+            .instance()
+            .get(&COMMIT)
+            .unwrap_or(Map::new(&env));
+        assert!(all.contains_key(plan_id), "no commitment");
+        all.remove(plan_id);
+        env.storage().instance().set(&COMMIT, &all);
+        Self::emit(&env, (symbol_short!("CommitClr"), plan_id), plan_id);
+    }
+
+    pub fn get_commitment(env: Env, plan_id: u64) -> Option<Commitment> {
+        let all: Map<u64, Commitment> = env
+            .storage()
+            .instance()
+            .get(&COMMIT)
+            .unwrap_or(Map::new(&env));
+        all.get(plan_id)
+    }
+
+    // A term of 0 makes the plan open-ended again.
+    pub fn set_plan_term(env: Env, invoker: Address, plan_id: u64, cycles: u32) {
+        invoker.require_auth();
+        Self::assert_plan_owner(&env, &invoker, plan_id);
+        let mut terms: Map<u64, u32> = env
+            .storage()
+            .instance()
+            .get(&TERM)
+            .unwrap_or(Map::new(&env));
+        if cycles == 0 {
+            terms.remove(plan_id);
+        } else {
+            assert!(cycles > 1, "term>1");
+            terms.set(plan_id, cycles);
+        }
+        env.storage().instance().set(&TERM, &terms);
+        Self::emit(&env, (symbol_short!("TermSet"), plan_id), cycles);
+    }
+
+    pub fn get_plan_term(env: Env, plan_id: u64) -> Option<u32> {
+        let terms: Map<u64, u32> = env
+            .storage()
+            .instance()
+            .get(&TERM)
+            .unwrap_or(Map::new(&env));
+        terms.get(plan_id)
+    }
+
+    pub(crate) fn term_reached(env: &Env, sub: &Subscription) -> bool {
+        match Self::get_plan_term(env.clone(), sub.plan_id) {
+            Some(term) => sub.cycles >= term,
+            None => false,
+        }
+    }
+
+    // Charged from the subscription's payer when it is cancelled inside its commitment, against
+    // the payer's allowance to the gateway since the beneficiary of a gift may be the one cancelling.
+    pub(crate) fn charge_early_exit(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+    ) {
+        let terms = match Self::get_commitment(env.clone(), sub.plan_id) {
+            Some(t) if sub.cycles < t.cycles => t,
+            _ => return,
+        };
+        // prepaid periods are refunded on cancellation, so they count as unpaid here
+        let unpaid = terms.cycles.saturating_sub(sub.cycles);
+        let remaining = Self::bps_of(
+            env,
+            &Self::checked(env, Self::seat_total(env, sub).checked_mul(unpaid as i128)),
+            terms.remaining_bps,
+        );
+        let charge = terms.exit_fee + remaining;
+        if charge <= 0 {
+            return;
+        }
+        let fee = Self::settle(
+            env,
+            &env.current_contract_address(),
+            &sub.payer,
+            &plan.merchant,
+            &charge,
+        );
+        let receipt = Receipt {
+            fee,
+            ..Receipt::new(
+                env,
+                PaymentSource::Subscription(sub.subscriber.clone(), subscription_id),
+                plan.merchant.clone(),
+                sub.payer.clone(),
+                sub.subscriber.clone(),
+                charge,
+            )
+        };
+        let receipt_id = Self::record_receipt(env, &receipt);
+        Self::emit(
+            env,
+            (symbol_short!("ExitFee"), subscription_id),
+            Self::payment_event(env, receipt_id, &receipt),
+        );
+    }
+
+    pub(crate) fn assert_plan_owner(env: &Env, invoker: &Address, plan_id: u64) {
+        let merchant = Self::acting_merchant(env, invoker);
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(env));
+        let plan = plans.get(plan_id).expect("no plan");
+        assert!(plan.merchant == merchant, "not merchant");
+    }
+}
@@ -0,0 +1,80 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Symbol};
+
+use crate::{AdminAction, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, OWNER, TOKEN};
+
+const PAUSED: Symbol = symbol_short!("PAUSED");
+// set once the owner has given up control for good
+const RENOUNCE: Symbol = symbol_short!("RENOUNCE");
+const VERSION: u32 = 1;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GatewayConfig {
+    owner: Address,
+    token: Address,
+    fee_bps: u32,
+    treasury: Option<Address>,
+    paused: bool,
+    renounced: bool,
+}
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn version(env: Env) -> (u32, Symbol) {
+        (VERSION, Symbol::new(&env, "payment_gateway"))
+    }
+
+    pub fn config(env: Env) -> GatewayConfig {
+        GatewayConfig {
+            owner: env.storage().instance().get(&OWNER).expect("OWNER not set"),
+            token: env.storage().instance().get(&TOKEN).expect("Token"),
+            fee_bps: Self::get_fee_bps(env.clone()),
+            treasury: Self::get_treasury(env.clone()),
+            paused: Self::is_paused(env.clone()),
+            renounced: Self::is_renounced(env),
+        }
+    }
+
+    // Pausing stops new inbound payments; refunds, withdrawals and escrow exits keep working.
+    pub fn set_paused(env: Env, invoker: Address, paused: bool) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        Self::assert_not_governed(&env, &AdminAction::SetPaused(paused));
+        Self::store_paused(&env, paused);
+    }
+
+    // Freezes the merchant registry, fees and every other owner setting for good. Anyone can
+    // still collect protocol fees into the fee shares, so those must be set while fees accrue.
+    pub fn renounce_ownership(env: Env, invoker: Address) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        assert!(!Self::has_pending_ops(&env), "ops pending");
+        assert!(Self::get_governor(env.clone()).is_none(), "governor set");
+        let fees_owed = Self::get_fee_bps(env.clone()) > 0
+            || Self::accrued_fees(env.clone()) > 0
+            || Self::custodial_held(&env) > 0;
+        assert!(
+            !fees_owed || !Self::get_fee_shares(env.clone()).is_empty(),
+            "fees would be stranded"
+        );
+        env.storage().instance().set(&RENOUNCE, &true);
+        Self::emit(&env, (symbol_short!("Renounced"),), invoker);
+    }
+
+    pub fn is_renounced(env: Env) -> bool {
+        env.storage().instance().has(&RENOUNCE)
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        env.storage().instance().get(&PAUSED).unwrap_or(false)
+    }
+
+    pub(crate) fn store_paused(env: &Env, paused: bool) {
+        env.storage().instance().set(&PAUSED, &paused);
+        Self::emit(env, (symbol_short!("Paused"),), paused);
+    }
+
+    pub(crate) fn assert_not_paused(env: &Env) {
+        assert!(!Self::is_paused(env.clone()), "paused");
+    }
+}
@@ -0,0 +1,248 @@
+use soroban_sdk::{
+    auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation},
+    contractimpl, contracttype, panic_with_error, symbol_short, vec, Address, Env, IntoVal, Map,
+    Symbol, Vec,
+};
+
+use crate::{
+    Error, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PaymentSource,
+    Receipt, PLINK, TOKEN,
+};
+
+// Payments in another token are swapped into the gateway token through `router`, which exposes
+// `swap(from, token_in, token_out, amount_in, min_out)`: it takes `amount_in` from `from` and
+// sends the output back to it. `oracle` is a SEP-40 price feed; a swap is refused when either
+// price is older than `max_age` seconds or the output falls more than `max_deviation_bps`
+// short of what the prices imply.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConversionConfig {
+    router: Address,
+    oracle: Address,
+    max_age: u64,
+    max_deviation_bps: u32,
+}
+
+// The SEP-40 `lastprice` argument and result.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OracleAsset {
+    Stellar(Address),
+    Other(Symbol),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PriceData {
+    pub price: i128,
+    pub timestamp: u64,
+}
+
+const CONVCFG: Symbol = symbol_short!("CONVCFG");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_conversion_config(
+        env: Env,
+        invoker: Address,
+        router: Address,
+        oracle: Address,
+        max_age: u64,
+        max_deviation_bps: u32,
+    ) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        assert!(max_age > 0, "max_age>0");
+        assert!(max_deviation_bps <= 10_000, "bps<=10000");
+        env.storage().instance().set(
+            &CONVCFG,
+            &ConversionConfig {
+                router: router.clone(),
+                oracle,
+                max_age,
+                max_deviation_bps,
+            },
+        );
+        Self::emit(
+            &env,
+            (symbol_short!("ConvCfg"), router),
+            (max_age, max_deviation_bps),
+        );
+    }
+
+    pub fn disable_conversion(env: Env, invoker: Address) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        let cfg = Self::get_conversion_config(env.clone()).expect("conversion off");
+        env.storage().instance().remove(&CONVCFG);
+        Self::emit(&env, (symbol_short!("ConvOff"),), cfg.router);
+    }
+
+    pub fn get_conversion_config(env: Env) -> Option<ConversionConfig> {
+        env.storage().instance().get(&CONVCFG)
+    }
+
+    // Pays a link with up to `max_in` of `pay_token`. All of `max_in` is swapped and whatever
+    // the swap returns above the link price goes back to the payer in the gateway token. Fails
+    // with SlippageExceeded if the output is below `min_out` or the price.
+    pub fn pay_link_with_swap(
+        env: Env,
+        invoker: Address,
+        link_id: u64,
+        amount: i128,
+        pay_token: Address,
+        max_in: i128,
+        min_out: i128,
+    ) -> u32 {
+        invoker.require_auth_for_args(
+            (link_id, amount, pay_token.clone(), max_in, min_out).into_val(&env),
+        );
+        Self::assert_link_price(&env, link_id, &amount);
+        Self::assert_no_seller(&env, link_id);
+        Self::assert_not_paused(&env);
+        let cfg = Self::get_conversion_config(env.clone()).expect("conversion off");
+        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        assert!(pay_token != token, "same token");
+        assert!(max_in > 0, "max_in>0");
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("link not found");
+        assert!(link.active, "inactive link");
+        Self::assert_not_blocked(&env, &link.merchant, &invoker);
+        Self::assert_allowed(&env, link_id, &invoker);
+        Self::count_purchase(&env, link_id, &invoker);
+        // priced before the swap so the check cannot be moved by it
+        let fair = Self::fair_output(&env, &cfg, &pay_token, &token, &max_in);
+        let out = Self::swap_in(&env, &cfg, &invoker, &pay_token, &token, &max_in, &min_out);
+        if out < min_out || out < amount {
+            panic_with_error!(&env, Error::SlippageExceeded);
+        }
+        if out < fair - Self::bps_of(&env, &fair, cfg.max_deviation_bps) {
+            panic_with_error!(&env, Error::PriceDeviation);
+        }
+        Self::track_volume(&env, &amount);
+        Self::track_spending(&env, &invoker, &amount);
+        let (fee, tax) = Self::settle_held_taxed(&env, &link.merchant, &amount, &amount);
+        if out > amount {
+            Self::payout(&env, &invoker, &(out - amount));
+        }
+        let receipt = Receipt {
+            tax,
+            fee,
+            ..Receipt::new(
+                &env,
+                PaymentSource::Link(link_id),
+                link.merchant,
+                invoker.clone(),
+                invoker,
+                amount,
+            )
+        };
+        let receipt_id = Self::record_receipt(&env, &receipt);
+        Self::emit(
+            &env,
+            (symbol_short!("PaidSwap"), link_id),
+            (
+                pay_token,
+                max_in,
+                out,
+                Self::payment_event(&env, receipt_id, &receipt),
+            ),
+        );
+        receipt_id
+    }
+
+    // Moves `amount_in` from the payer through the router and returns how much of `token_out`
+    // the gateway received, measured from its own balance rather than the router's word.
+    fn swap_in(
+        env: &Env,
+        cfg: &ConversionConfig,
+        payer: &Address,
+        token_in: &Address,
+        token_out: &Address,
+        amount_in: &i128,
+        min_out: &i128,
+    ) -> i128 {
+        let gateway = env.current_contract_address();
+        Self::invoke_token(
+            env,
+            token_in,
+            "transfer",
+            Vec::from_array(
+                env,
+                [payer.to_val(), gateway.to_val(), amount_in.into_val(env)],
+            ),
+        );
+        env.authorize_as_current_contract(vec![
+            env,
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: token_in.clone(),
+                    fn_name: Symbol::new(env, "transfer"),
+                    args: (gateway.clone(), cfg.router.clone(), *amount_in).into_val(env),
+                },
+                sub_invocations: Vec::new(env),
+            }),
+        ]);
+        let before = Self::liquid_balance(env);
+        Self::invoke_token(
+            env,
+            &cfg.router,
+            "swap",
+            Vec::from_array(
+                env,
+                [
+                    gateway.to_val(),
+                    token_in.to_val(),
+                    token_out.to_val(),
+                    amount_in.into_val(env),
+                    min_out.into_val(env),
+                ],
+            ),
+        );
+        Self::liquid_balance(env) - before
+    }
+
+    // What `amount` of `token_in` is worth in `token_out` at the oracle's prices.
+    fn fair_output(
+        env: &Env,
+        cfg: &ConversionConfig,
+        token_in: &Address,
+        token_out: &Address,
+        amount: &i128,
+    ) -> i128 {
+        let price_in = Self::oracle_price(env, cfg, token_in);
+        let price_out = Self::oracle_price(env, cfg, token_out);
+        let decimals_in: u32 =
+            env.invoke_contract(token_in, &Symbol::new(env, "decimals"), Vec::new(env));
+        let decimals_out = Self::token_decimals(env.clone());
+        let value = Self::checked(
+            env,
+            amount
+                .checked_mul(price_in)
+                .and_then(|v| v.checked_mul(10i128.checked_pow(decimals_out)?)),
+        );
+        let scale = Self::checked(
+            env,
+            price_out.checked_mul(Self::checked(env, 10i128.checked_pow(decimals_in))),
+        );
+        value / scale
+    }
+
+    fn oracle_price(env: &Env, cfg: &ConversionConfig, token: &Address) -> i128 {
+        let data: Option<PriceData> = env.invoke_contract(
+            &cfg.oracle,
+            &Symbol::new(env, "lastprice"),
+            vec![env, OracleAsset::Stellar(token.clone()).into_val(env)],
+        );
+        let data = match data {
+            Some(d) if env.ledger().timestamp().saturating_sub(d.timestamp) <= cfg.max_age => d,
+            _ => panic_with_error!(env, Error::StalePrice),
+        };
+        assert!(data.price > 0, "bad price");
+        data.price
+    }
+}
@@ -0,0 +1,60 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// (merchant, code) -> share of the price the coupon takes off, in bps
+const COUPON: Symbol = symbol_short!("COUPON");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Setting an existing code changes its discount for checkouts quoted from then on.
+    pub fn set_coupon(env: Env, invoker: Address, code: Symbol, discount_bps: u32) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(discount_bps > 0, "bps>0");
+        assert!(discount_bps < 10_000, "bps<10000");
+        let mut coupons = Self::coupons(&env);
+        coupons.set((merchant.clone(), code.clone()), discount_bps);
+        env.storage().instance().set(&COUPON, &coupons);
+        Self::emit(
+            &env,
+            (symbol_short!("CouponSet"), merchant),
+            (code, discount_bps),
+        );
+    }
+
+    pub fn remove_coupon(env: Env, invoker: Address, code: Symbol) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let mut coupons = Self::coupons(&env);
+        assert!(
+            coupons.contains_key((merchant.clone(), code.clone())),
+            "no coupon"
+        );
+        coupons.remove((merchant.clone(), code.clone()));
+        env.storage().instance().set(&COUPON, &coupons);
+        Self::emit(&env, (symbol_short!("CouponOff"), merchant), code);
+    }
+
+    pub fn get_coupon(env: Env, merchant: Address, code: Symbol) -> Option<u32> {
+        Self::coupons(&env).get((merchant, code))
+    }
+
+    // What `code` takes off `price` at `merchant`.
+    pub(crate) fn coupon_discount(
+        env: &Env,
+        merchant: &Address,
+        code: &Symbol,
+        price: &i128,
+    ) -> i128 {
+        let bps = Self::get_coupon(env.clone(), merchant.clone(), code.clone()).expect("no coupon");
+        Self::bps_of(env, price, bps)
+    }
+
+    fn coupons(env: &Env) -> Map<(Address, Symbol), u32> {
+        env.storage()
+            .instance()
+            .get(&COUPON)
+            .unwrap_or(Map::new(env))
+    }
+}
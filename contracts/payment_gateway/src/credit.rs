@@ -0,0 +1,111 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, IntoVal, Map, Symbol};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PaymentSource, Receipt,
+    PLINK,
+};
+
+// (customer, merchant) -> store credit the customer can spend on the merchant's links
+const CREDIT: Symbol = symbol_short!("CREDIT");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Refunds into store credit instead of tokens. The payer must consent, since the credit
+    // can only be spent with this merchant; the tokens stay with the merchant meanwhile.
+    // Returns the id of the receipt recording the credit issued.
+    pub fn refund_to_credit(env: Env, invoker: Address, receipt_id: u32, amount: i128) -> u32 {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let receipt = Self::get_receipt(env.clone(), receipt_id);
+        assert!(receipt.merchant == merchant, "not merchant");
+        receipt.payer.require_auth();
+        Self::assert_refund_window(&env, &receipt);
+        let receipt = Self::take_refund(&env, receipt_id, &amount);
+        Self::record_refund(&env, &merchant, &amount);
+        let balance = Self::store_credit(env.clone(), receipt.payer.clone(), merchant.clone());
+        Self::set_credit(&env, &receipt.payer, &merchant, &(balance + amount));
+        let credit_id = Self::store_receipt(
+            &env,
+            &Receipt::new(
+                &env,
+                PaymentSource::StoreCredit(receipt_id),
+                merchant,
+                receipt.payer.clone(),
+                receipt.customer,
+                amount,
+            ),
+        );
+        Self::emit(
+            &env,
+            (symbol_short!("CreditIs"), receipt_id),
+            (credit_id, receipt.payer, amount),
+        );
+        credit_id
+    }
+
+    pub fn store_credit(env: Env, customer: Address, merchant: Address) -> i128 {
+        let credits: Map<(Address, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&CREDIT)
+            .unwrap_or(Map::new(&env));
+        credits.get((customer, merchant)).unwrap_or(0)
+    }
+
+    // Pays a link in full from store credit. No tokens move, no fee is taken and no cashback is
+    // paid: the merchant already holds the funds the credit was issued against.
+    pub fn pay_with_credit(env: Env, invoker: Address, link_id: u64, amount: i128) -> u32 {
+        invoker.require_auth_for_args((link_id, amount).into_val(&env));
+        Self::assert_link_price(&env, link_id, &amount);
+        Self::assert_no_seller(&env, link_id);
+        Self::assert_not_paused(&env);
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("link not found");
+        assert!(link.active, "inactive link");
+        Self::assert_not_blocked(&env, &link.merchant, &invoker);
+        Self::assert_allowed(&env, link_id, &invoker);
+        Self::count_purchase(&env, link_id, &invoker);
+        let balance = Self::store_credit(env.clone(), invoker.clone(), link.merchant.clone());
+        assert!(balance >= amount, "insufficient credit");
+        Self::set_credit(&env, &invoker, &link.merchant, &(balance - amount));
+        let receipt = Receipt::new(
+            &env,
+            PaymentSource::Link(link_id),
+            link.merchant,
+            invoker.clone(),
+            invoker,
+            amount,
+        );
+        // `record_receipt` without the cashback: it would pay out tokens on a purchase that
+        // moved none
+        let receipt_id = Self::store_receipt(&env, &receipt);
+        Self::accrue_points(&env, &receipt);
+        Self::mint_receipt_nft(&env, receipt_id, &receipt);
+        Self::run_payment_hook(&env, receipt_id, &receipt);
+        Self::emit(
+            &env,
+            (symbol_short!("PaydCred"), link_id),
+            Self::payment_event(&env, receipt_id, &receipt),
+        );
+        receipt_id
+    }
+
+    fn set_credit(env: &Env, customer: &Address, merchant: &Address, amount: &i128) {
+        let mut credits: Map<(Address, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&CREDIT)
+            .unwrap_or(Map::new(env));
+        let key = (customer.clone(), merchant.clone());
+        if *amount == 0 {
+            credits.remove(key);
+        } else {
+            credits.set(key, *amount);
+        }
+        env.storage().instance().set(&CREDIT, &credits);
+    }
+}
@@ -0,0 +1,71 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, TOKEN};
+
+// token -> its `decimals()`, fetched once per token
+const DECIMALS: Symbol = symbol_short!("DECIMALS");
+// smallest link or plan price, in hundredths of a whole token
+const MINPRICE: Symbol = symbol_short!("MINPRICE");
+const DEFAULT_MIN_PRICE: u32 = 1;
+
+#[contractimpl]
+impl PaymentGateway {
+    // The floor is in display units so it means the same whatever the token's decimals.
+    // Zero turns it off; existing links and plans are not affected.
+    pub fn set_min_price(env: Env, invoker: Address, hundredths: u32) {
+        Self::only_owner(&env, &invoker);
+        env.storage().instance().set(&MINPRICE, &hundredths);
+        Self::emit(&env, (symbol_short!("MinPrice"),), hundredths);
+    }
+
+    pub fn get_min_price(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&MINPRICE)
+            .unwrap_or(DEFAULT_MIN_PRICE)
+    }
+
+    pub fn token_decimals(env: Env) -> u32 {
+        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        let mut cache: Map<Address, u32> = env
+            .storage()
+            .instance()
+            .get(&DECIMALS)
+            .unwrap_or(Map::new(&env));
+        if let Some(decimals) = cache.get(token.clone()) {
+            return decimals;
+        }
+        let decimals: u32 =
+            env.invoke_contract(&token, &Symbol::new(&env, "decimals"), Vec::new(&env));
+        cache.set(token, decimals);
+        env.storage().instance().set(&DECIMALS, &cache);
+        decimals
+    }
+
+    // Whole tokens in base units, for pricing in display units.
+    pub fn to_base_units(env: Env, whole: i128) -> i128 {
+        let unit = Self::unit(&env);
+        Self::checked(&env, whole.checked_mul(unit))
+    }
+
+    // Base units as (whole tokens, remaining base units).
+    pub fn to_whole_units(env: Env, amount: i128) -> (i128, i128) {
+        let unit = Self::unit(&env);
+        (amount / unit, amount % unit)
+    }
+
+    pub(crate) fn assert_min_price(env: &Env, amount: &i128) {
+        let hundredths = Self::get_min_price(env.clone());
+        if hundredths == 0 {
+            return;
+        }
+        // tokens with fewer than two decimals still need at least one base unit
+        let floor =
+            (Self::checked(env, Self::unit(env).checked_mul(hundredths as i128)) / 100).max(1);
+        assert!(*amount >= floor, "below min price");
+    }
+
+    fn unit(env: &Env) -> i128 {
+        Self::checked(env, 10i128.checked_pow(Self::token_decimals(env.clone())))
+    }
+}
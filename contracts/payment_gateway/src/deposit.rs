@@ -0,0 +1,105 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Subscription};
+
+// (subscriber, subscription id) -> funds the payer has escrowed for future charges
+const DEPOS: Symbol = symbol_short!("DEPOS");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Charges draw from the deposit while it covers them, instead of pulling from the payer.
+    pub fn deposit_for_subscription(
+        env: Env,
+        invoker: Address,
+        subscriber: Address,
+        subscription_id: u64,
+        amount: i128,
+    ) {
+        invoker.require_auth();
+        assert!(amount > 0, "amount>0");
+        let sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        assert!(sub.payer == invoker, "not payer");
+        assert!(sub.status.is_live(), "sub inactive");
+        let balance = Self::subscription_deposit(env.clone(), subscriber.clone(), subscription_id);
+        Self::set_deposit(&env, &subscriber, subscription_id, &(balance + amount));
+        Self::collect(&env, &invoker, &amount);
+        Self::emit(&env, (symbol_short!("Deposit"), subscription_id), amount);
+    }
+
+    pub fn subscription_deposit(env: Env, subscriber: Address, subscription_id: u64) -> i128 {
+        let deposits: Map<(Address, u64), i128> = env
+            .storage()
+            .instance()
+            .get(&DEPOS)
+            .unwrap_or(Map::new(&env));
+        deposits.get((subscriber, subscription_id)).unwrap_or(0)
+    }
+
+    // The unspent deposit goes back to the payer once the subscription has ended.
+    pub fn withdraw_deposit(
+        env: Env,
+        invoker: Address,
+        subscriber: Address,
+        subscription_id: u64,
+    ) -> i128 {
+        invoker.require_auth();
+        let sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        assert!(sub.payer == invoker, "not payer");
+        assert!(!sub.status.is_live(), "sub still live");
+        Self::return_deposit(&env, subscription_id, &sub)
+    }
+
+    pub(crate) fn return_deposit(env: &Env, subscription_id: u64, sub: &Subscription) -> i128 {
+        let balance =
+            Self::subscription_deposit(env.clone(), sub.subscriber.clone(), subscription_id);
+        if balance > 0 {
+            Self::set_deposit(env, &sub.subscriber, subscription_id, &0);
+            Self::payout(env, &sub.payer, &balance);
+            Self::emit(env, (symbol_short!("DepRet"), subscription_id), balance);
+        }
+        balance
+    }
+
+    // Debits `amount` if the deposit covers it in full.
+    pub(crate) fn draw_deposit(
+        env: &Env,
+        subscriber: &Address,
+        subscription_id: u64,
+        amount: &i128,
+    ) -> bool {
+        let balance = Self::subscription_deposit(env.clone(), subscriber.clone(), subscription_id);
+        if balance < *amount {
+            return false;
+        }
+        Self::set_deposit(env, subscriber, subscription_id, &(balance - amount));
+        true
+    }
+
+    fn set_deposit(env: &Env, subscriber: &Address, subscription_id: u64, amount: &i128) {
+        let mut deposits: Map<(Address, u64), i128> = env
+            .storage()
+            .instance()
+            .get(&DEPOS)
+            .unwrap_or(Map::new(env));
+        let key = (subscriber.clone(), subscription_id);
+        if *amount == 0 {
+            deposits.remove(key);
+        } else {
+            deposits.set(key, *amount);
+        }
+        env.storage().instance().set(&DEPOS, &deposits);
+    }
+
+    pub(crate) fn deposits_held(env: &Env) -> i128 {
+        let deposits: Map<(Address, u64), i128> = env
+            .storage()
+            .instance()
+            .get(&DEPOS)
+            .unwrap_or(Map::new(env));
+        let mut total = 0;
+        for (_, amount) in deposits.iter() {
+            total += amount;
+        }
+        total
+    }
+}
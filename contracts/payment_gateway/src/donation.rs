@@ -0,0 +1,147 @@
+use soroban_sdk::{
+    contractimpl, contracttype, symbol_short, Address, Env, Map, String, Symbol, Vec,
+};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, RateAction, Subscription,
+    SubscriptionPlan, SPLAN, SUBS,
+};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DonationTier {
+    name: Symbol,
+    min_amount: i128,
+}
+
+const TIERS: Symbol = symbol_short!("TIERS");
+const SUPP: Symbol = symbol_short!("SUPP");
+
+#[contractimpl]
+impl PaymentGateway {
+    // `min_amount` is stored as the plan amount; tiers must be listed in ascending order.
+    pub fn create_donation_plan(
+        env: Env,
+        invoker: Address,
+        min_amount: i128,
+        interval: u32,
+        name: String,
+        tiers: Vec<DonationTier>,
+    ) -> u64 {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(min_amount > 0, "amount>0");
+        Self::assert_min_price(&env, &min_amount);
+        Self::assert_amount_bounds(&env, &min_amount);
+        assert!(interval > 0, "interval>0");
+        Self::rate_limit(&env, RateAction::CreatePlan, &invoker);
+        let mut floor = min_amount;
+        for tier in tiers.iter() {
+            assert!(tier.min_amount >= floor, "tiers unordered");
+            floor = tier.min_amount;
+        }
+        let plan_id = Self::add_plan(
+            &env,
+            SubscriptionPlan {
+                merchant,
+                amount: min_amount,
+                interval,
+                active: true,
+                name,
+                donation: true,
+            },
+        );
+        let mut all: Map<u64, Vec<DonationTier>> = env
+            .storage()
+            .instance()
+            .get(&TIERS)
+            .unwrap_or(Map::new(&env));
+        all.set(plan_id, tiers);
+        env.storage().instance().set(&TIERS, &all);
+        plan_id
+    }
+
+    pub fn subscribe_donation(env: Env, invoker: Address, plan_id: u64, amount: i128) -> u64 {
+        invoker.require_auth();
+        Self::rate_limit(&env, RateAction::Subscribe, &invoker);
+        Self::open_subscription(&env, &invoker, &invoker, plan_id, Some(amount), 1, 0)
+    }
+
+    pub fn set_donation_amount(env: Env, invoker: Address, subscription_id: u64, amount: i128) {
+        invoker.require_auth();
+        let key = (invoker.clone(), subscription_id);
+        let mut subs: Map<(Address, u64), Subscription> = env
+            .storage()
+            .instance()
+            .get(&SUBS)
+            .unwrap_or(Map::new(&env));
+        let mut sub = subs.get(key.clone()).expect("no sub");
+        assert!(sub.status.is_live(), "sub inactive");
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        let plan = plans.get(sub.plan_id).expect("plan not found");
+        assert!(plan.donation, "fixed price plan");
+        assert!(amount >= plan.amount, "below minimum");
+        Self::assert_amount_bounds(&env, &amount);
+        Self::track_supporter(&env, &plan.merchant, sub.plan_id, &sub.amount, false);
+        Self::track_supporter(&env, &plan.merchant, sub.plan_id, &amount, true);
+        Self::count_plan_subscriber(&env, sub.plan_id, &Self::seat_total(&env, &sub), false);
+        sub.amount = amount;
+        Self::count_plan_subscriber(&env, sub.plan_id, &Self::seat_total(&env, &sub), true);
+        subs.set(key, sub);
+        env.storage().instance().set(&SUBS, &subs);
+        Self::emit(&env, (symbol_short!("DonAmt"), subscription_id), amount);
+    }
+
+    pub fn get_donation_tiers(env: Env, plan_id: u64) -> Vec<DonationTier> {
+        let all: Map<u64, Vec<DonationTier>> = env
+            .storage()
+            .instance()
+            .get(&TIERS)
+            .unwrap_or(Map::new(&env));
+        all.get(plan_id).unwrap_or(Vec::new(&env))
+    }
+
+    // Active supporters per tier name, aggregated across the merchant's donation plans.
+    pub fn supporter_counts(env: Env, merchant: Address) -> Map<Symbol, u32> {
+        let all: Map<Address, Map<Symbol, u32>> = env
+            .storage()
+            .instance()
+            .get(&SUPP)
+            .unwrap_or(Map::new(&env));
+        all.get(merchant).unwrap_or(Map::new(&env))
+    }
+
+    pub(crate) fn track_supporter(
+        env: &Env,
+        merchant: &Address,
+        plan_id: u64,
+        amount: &i128,
+        joined: bool,
+    ) {
+        let tier = match Self::tier_for(env, plan_id, amount) {
+            Some(t) => t,
+            None => return,
+        };
+        let mut all: Map<Address, Map<Symbol, u32>> =
+            env.storage().instance().get(&SUPP).unwrap_or(Map::new(env));
+        let mut counts = all.get(merchant.clone()).unwrap_or(Map::new(env));
+        let n = counts.get(tier.clone()).unwrap_or(0);
+        counts.set(tier, if joined { n + 1 } else { n - 1 });
+        all.set(merchant.clone(), counts);
+        env.storage().instance().set(&SUPP, &all);
+    }
+
+    fn tier_for(env: &Env, plan_id: u64, amount: &i128) -> Option<Symbol> {
+        let mut found = None;
+        for tier in Self::get_donation_tiers(env.clone(), plan_id).iter() {
+            if *amount >= tier.min_amount {
+                found = Some(tier.name);
+            }
+        }
+        found
+    }
+}
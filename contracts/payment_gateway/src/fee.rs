@@ -0,0 +1,123 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Symbol, Vec};
+
+use crate::{AdminAction, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+const FEEBPS: Symbol = symbol_short!("FEEBPS");
+const TREAS: Symbol = symbol_short!("TREAS");
+// protocol fees accrued in the contract and not yet collected
+const FEES: Symbol = symbol_short!("FEES");
+// (recipient, bps) shares fees are split by on collection, in place of the treasury
+const FSHARES: Symbol = symbol_short!("FSHARES");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_fee(env: Env, invoker: Address, fee_bps: u32, treasury: Address) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        let action = AdminAction::SetFee(fee_bps, treasury.clone());
+        Self::assert_not_governed(&env, &action);
+        Self::assert_not_timelocked(&env, &action);
+        Self::store_fee(&env, fee_bps, treasury);
+    }
+
+    pub fn get_fee_bps(env: Env) -> u32 {
+        env.storage().instance().get(&FEEBPS).unwrap_or(0)
+    }
+
+    pub fn get_treasury(env: Env) -> Option<Address> {
+        env.storage().instance().get(&TREAS)
+    }
+
+    pub fn accrued_fees(env: Env) -> i128 {
+        env.storage().instance().get(&FEES).unwrap_or(0)
+    }
+
+    // An empty table sends all fees to the treasury again.
+    pub fn set_fee_shares(env: Env, invoker: Address, shares: Vec<(Address, u32)>) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        Self::assert_not_governed(&env, &AdminAction::SetFeeShares(shares.clone()));
+        Self::store_fee_shares(&env, shares);
+    }
+
+    // The share table, or the whole of it to the treasury when none is set.
+    pub fn get_fee_shares(env: Env) -> Vec<(Address, u32)> {
+        let shares: Vec<(Address, u32)> = env
+            .storage()
+            .instance()
+            .get(&FSHARES)
+            .unwrap_or(Vec::new(&env));
+        if !shares.is_empty() {
+            return shares;
+        }
+        match Self::get_treasury(env.clone()) {
+            Some(treasury) => Vec::from_array(&env, [(treasury, 10_000)]),
+            None => shares,
+        }
+    }
+
+    // Open to anyone once ownership is renounced; fees only ever go to the share table.
+    // Rounding dust goes to the last recipient.
+    pub fn collect_fees(env: Env, invoker: Address) -> i128 {
+        if !Self::is_renounced(env.clone()) {
+            Self::only_owner(&env, &invoker);
+        }
+        let shares = Self::get_fee_shares(env.clone());
+        assert!(!shares.is_empty(), "no treasury");
+        let fees = Self::accrued_fees(env.clone());
+        assert!(fees > 0, "no fees");
+        env.storage().instance().set(&FEES, &0);
+        let mut left = fees;
+        for (i, (to, bps)) in shares.iter().enumerate() {
+            let cut = if i as u32 == shares.len() - 1 {
+                left
+            } else {
+                Self::bps_of(&env, &fees, bps)
+            };
+            left -= cut;
+            Self::payout(&env, &to, &cut);
+            Self::emit(&env, (symbol_short!("FeeColl"), to), cut);
+        }
+        fees
+    }
+
+    pub(crate) fn store_fee_shares(env: &Env, shares: Vec<(Address, u32)>) {
+        if !shares.is_empty() {
+            let mut total = 0u32;
+            for (i, (to, bps)) in shares.iter().enumerate() {
+                assert!(bps > 0, "share>0");
+                assert!(
+                    !shares.iter().skip(i + 1).any(|(other, _)| other == to),
+                    "duplicate recipient"
+                );
+                total += bps;
+            }
+            assert!(total == 10_000, "shares must total 10000");
+        }
+        env.storage().instance().set(&FSHARES, &shares);
+        Self::emit(env, (symbol_short!("FeeShare"),), shares);
+    }
+
+    pub(crate) fn store_fee(env: &Env, fee_bps: u32, treasury: Address) {
+        assert!(fee_bps <= 10_000, "bps<=10000");
+        env.storage().instance().set(&FEEBPS, &fee_bps);
+        env.storage().instance().set(&TREAS, &treasury);
+        Self::emit(env, (symbol_short!("FeeSet"),), (fee_bps, treasury));
+    }
+
+    pub(crate) fn fee_for(env: &Env, amount: &i128) -> i128 {
+        Self::bps_of(env, amount, Self::get_fee_bps(env.clone()))
+    }
+
+    // Books a fee that is already sitting in the contract's token balance.
+    pub(crate) fn accrue_fee(env: &Env, fee: &i128) {
+        let insured = Self::insure_from_fee(env, fee);
+        Self::add_fees(env, &(fee - insured));
+        Self::record_fee(env, fee);
+    }
+
+    pub(crate) fn add_fees(env: &Env, amount: &i128) {
+        let fees = Self::accrued_fees(env.clone()) + amount;
+        env.storage().instance().set(&FEES, &fees);
+    }
+}
@@ -0,0 +1,188 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReportStatus {
+    Open,
+    Upheld,
+    Dismissed,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FraudReport {
+    receipt_id: u32,
+    reporter: Address,
+    merchant: Address,
+    claim: i128,
+    reason: Symbol,
+    status: ReportStatus,
+    compensation: i128,
+    arbiter: Option<Address>,
+}
+
+const FRCTR: Symbol = symbol_short!("FRCTR");
+const FRPT: Symbol = symbol_short!("FRPT");
+// receipt id -> report id, so a receipt can only be reported once
+const FRRCPT: Symbol = symbol_short!("FRRCPT");
+// (FROPEN, merchant) -> number of the merchant's reports still awaiting a decision
+const FROPEN: Symbol = symbol_short!("FROPEN");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn file_fraud_report(
+        env: Env,
+        invoker: Address,
+        receipt_id: u32,
+        claim: i128,
+        reason: Symbol,
+    ) -> u32 {
+        invoker.require_auth();
+        let receipt = Self::get_receipt(env.clone(), receipt_id);
+        assert!(
+            receipt.customer == invoker || receipt.payer == invoker,
+            "not customer"
+        );
+        assert!(claim > 0, "claim>0");
+        assert!(claim <= receipt.amount, "claim exceeds payment");
+        let mut by_receipt: Map<u32, u32> = env
+            .storage()
+            .instance()
+            .get(&FRRCPT)
+            .unwrap_or(Map::new(&env));
+        assert!(!by_receipt.contains_key(receipt_id), "already reported");
+        let ctr = Self::next_id(&env, &FRCTR);
+        by_receipt.set(receipt_id, ctr);
+        env.storage().instance().set(&FRRCPT, &by_receipt);
+        Self::save_fraud_report(
+            &env,
+            ctr,
+            &FraudReport {
+                receipt_id,
+                reporter: invoker,
+                merchant: receipt.merchant.clone(),
+                claim,
+                reason,
+                status: ReportStatus::Open,
+                compensation: 0,
+                arbiter: None,
+            },
+        );
+        Self::count_open_report(&env, &receipt.merchant, true);
+        Self::emit(
+            &env,
+            (symbol_short!("FrFiled"), ctr),
+            (receipt_id, receipt.merchant),
+        );
+        ctr
+    }
+
+    // The owner hands an open report to a registered arbiter, who then rules on it instead of
+    // the owner.
+    pub fn assign_report_arbiter(env: Env, invoker: Address, report_id: u32, arbiter: Address) {
+        Self::only_owner(&env, &invoker);
+        assert!(
+            Self::is_arbiter(env.clone(), arbiter.clone()),
+            "not arbiter"
+        );
+        let mut report = Self::get_fraud_report(env.clone(), report_id);
+        assert!(report.status == ReportStatus::Open, "already decided");
+        report.arbiter = Some(arbiter.clone());
+        Self::save_fraud_report(&env, report_id, &report);
+        Self::emit(&env, (symbol_short!("FrAssign"), report_id), arbiter);
+    }
+
+    // The assigned arbiter, or the owner, decides. An upheld report slashes up to `slash`
+    // (bounded by the claim, the part of the payment not yet refunded and the bond) to the
+    // reporter, topped up from the insurance pool when the bond falls short; the compensation
+    // counts as refunded. An arbiter's ruling counts as one of its cases and its fee is taken
+    // from the slashed bond.
+    pub fn adjudicate_report(
+        env: Env,
+        invoker: Address,
+        report_id: u32,
+        uphold: bool,
+        slash: i128,
+    ) -> i128 {
+        let mut report = Self::get_fraud_report(env.clone(), report_id);
+        let arbiter = report.arbiter.clone().filter(|a| *a == invoker);
+        if arbiter.is_some() {
+            invoker.require_auth();
+        } else {
+            Self::only_owner(&env, &invoker);
+        }
+        assert!(report.status == ReportStatus::Open, "already decided");
+        Self::count_open_report(&env, &report.merchant, false);
+        if !uphold {
+            if let Some(arbiter) = &arbiter {
+                Self::pay_arbiter(&env, arbiter, &0);
+            }
+            report.status = ReportStatus::Dismissed;
+            Self::save_fraud_report(&env, report_id, &report);
+            Self::emit(&env, (symbol_short!("FrDism"), report_id), report_id);
+            return report.compensation;
+        }
+        assert!(slash >= 0, "slash>=0");
+        let mut receipt = Self::get_receipt(env.clone(), report.receipt_id);
+        let max = slash
+            .min(report.claim)
+            .min(receipt.amount - receipt.refunded);
+        let slashed = Self::slash_bond(&env, &report.merchant, &max);
+        let awarded = match &arbiter {
+            Some(arbiter) => Self::pay_arbiter(&env, arbiter, &slashed),
+            None => slashed,
+        };
+        report.status = ReportStatus::Upheld;
+        report.compensation = awarded;
+        Self::save_fraud_report(&env, report_id, &report);
+        if slashed > 0 {
+            Self::payout(&env, &report.reporter, &awarded);
+            Self::emit(
+                &env,
+                (symbol_short!("BondSlsh"), report.merchant.clone()),
+                slashed,
+            );
+        }
+        // the insurance pool covers what the bond could not
+        let insured = Self::pay_insurance(&env, report_id, &report.reporter, &(max - slashed));
+        let amount = awarded + insured;
+        report.compensation = amount;
+        Self::save_fraud_report(&env, report_id, &report);
+        receipt.refunded += amount;
+        Self::save_receipt(&env, report.receipt_id, &receipt);
+        Self::emit(&env, (symbol_short!("FrUphld"), report_id), amount);
+        amount
+    }
+
+    pub fn get_fraud_report(env: Env, report_id: u32) -> FraudReport {
+        let reports: Map<u32, FraudReport> = env
+            .storage()
+            .instance()
+            .get(&FRPT)
+            .unwrap_or(Map::new(&env));
+        reports.get(report_id).expect("no report")
+    }
+
+    pub(crate) fn open_reports(env: &Env, merchant: &Address) -> u32 {
+        Self::get_record(env, &(FROPEN, merchant.clone())).unwrap_or(0)
+    }
+
+    fn count_open_report(env: &Env, merchant: &Address, open: bool) {
+        let n = Self::open_reports(env, merchant);
+        let key = (FROPEN, merchant.clone());
+        match (open, n) {
+            (true, _) => Self::put_record(env, &key, &(n + 1)),
+            (false, 0 | 1) => Self::drop_record(env, &key),
+            (false, _) => Self::put_record(env, &key, &(n - 1)),
+        }
+    }
+
+    fn save_fraud_report(env: &Env, report_id: u32, report: &FraudReport) {
+        let mut reports: Map<u32, FraudReport> =
+            env.storage().instance().get(&FRPT).unwrap_or(Map::new(env));
+        reports.set(report_id, report.clone());
+        env.storage().instance().set(&FRPT, &reports);
+    }
+}
@@ -0,0 +1,59 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Symbol};
+
+use crate::{AdminAction, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// contract (typically a DAO) that alone controls fees, the insurance share, the token and
+// pausing once set
+const GOVERNOR: Symbol = symbol_short!("GOVERNOR");
+
+#[contractimpl]
+impl PaymentGateway {
+    // One-off hand-over; afterwards only the governor can replace or clear itself.
+    pub fn set_governor(env: Env, invoker: Address, governor: Address) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        assert!(Self::get_governor(env.clone()).is_none(), "governor set");
+        Self::store_governor(&env, Some(governor));
+    }
+
+    pub fn get_governor(env: Env) -> Option<Address> {
+        env.storage().instance().get(&GOVERNOR)
+    }
+
+    // Sensitive changes still wait out the timelock delay.
+    pub fn execute_param_change(env: Env, action: AdminAction) {
+        let governor = Self::get_governor(env.clone()).expect("no governor");
+        governor.require_auth();
+        assert!(Self::is_governed(&action), "not a governed param");
+        Self::emit(&env, (symbol_short!("GovExec"),), action.clone());
+        Self::run_or_schedule(&env, action, None);
+    }
+
+    // Keeps the owner and multisig off the parameters a governor has taken over.
+    pub(crate) fn assert_not_governed(env: &Env, action: &AdminAction) {
+        assert!(
+            Self::get_governor(env.clone()).is_none() || !Self::is_governed(action),
+            "governed"
+        );
+    }
+
+    pub(crate) fn store_governor(env: &Env, governor: Option<Address>) {
+        match &governor {
+            Some(g) => env.storage().instance().set(&GOVERNOR, g),
+            None => env.storage().instance().remove(&GOVERNOR),
+        }
+        Self::emit(env, (symbol_short!("Governor"),), governor);
+    }
+
+    fn is_governed(action: &AdminAction) -> bool {
+        matches!(
+            action,
+            AdminAction::SetFee(..)
+                | AdminAction::SetFeeShares(_)
+                | AdminAction::SetToken(_)
+                | AdminAction::SetPaused(_)
+                | AdminAction::SetGovernor(_)
+                | AdminAction::SetInsurance(..)
+        )
+    }
+}
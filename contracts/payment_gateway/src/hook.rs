@@ -0,0 +1,166 @@
+use soroban_sdk::{
+    contractimpl, symbol_short, Address, BytesN, Env, IntoVal, InvokeError, Map, Symbol, Vec,
+};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Receipt, LOCK};
+
+// merchant -> contract implementing `on_payment(receipt_id: u32, receipt: Receipt)`
+const HOOKS: Symbol = symbol_short!("HOOKS");
+// owner-approved hook wasm hashes, and the hash each gateway-deployed hook was built from
+const HOOKWASM: Symbol = symbol_short!("HOOKWASM");
+const HOOKSRC: Symbol = symbol_short!("HOOKSRC");
+// merchants whose payments revert when their hook fails
+const HSTRICT: Symbol = symbol_short!("HSTRICT");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn allow_hook_wasm(env: Env, invoker: Address, wasm_hash: BytesN<32>) {
+        Self::only_owner(&env, &invoker);
+        let mut allowed = Self::hook_wasms(&env);
+        allowed.set(wasm_hash.clone(), true);
+        env.storage().instance().set(&HOOKWASM, &allowed);
+        Self::emit(&env, (symbol_short!("HookAllow"),), wasm_hash);
+    }
+
+    // Revoking a hash disables every hook deployed from it.
+    pub fn revoke_hook_wasm(env: Env, invoker: Address, wasm_hash: BytesN<32>) {
+        Self::only_owner(&env, &invoker);
+        let mut allowed = Self::hook_wasms(&env);
+        assert!(allowed.contains_key(wasm_hash.clone()), "not allowed");
+        allowed.remove(wasm_hash.clone());
+        env.storage().instance().set(&HOOKWASM, &allowed);
+        Self::emit(&env, (symbol_short!("HookRevok"),), wasm_hash);
+    }
+
+    pub fn is_hook_wasm_allowed(env: Env, wasm_hash: BytesN<32>) -> bool {
+        Self::hook_wasms(&env).contains_key(wasm_hash)
+    }
+
+    // Hooks are deployed by the gateway so the code behind each one is known to be approved.
+    pub fn deploy_payment_hook(
+        env: Env,
+        invoker: Address,
+        wasm_hash: BytesN<32>,
+        salt: BytesN<32>,
+    ) -> Address {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not merchant");
+        assert!(
+            Self::hook_wasms(&env).contains_key(wasm_hash.clone()),
+            "hook not allowed"
+        );
+        let hook = env
+            .deployer()
+            .with_current_contract(salt)
+            .deploy_v2(wasm_hash.clone(), ());
+        let mut sources: Map<Address, BytesN<32>> = env
+            .storage()
+            .instance()
+            .get(&HOOKSRC)
+            .unwrap_or(Map::new(&env));
+        sources.set(hook.clone(), wasm_hash);
+        env.storage().instance().set(&HOOKSRC, &sources);
+        Self::set_payment_hook(env.clone(), invoker, Some(hook.clone()));
+        hook
+    }
+
+    pub fn set_payment_hook(env: Env, invoker: Address, hook: Option<Address>) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not merchant");
+        let mut hooks: Map<Address, Address> = env
+            .storage()
+            .instance()
+            .get(&HOOKS)
+            .unwrap_or(Map::new(&env));
+        match hook.clone() {
+            Some(h) => {
+                assert!(Self::hook_source(&env, &h).is_some(), "hook not deployed");
+                hooks.set(invoker.clone(), h)
+            }
+            None => {
+                hooks.remove(invoker.clone());
+            }
+        }
+        env.storage().instance().set(&HOOKS, &hooks);
+        Self::emit(&env, (symbol_short!("HookSet"), invoker), hook);
+    }
+
+    pub fn get_payment_hook(env: Env, merchant: Address) -> Option<Address> {
+        let hooks: Map<Address, Address> = env
+            .storage()
+            .instance()
+            .get(&HOOKS)
+            .unwrap_or(Map::new(&env));
+        hooks.get(merchant)
+    }
+
+    pub fn set_hook_strict(env: Env, invoker: Address, strict: bool) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not merchant");
+        let mut merchants: Map<Address, bool> = env
+            .storage()
+            .instance()
+            .get(&HSTRICT)
+            .unwrap_or(Map::new(&env));
+        if strict {
+            merchants.set(invoker.clone(), true);
+        } else {
+            merchants.remove(invoker.clone());
+        }
+        env.storage().instance().set(&HSTRICT, &merchants);
+        Self::emit(&env, (symbol_short!("HookStrct"), invoker), strict);
+    }
+
+    pub fn is_hook_strict(env: Env, merchant: Address) -> bool {
+        let merchants: Map<Address, bool> = env
+            .storage()
+            .instance()
+            .get(&HSTRICT)
+            .unwrap_or(Map::new(&env));
+        merchants.contains_key(merchant)
+    }
+
+    // Runs after every recorded payment. The hook cannot re-enter the gateway. A failing hook
+    // reverts the payment for strict merchants and is otherwise skipped with a `HookFail` event.
+    // Hooks whose wasm hash has been revoked are not called at all.
+    pub(crate) fn run_payment_hook(env: &Env, receipt_id: u32, receipt: &Receipt) {
+        let hook = match Self::get_payment_hook(env.clone(), receipt.merchant.clone()) {
+            Some(h) => h,
+            None => return,
+        };
+        match Self::hook_source(env, &hook) {
+            Some(hash) if Self::hook_wasms(env).contains_key(hash.clone()) => {}
+            _ => return,
+        }
+        env.storage().instance().set(&LOCK, &true);
+        let res = env.try_invoke_contract::<(), InvokeError>(
+            &hook,
+            &Symbol::new(env, "on_payment"),
+            Vec::from_array(env, [receipt_id.into_val(env), receipt.into_val(env)]),
+        );
+        env.storage().instance().remove(&LOCK);
+        if !matches!(res, Ok(Ok(()))) {
+            assert!(
+                !Self::is_hook_strict(env.clone(), receipt.merchant.clone()),
+                "hook failed"
+            );
+            Self::emit(env, (symbol_short!("HookFail"), receipt_id), hook);
+        }
+    }
+
+    fn hook_wasms(env: &Env) -> Map<BytesN<32>, bool> {
+        env.storage()
+            .instance()
+            .get(&HOOKWASM)
+            .unwrap_or(Map::new(env))
+    }
+
+    fn hook_source(env: &Env, hook: &Address) -> Option<BytesN<32>> {
+        let sources: Map<Address, BytesN<32>> = env
+            .storage()
+            .instance()
+            .get(&HOOKSRC)
+            .unwrap_or(Map::new(env));
+        sources.get(hook.clone())
+    }
+}
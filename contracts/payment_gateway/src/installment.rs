@@ -0,0 +1,280 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Timepoint};
+
+use crate::{
+    LateFeePolicy, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink,
+    PaymentSource, Receipt, PLINK,
+};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallmentTerms {
+    count: u32,
+    interval: u32,
+    grace: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InstallmentStatus {
+    Active,
+    // the next installment fell due unpaid; charging it adds the late fee
+    PastDue,
+    Completed,
+    Defaulted,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallmentPlan {
+    link_id: u64,
+    merchant: Address,
+    payer: Address,
+    total: i128,
+    installment: i128,
+    count: u32,
+    paid: u32,
+    interval: u32,
+    grace: u32,
+    start_time: Timepoint,
+    status: InstallmentStatus,
+    // the merchant's late fee policy when the plan was taken out; all zero if it had none
+    late_fee: LateFeePolicy,
+}
+
+const ITERM: Symbol = symbol_short!("ITERM");
+const ICTR: Symbol = symbol_short!("ICTR");
+const IPLAN: Symbol = symbol_short!("IPLAN");
+
+impl InstallmentPlan {
+    fn next_due(&self, env: &Env) -> u64 {
+        let elapsed = (self.paid as u64).checked_mul(self.interval as u64);
+        let due = elapsed.and_then(|e| self.start_time.to_unix().checked_add(e));
+        PaymentGateway::checked_time(env, due)
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(
+            self.status,
+            InstallmentStatus::Active | InstallmentStatus::PastDue
+        )
+    }
+
+    // The last installment absorbs the rounding remainder.
+    fn amount_for(&self, env: &Env, index: u32) -> i128 {
+        if index + 1 == self.count {
+            let earlier = self.installment.checked_mul((self.count - 1) as i128);
+            PaymentGateway::checked(env, earlier.and_then(|e| self.total.checked_sub(e)))
+        } else {
+            self.installment
+        }
+    }
+}
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn enable_installments(
+        env: Env,
+        invoker: Address,
+        link_id: u64,
+        count: u32,
+        interval: u32,
+        grace: u32,
+    ) {
+        invoker.require_auth();
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("no link");
+        assert!(link.merchant == invoker, "not merchant");
+        assert!(count > 1, "count>1");
+        assert!(interval > 0, "interval>0");
+        let mut terms: Map<u64, InstallmentTerms> = env
+            .storage()
+            .instance()
+            .get(&ITERM)
+            .unwrap_or(Map::new(&env));
+        terms.set(
+            link_id,
+            InstallmentTerms {
+                count,
+                interval,
+                grace,
+            },
+        );
+        env.storage().instance().set(&ITERM, &terms);
+        Self::emit(&env, (symbol_short!("IEnable"), link_id), count);
+    }
+
+    pub fn disable_installments(env: Env, invoker: Address, link_id: u64) {
+        invoker.require_auth();
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("no link");
+        assert!(link.merchant == invoker, "not merchant");
+        let mut terms: Map<u64, InstallmentTerms> = env
+            .storage()
+            .instance()
+            .get(&ITERM)
+            .unwrap_or(Map::new(&env));
+        assert!(terms.contains_key(link_id), "installments off");
+        terms.remove(link_id);
+        env.storage().instance().set(&ITERM, &terms);
+        Self::emit(&env, (symbol_short!("IDisable"), link_id), link_id);
+    }
+
+    pub fn pay_in_installments(env: Env, invoker: Address, link_id: u64) -> u32 {
+        invoker.require_auth();
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("link not found");
+        assert!(link.active, "inactive link");
+        Self::assert_no_seller(&env, link_id);
+        Self::assert_not_blocked(&env, &link.merchant, &invoker);
+        Self::assert_allowed(&env, link_id, &invoker);
+        Self::count_purchase(&env, link_id, &invoker);
+        let terms: Map<u64, InstallmentTerms> = env
+            .storage()
+            .instance()
+            .get(&ITERM)
+            .unwrap_or(Map::new(&env));
+        let t = terms.get(link_id).expect("installments off");
+        let installment = link.amount / t.count as i128;
+        assert!(installment > 0, "amount too small");
+        let ctr = Self::next_id(&env, &ICTR);
+        let mut plan = InstallmentPlan {
+            link_id,
+            merchant: link.merchant.clone(),
+            payer: invoker,
+            total: link.amount,
+            installment,
+            count: t.count,
+            paid: 0,
+            interval: t.interval,
+            grace: t.grace,
+            start_time: Timepoint::from_unix(&env, env.ledger().timestamp()),
+            status: InstallmentStatus::Active,
+            late_fee: Self::late_fee_snapshot(&env, &link.merchant),
+        };
+        let payer = plan.payer.clone();
+        Self::charge_installment_now(&env, &payer, ctr, &mut plan);
+        Self::emit(&env, (symbol_short!("InstCr"), link_id), ctr);
+        ctr
+    }
+
+    // Later installments are pulled against the payer's allowance to the gateway, so anyone
+    // may trigger a due one.
+    pub fn charge_installment(env: Env, invoker: Address, plan_id: u32) {
+        invoker.require_auth();
+        let mut plan = Self::get_installment_plan(env.clone(), plan_id);
+        assert!(plan.is_open(), "plan not active");
+        assert!(env.ledger().timestamp() >= plan.next_due(&env), "not due");
+        Self::charge_installment_now(&env, &env.current_contract_address(), plan_id, &mut plan);
+    }
+
+    // Anyone may flag a plan whose next installment is due but unpaid.
+    pub fn mark_installment_past_due(env: Env, plan_id: u32) {
+        let mut plan = Self::get_installment_plan(env.clone(), plan_id);
+        assert!(plan.status == InstallmentStatus::Active, "plan not active");
+        assert!(env.ledger().timestamp() >= plan.next_due(&env), "not due");
+        plan.status = InstallmentStatus::PastDue;
+        Self::save_installment_plan(&env, plan_id, &plan);
+        Self::emit(&env, (symbol_short!("InstLate"), plan_id), plan.paid);
+    }
+
+    // Anyone may flag a plan whose next installment is past its grace period.
+    pub fn mark_installment_default(env: Env, plan_id: u32) {
+        let mut plan = Self::get_installment_plan(env.clone(), plan_id);
+        assert!(plan.is_open(), "plan not active");
+        assert!(
+            env.ledger().timestamp() > plan.next_due(&env) + plan.grace as u64,
+            "within grace"
+        );
+        plan.status = InstallmentStatus::Defaulted;
+        Self::save_installment_plan(&env, plan_id, &plan);
+        Self::emit(
+            &env,
+            (symbol_short!("InstDflt"), plan_id),
+            (plan.paid, plan.count),
+        );
+    }
+
+    pub fn get_installment_plan(env: Env, plan_id: u32) -> InstallmentPlan {
+        let plans: Map<u32, InstallmentPlan> = env
+            .storage()
+            .instance()
+            .get(&IPLAN)
+            .unwrap_or(Map::new(&env));
+        plans.get(plan_id).expect("no installment plan")
+    }
+
+    fn charge_installment_now(
+        env: &Env,
+        spender: &Address,
+        plan_id: u32,
+        plan: &mut InstallmentPlan,
+    ) {
+        let amount = plan.amount_for(env, plan.paid);
+        let late_fee = if plan.status == InstallmentStatus::PastDue {
+            Self::late_fee_under(env, &plan.late_fee, &amount, plan.next_due(env))
+        } else {
+            0
+        };
+        let fee = Self::settle(
+            env,
+            spender,
+            &plan.payer,
+            &plan.merchant,
+            &(amount + late_fee),
+        );
+        plan.paid += 1;
+        plan.status = if plan.paid == plan.count {
+            InstallmentStatus::Completed
+        } else {
+            InstallmentStatus::Active
+        };
+        Self::save_installment_plan(env, plan_id, plan);
+        let receipt = Receipt {
+            late_fee,
+            fee,
+            ..Receipt::new(
+                env,
+                PaymentSource::Link(plan.link_id),
+                plan.merchant.clone(),
+                plan.payer.clone(),
+                plan.payer.clone(),
+                amount,
+            )
+        };
+        let receipt_id = Self::record_receipt(env, &receipt);
+        Self::emit(
+            env,
+            (symbol_short!("InstPay"), plan_id),
+            (
+                plan.paid,
+                plan.count,
+                Self::payment_event(env, receipt_id, &receipt),
+            ),
+        );
+        if plan.status == InstallmentStatus::Completed {
+            Self::emit(env, (symbol_short!("InstDone"), plan_id), plan_id);
+        }
+    }
+
+    fn save_installment_plan(env: &Env, plan_id: u32, plan: &InstallmentPlan) {
+        let mut plans: Map<u32, InstallmentPlan> = env
+            .storage()
+            .instance()
+            .get(&IPLAN)
+            .unwrap_or(Map::new(env));
+        plans.set(plan_id, plan.clone());
+        env.storage().instance().set(&IPLAN, &plans);
+    }
+}
@@ -0,0 +1,94 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Symbol};
+
+use crate::{AdminAction, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// `fee_share_bps` of every protocol fee goes into the pool; an upheld report draws at most
+// `cap` from it for whatever the merchant's bond did not cover.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InsuranceConfig {
+    fee_share_bps: u32,
+    cap: i128,
+}
+
+const INSCFG: Symbol = symbol_short!("INSCFG");
+const INSPOOL: Symbol = symbol_short!("INSPOOL");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_insurance(env: Env, invoker: Address, fee_share_bps: u32, cap: i128) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        Self::assert_not_governed(&env, &AdminAction::SetInsurance(fee_share_bps, cap));
+        Self::store_insurance(&env, fee_share_bps, cap);
+    }
+
+    pub fn get_insurance(env: Env) -> Option<InsuranceConfig> {
+        env.storage().instance().get(&INSCFG)
+    }
+
+    pub fn insurance_pool(env: Env) -> i128 {
+        env.storage().instance().get(&INSPOOL).unwrap_or(0)
+    }
+
+    // Anyone may top the pool up on top of its fee share.
+    pub fn fund_insurance(env: Env, invoker: Address, amount: i128) {
+        invoker.require_auth();
+        assert!(amount > 0, "amount>0");
+        Self::set_pool(&env, &(Self::insurance_pool(env.clone()) + amount));
+        Self::transfer_from(
+            &env,
+            &invoker,
+            &invoker,
+            &env.current_contract_address(),
+            &amount,
+        );
+        Self::emit(&env, (symbol_short!("InsFund"), invoker), amount);
+    }
+
+    pub(crate) fn store_insurance(env: &Env, fee_share_bps: u32, cap: i128) {
+        assert!(fee_share_bps <= 10_000, "bps<=10000");
+        assert!(cap >= 0, "cap>=0");
+        env.storage()
+            .instance()
+            .set(&INSCFG, &InsuranceConfig { fee_share_bps, cap });
+        Self::emit(env, (symbol_short!("InsCfg"),), (fee_share_bps, cap));
+    }
+
+    // Moves the pool's share out of a freshly accrued fee and returns it.
+    pub(crate) fn insure_from_fee(env: &Env, fee: &i128) -> i128 {
+        let share = match Self::get_insurance(env.clone()) {
+            Some(cfg) => Self::bps_of(env, fee, cfg.fee_share_bps),
+            None => return 0,
+        };
+        Self::set_pool(env, &(Self::insurance_pool(env.clone()) + share));
+        share
+    }
+
+    // Pays up to `shortfall` (bounded by the cap and the pool) to `to`; returns the amount paid.
+    pub(crate) fn pay_insurance(env: &Env, report_id: u32, to: &Address, shortfall: &i128) -> i128 {
+        let cap = match Self::get_insurance(env.clone()) {
+            Some(cfg) => cfg.cap,
+            None => return 0,
+        };
+        let pool = Self::insurance_pool(env.clone());
+        let mut amount = *shortfall;
+        if amount > cap {
+            amount = cap;
+        }
+        if amount > pool {
+            amount = pool;
+        }
+        if amount <= 0 {
+            return 0;
+        }
+        Self::set_pool(env, &(pool - amount));
+        Self::payout(env, to, &amount);
+        Self::emit(env, (symbol_short!("InsPaid"), report_id), amount);
+        amount
+    }
+
+    fn set_pool(env: &Env, amount: &i128) {
+        env.storage().instance().set(&INSPOOL, amount);
+    }
+}
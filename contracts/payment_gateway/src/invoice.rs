@@ -0,0 +1,186 @@
+use soroban_sdk::{
+    contractimpl, contracttype, symbol_short, Address, BytesN, Env, Map, Symbol, Timepoint, Vec,
+};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentSource, Receipt};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LineItem {
+    pub description: Symbol,
+    pub quantity: u32,
+    pub unit_price: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InvoiceStatus {
+    Open,
+    Paid,
+    Overdue,
+    Void,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Invoice {
+    merchant: Address,
+    customer: Address,
+    items: Vec<LineItem>,
+    total: i128,
+    due_date: Timepoint,
+    status: InvoiceStatus,
+}
+
+const INVCTR: Symbol = symbol_short!("INVCTR");
+const INVC: Symbol = symbol_short!("INVC");
+// invoice id -> hash of the off-chain order, carried onto the receipt when paid
+const INVORDER: Symbol = symbol_short!("INVORDER");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn create_invoice(
+        env: Env,
+        invoker: Address,
+        customer: Address,
+        items: Vec<LineItem>,
+        due_date: u64,
+    ) -> u32 {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(!items.is_empty(), "no items");
+        assert!(due_date > env.ledger().timestamp(), "due in past");
+        let mut total: i128 = 0;
+        for item in items.iter() {
+            assert!(item.quantity > 0, "quantity>0");
+            assert!(item.unit_price > 0, "price>0");
+            let line = Self::checked(&env, item.unit_price.checked_mul(item.quantity as i128));
+            total = Self::checked(&env, total.checked_add(line));
+        }
+        Self::assert_min_price(&env, &total);
+        Self::assert_amount_bounds(&env, &total);
+        let ctr = Self::next_id(&env, &INVCTR);
+        Self::save_invoice(
+            &env,
+            ctr,
+            &Invoice {
+                merchant: invoker,
+                customer: customer.clone(),
+                items,
+                total,
+                due_date: Timepoint::from_unix(&env, due_date),
+                status: InvoiceStatus::Open,
+            },
+        );
+        Self::emit(&env, (symbol_short!("InvCr"), ctr), &customer);
+        ctr
+    }
+
+    pub fn pay_invoice(env: Env, invoker: Address, invoice_id: u32) -> u32 {
+        invoker.require_auth();
+        let mut invoice = Self::get_invoice(env.clone(), invoice_id);
+        assert!(invoice.customer == invoker, "not addressee");
+        assert!(
+            invoice.status == InvoiceStatus::Open || invoice.status == InvoiceStatus::Overdue,
+            "not payable"
+        );
+        invoice.status = InvoiceStatus::Paid;
+        Self::save_invoice(&env, invoice_id, &invoice);
+        let fee = Self::settle(&env, &invoker, &invoker, &invoice.merchant, &invoice.total);
+        // A hash another payment has claimed since stays on the invoice but not the receipt.
+        let order_id = Self::get_invoice_order(env.clone(), invoice_id).filter(|order_id| {
+            Self::get_order_receipt(env.clone(), invoice.merchant.clone(), order_id.clone())
+                .is_none()
+        });
+        let receipt = Receipt {
+            order_id: order_id.clone(),
+            fee,
+            ..Receipt::new(
+                &env,
+                PaymentSource::Invoice(invoice_id),
+                invoice.merchant,
+                invoker.clone(),
+                invoker,
+                invoice.total,
+            )
+        };
+        let receipt_id = Self::record_receipt(&env, &receipt);
+        if let Some(order_id) = order_id {
+            Self::index_order(&env, &receipt.merchant, order_id, receipt_id);
+        }
+        Self::emit(
+            &env,
+            (symbol_short!("InvPaid"), invoice_id),
+            Self::payment_event(&env, receipt_id, &receipt),
+        );
+        receipt_id
+    }
+
+    pub fn void_invoice(env: Env, invoker: Address, invoice_id: u32) {
+        invoker.require_auth();
+        let mut invoice = Self::get_invoice(env.clone(), invoice_id);
+        assert!(invoice.merchant == invoker, "not merchant");
+        assert!(
+            invoice.status == InvoiceStatus::Open || invoice.status == InvoiceStatus::Overdue,
+            "not open"
+        );
+        invoice.status = InvoiceStatus::Void;
+        Self::save_invoice(&env, invoice_id, &invoice);
+        Self::emit(&env, (symbol_short!("InvVoid"), invoice_id), invoice_id);
+    }
+
+    // The merchant attaches the order hash while the invoice is unpaid, once. It must not
+    // already belong to another of the merchant's payments.
+    pub fn set_invoice_order(env: Env, invoker: Address, invoice_id: u32, order_id: BytesN<32>) {
+        invoker.require_auth();
+        let invoice = Self::get_invoice(env.clone(), invoice_id);
+        assert!(invoice.merchant == invoker, "not merchant");
+        assert!(
+            invoice.status == InvoiceStatus::Open || invoice.status == InvoiceStatus::Overdue,
+            "not open"
+        );
+        assert!(
+            Self::get_order_receipt(env.clone(), invoker, order_id.clone()).is_none(),
+            "order exists"
+        );
+        let mut orders = Self::invoice_orders(&env);
+        assert!(!orders.contains_key(invoice_id), "order attached");
+        orders.set(invoice_id, order_id.clone());
+        env.storage().instance().set(&INVORDER, &orders);
+        Self::emit(&env, (symbol_short!("InvOrder"), invoice_id), order_id);
+    }
+
+    pub fn get_invoice_order(env: Env, invoice_id: u32) -> Option<BytesN<32>> {
+        Self::invoice_orders(&env).get(invoice_id)
+    }
+
+    // Overdue is derived from the due date rather than stored.
+    pub fn get_invoice(env: Env, invoice_id: u32) -> Invoice {
+        let invoices: Map<u32, Invoice> = env
+            .storage()
+            .instance()
+            .get(&INVC)
+            .unwrap_or(Map::new(&env));
+        let mut invoice = invoices.get(invoice_id).expect("no invoice");
+        if invoice.status == InvoiceStatus::Open
+            && env.ledger().timestamp() > invoice.due_date.to_unix()
+        {
+            invoice.status = InvoiceStatus::Overdue;
+        }
+        invoice
+    }
+
+    fn invoice_orders(env: &Env) -> Map<u32, BytesN<32>> {
+        env.storage()
+            .instance()
+            .get(&INVORDER)
+            .unwrap_or(Map::new(env))
+    }
+
+    fn save_invoice(env: &Env, invoice_id: u32, invoice: &Invoice) {
+        let mut invoices: Map<u32, Invoice> =
+            env.storage().instance().get(&INVC).unwrap_or(Map::new(env));
+        invoices.set(invoice_id, invoice.clone());
+        env.storage().instance().set(&INVC, &invoices);
+    }
+}
@@ -0,0 +1,27 @@
+use soroban_sdk::{contractimpl, symbol_short, Env, IntoVal, Symbol, Topics, Val};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+const ESEQ: Symbol = symbol_short!("ESEQ");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn last_event_seq(env: Env) -> u64 {
+        env.storage().instance().get(&ESEQ).unwrap_or(0)
+    }
+}
+
+impl PaymentGateway {
+    // Publishes `(seq, data)` so indexers can order events across topics and spot gaps.
+    pub(crate) fn emit<T, D>(env: &Env, topics: T, data: D)
+    where
+        T: Topics,
+        D: IntoVal<Env, Val>,
+    {
+        Self::assert_not_entered(env);
+        let seq = Self::last_event_seq(env.clone()) + 1;
+        env.storage().instance().set(&ESEQ, &seq);
+        let data: Val = data.into_val(env);
+        env.events().publish(topics, (seq, data));
+    }
+}
@@ -0,0 +1,80 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// owner-registered addresses allowed to trigger recurring charges for any merchant
+const KEEPERS: Symbol = symbol_short!("KEEPERS");
+// merchants that let any address trigger their subscription charges
+const OPENCHG: Symbol = symbol_short!("OPENCHG");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn add_keeper(env: Env, invoker: Address, keeper: Address) {
+        Self::only_owner(&env, &invoker);
+        let mut keepers = Self::get_keepers(env.clone());
+        assert!(!keepers.contains(&keeper), "already keeper");
+        keepers.push_back(keeper.clone());
+        env.storage().instance().set(&KEEPERS, &keepers);
+        Self::emit(&env, (symbol_short!("KeepAdd"),), &keeper);
+    }
+
+    pub fn remove_keeper(env: Env, invoker: Address, keeper: Address) {
+        Self::only_owner(&env, &invoker);
+        let mut keepers = Self::get_keepers(env.clone());
+        let i = keepers.first_index_of(&keeper).expect("not keeper");
+        keepers.remove(i);
+        env.storage().instance().set(&KEEPERS, &keepers);
+        Self::emit(&env, (symbol_short!("KeepRem"),), &keeper);
+    }
+
+    pub fn get_keepers(env: Env) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&KEEPERS)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn set_open_charging(env: Env, invoker: Address, open: bool) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not merchant");
+        let mut merchants: Map<Address, bool> = env
+            .storage()
+            .instance()
+            .get(&OPENCHG)
+            .unwrap_or(Map::new(&env));
+        if open {
+            merchants.set(invoker.clone(), true);
+        } else {
+            merchants.remove(invoker.clone());
+        }
+        env.storage().instance().set(&OPENCHG, &merchants);
+        Self::emit(&env, (symbol_short!("OpenChg"), invoker), open);
+    }
+
+    pub fn is_open_charging(env: Env, merchant: Address) -> bool {
+        let merchants: Map<Address, bool> = env
+            .storage()
+            .instance()
+            .get(&OPENCHG)
+            .unwrap_or(Map::new(&env));
+        merchants.contains_key(merchant)
+    }
+
+    // Unless the merchant opted into open charging, only the merchant (or its operators), the
+    // subscriber, or a registered keeper may trigger a charge.
+    pub(crate) fn assert_can_charge(
+        env: &Env,
+        invoker: &Address,
+        merchant: &Address,
+        subscriber: &Address,
+    ) {
+        assert!(
+            invoker == merchant
+                || invoker == subscriber
+                || Self::is_open_charging(env.clone(), merchant.clone())
+                || Self::get_keepers(env.clone()).contains(invoker)
+                || Self::get_operators(env.clone(), merchant.clone()).contains(invoker),
+            "not authorized"
+        );
+    }
+}
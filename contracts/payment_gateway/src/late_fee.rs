@@ -0,0 +1,106 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LateFeePolicy {
+    flat: i128,
+    bps: u32,
+    grace: u32,
+    cap: i128,
+}
+
+const LFEE: Symbol = symbol_short!("LFEE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_late_fee_policy(
+        env: Env,
+        invoker: Address,
+        flat: i128,
+        bps: u32,
+        grace: u32,
+        cap: i128,
+    ) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(flat >= 0, "flat>=0");
+        assert!(bps <= 10_000, "bps<=10000");
+        assert!(cap >= 0, "cap>=0");
+        let mut policies: Map<Address, LateFeePolicy> = env
+            .storage()
+            .instance()
+            .get(&LFEE)
+            .unwrap_or(Map::new(&env));
+        policies.set(
+            invoker.clone(),
+            LateFeePolicy {
+                flat,
+                bps,
+                grace,
+                cap,
+            },
+        );
+        env.storage().instance().set(&LFEE, &policies);
+        Self::emit(&env, (symbol_short!("LFeeSet"),), &invoker);
+    }
+
+    pub fn clear_late_fee_policy(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let mut policies: Map<Address, LateFeePolicy> = env
+            .storage()
+            .instance()
+            .get(&LFEE)
+            .unwrap_or(Map::new(&env));
+        assert!(policies.contains_key(invoker.clone()), "no policy");
+        policies.remove(invoker.clone());
+        env.storage().instance().set(&LFEE, &policies);
+        Self::emit(&env, (symbol_short!("LFeeClr"),), &invoker);
+    }
+
+    pub fn get_late_fee_policy(env: Env, merchant: Address) -> Option<LateFeePolicy> {
+        let policies: Map<Address, LateFeePolicy> = env
+            .storage()
+            .instance()
+            .get(&LFEE)
+            .unwrap_or(Map::new(&env));
+        policies.get(merchant)
+    }
+
+    // Fee owed under the merchant's current policy on a charge of `amount` that fell due at
+    // `due` (unix seconds). Callers only ask once the charge has been flagged past due.
+    pub(crate) fn late_fee(env: &Env, merchant: &Address, amount: &i128, due: u64) -> i128 {
+        match Self::get_late_fee_policy(env.clone(), merchant.clone()) {
+            Some(policy) => Self::late_fee_under(env, &policy, amount, due),
+            None => 0,
+        }
+    }
+
+    // A copy of the merchant's policy to fix on a new agreement; a zero policy charges nothing.
+    pub(crate) fn late_fee_snapshot(env: &Env, merchant: &Address) -> LateFeePolicy {
+        Self::get_late_fee_policy(env.clone(), merchant.clone()).unwrap_or(LateFeePolicy {
+            flat: 0,
+            bps: 0,
+            grace: 0,
+            cap: 0,
+        })
+    }
+
+    pub(crate) fn late_fee_under(
+        env: &Env,
+        policy: &LateFeePolicy,
+        amount: &i128,
+        due: u64,
+    ) -> i128 {
+        if env.ledger().timestamp() <= due + policy.grace as u64 {
+            return 0;
+        }
+        let fee = policy.flat + Self::bps_of(env, amount, policy.bps);
+        if fee > policy.cap {
+            policy.cap
+        } else {
+            fee
+        }
+    }
+}
@@ -1,47 +1,178 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol,IntoVal, Timepoint, Vec, I256
+    contract, contracterror, contractimpl, contractmeta, contracttype, panic_with_error,
+    symbol_short, Address, BytesN, Env, IntoVal, Map, String, Symbol, Timepoint, TryFromVal, Val,
+    Vec,
 };
 
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct PaymentLink {
-    merchant: Address,
-    amount: I256,
-    active: bool,
-    description: Symbol,
-}
+contractmeta!(key = "name", val = "payment-gateway");
+contractmeta!(key = "version", val = "0.1.0");
+contractmeta!(
+    key = "repo",
+    val = "https://github.com/Adi9876/Stellar-Contract"
+);
 
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct SubscriptionPlan {
-    merchant: Address,
-    amount: I256,
-    interval: u32,
-    active: bool,
-    name: Symbol,
+mod access;
+mod arbiter;
+mod bond;
+mod bounds;
+mod breaker;
+mod bundle;
+mod campaign;
+mod cart;
+mod cashback;
+mod catalog;
+mod checkout;
+mod commitment;
+mod config;
+mod convert;
+mod coupon;
+mod credit;
+mod decimals;
+mod deposit;
+mod donation;
+mod fee;
+mod fraud;
+mod governance;
+mod hook;
+mod installment;
+mod insurance;
+mod invoice;
+mod journal;
+mod keeper;
+mod late_fee;
+mod lifecycle;
+mod link_bundle;
+mod loyalty;
+mod marketplace;
+mod metered;
+mod migrate;
+mod milestone;
+mod multisig;
+mod nft;
+mod operator;
+mod pass;
+mod profile;
+mod proration;
+mod prune;
+mod quote;
+mod ratelimit;
+mod refund;
+mod renewal;
+mod request;
+mod rescue;
+mod revshare;
+mod scheduled;
+mod seats;
+mod settlement;
+mod shortcode;
+mod spending;
+mod stats;
+mod stream;
+mod surcharge;
+mod tax;
+#[cfg(test)]
+mod test;
+#[cfg(any(test, feature = "testutils"))]
+pub mod testutils;
+mod timelock;
+mod yield_pool;
+
+pub use bond::{BondConfig, MerchantBond};
+pub use bounds::AmountBounds;
+pub use breaker::VolumeLimits;
+pub use bundle::PlanBundle;
+pub use campaign::Campaign;
+pub use cart::Cart;
+pub use cashback::CashbackCampaign;
+pub use catalog::Visibility;
+pub use checkout::CheckoutSession;
+pub use commitment::Commitment;
+pub use config::GatewayConfig;
+pub use convert::{ConversionConfig, OracleAsset, PriceData};
+pub use donation::DonationTier;
+pub use fraud::{FraudReport, ReportStatus};
+pub use installment::{InstallmentPlan, InstallmentStatus};
+pub use insurance::InsuranceConfig;
+pub use invoice::{Invoice, InvoiceStatus, LineItem};
+pub use late_fee::LateFeePolicy;
+pub use link_bundle::LinkBundle;
+pub use loyalty::LoyaltyProgram;
+pub use milestone::{Milestone, MilestoneLink, MilestoneStatus};
+pub use multisig::{AdminAction, Proposal};
+pub use profile::{MerchantCategory, MerchantProfile};
+pub use quote::Quote;
+pub use ratelimit::{RateAction, RateLimit};
+pub use request::{PaymentRequest, RequestStatus};
+pub use scheduled::{ScheduleStatus, ScheduledPayment};
+pub use settlement::PeriodAccount;
+pub use spending::{LimitWindow, SpendingLimit};
+pub use stats::{GlobalStats, MerchantStats, PlanStats};
+pub use stream::Stream;
+pub use surcharge::SurchargePolicy;
+pub use tax::TaxRate;
+pub use timelock::PendingOp;
+pub use yield_pool::YieldConfig;
+
+pub use payment_gateway_interface::{
+    PaymentEvent, PaymentLink, PaymentSource, Receipt, Subscription, SubscriptionPlan,
+    SubscriptionStatus, TaxLine,
+};
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    PayerNotAllowed = 1,
+    PurchaseLimitReached = 2,
+    Reentrant = 3,
+    VolumeLimitExceeded = 4,
+    SpendingLimitExceeded = 5,
+    RateLimited = 6,
+    CounterOverflow = 7,
+    AmountOverflow = 8,
+    SlippageExceeded = 9,
+    StalePrice = 10,
+    PriceDeviation = 11,
 }
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Subscription {
-    subscriber: Address,
-    plan_id: u32,
-    start_time: Timepoint,
-    last_payment: Timepoint,
-    active: bool,
+pub struct MerchantRecord {
+    added_at: Timepoint,
 }
 
 // Storage Keys (all <=9 chars)
 const OWNER: Symbol = symbol_short!("OWNER");
 const TOKEN: Symbol = symbol_short!("TOKEN");
-const MERCH: Symbol = symbol_short!("MERCH");
+// (MREG, merchant) -> MerchantRecord; the original release kept a Vec<Address> under "MERCH"
+const MREG: Symbol = symbol_short!("MREG");
+// number of registered merchants
+const MCOUNT: Symbol = symbol_short!("MCOUNT");
 const LCTR: Symbol = symbol_short!("LCTR");
 const PCTR: Symbol = symbol_short!("PCTR");
-const SCTR: Symbol = symbol_short!("SCTR");
 const PLINK: Symbol = symbol_short!("PLINK");
 const SPLAN: Symbol = symbol_short!("SPLAN");
 const SUBS: Symbol = symbol_short!("SUBS");
+const RCTR: Symbol = symbol_short!("RCTR");
+// (RCPT, receipt id) -> Receipt
+const RCPT: Symbol = symbol_short!("RCPT");
+// set while a token call is in flight
+const LOCK: Symbol = symbol_short!("LOCK");
+// (ORDERS, merchant, order_id) -> receipt id of the payment that fulfilled the order
+const ORDERS: Symbol = symbol_short!("ORDERS");
+
+// Per-record entries (receipts, order ids, spending, periods, usage) live in persistent
+// storage, each under its own key, and are kept alive for about a month past last use.
+const RECORD_TTL: u32 = 535_680;
+const RECORD_TTL_THRESHOLD: u32 = RECORD_TTL / 2;
+// expected ledger close time, for turning durations into temporary-entry TTLs
+const LEDGER_SECS: u64 = 5;
+
+const MEMO_MAX: u32 = 64;
+const DESC_MAX: u32 = 128;
+const NAME_MAX: u32 = 64;
+const BATCH_MAX: u32 = 100;
 
 #[contract]
 pub struct PaymentGateway;
@@ -50,279 +181,825 @@ pub struct PaymentGateway;
 impl PaymentGateway {
     pub fn init(env: Env, invoker: Address, token: Address) {
         invoker.require_auth();
+        assert!(!env.storage().instance().has(&OWNER), "already initialized");
         env.storage().instance().set(&OWNER, &invoker);
         env.storage().instance().set(&TOKEN, &token);
+        env.storage().instance().set(&LCTR, &0u64);
+        env.storage().instance().set(&PCTR, &0u64);
+        env.storage().instance().set(&RCTR, &0u32);
         env.storage()
             .instance()
-            .set(&MERCH, &Vec::<Address>::new(&env));
-        env.storage().instance().set(&LCTR, &0u32);
-        env.storage().instance().set(&PCTR, &0u32);
-        env.storage().instance().set(&SCTR, &0u32);
+            .set(&migrate::SCHEMA, &migrate::SCHEMA_VERSION);
+    }
+
+    // Bumps the counter under `key` and returns the new id; ids never wrap onto old records.
+    pub(crate) fn next_id(env: &Env, key: &Symbol) -> u32 {
+        let ctr: u32 = env.storage().instance().get(key).unwrap_or(0);
+        let next = match ctr.checked_add(1) {
+            Some(next) => next,
+            None => panic_with_error!(env, Error::CounterOverflow),
+        };
+        env.storage().instance().set(key, &next);
+        next
+    }
+
+    // `next_id` for the link and plan counters, which are u64.
+    pub(crate) fn next_wide_id(env: &Env, key: &Symbol) -> u64 {
+        let ctr: u64 = env.storage().instance().get(key).unwrap_or(0);
+        let next = match ctr.checked_add(1) {
+            Some(next) => next,
+            None => panic_with_error!(env, Error::CounterOverflow),
+        };
+        env.storage().instance().set(key, &next);
+        next
+    }
+
+    pub(crate) fn put_record<K, V>(env: &Env, key: &K, value: &V)
+    where
+        K: IntoVal<Env, Val>,
+        V: IntoVal<Env, Val>,
+    {
+        env.storage().persistent().set(key, value);
+        env.storage()
+            .persistent()
+            .extend_ttl(key, RECORD_TTL_THRESHOLD, RECORD_TTL);
+    }
+
+    pub(crate) fn get_record<K, V>(env: &Env, key: &K) -> Option<V>
+    where
+        K: IntoVal<Env, Val>,
+        V: TryFromVal<Env, Val>,
+    {
+        let value = env.storage().persistent().get(key)?;
+        env.storage()
+            .persistent()
+            .extend_ttl(key, RECORD_TTL_THRESHOLD, RECORD_TTL);
+        Some(value)
+    }
+
+    pub(crate) fn drop_record<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        env.storage().persistent().remove(key);
+    }
+
+    // Unwraps checked amount arithmetic, failing with AmountOverflow instead of trapping.
+    pub(crate) fn checked(env: &Env, result: Option<i128>) -> i128 {
+        match result {
+            Some(amount) => amount,
+            None => panic_with_error!(env, Error::AmountOverflow),
+        }
+    }
+
+    // Same as `checked`, for timestamps and durations.
+    pub(crate) fn checked_time(env: &Env, result: Option<u64>) -> u64 {
+        match result {
+            Some(t) => t,
+            None => panic_with_error!(env, Error::AmountOverflow),
+        }
+    }
+
+    // `amount * bps / 10_000` with the multiplication checked.
+    pub(crate) fn bps_of(env: &Env, amount: &i128, bps: u32) -> i128 {
+        Self::checked(env, amount.checked_mul(bps as i128)) / 10_000
     }
 
     fn only_owner(env: &Env, invoker: &Address) {
         let o: Address = env.storage().instance().get(&OWNER).expect("OWNER not set");
         invoker.require_auth();
         assert!(invoker == &o, "only owner");
+        assert!(!Self::is_renounced(env.clone()), "ownership renounced");
     }
 
     pub fn add_merchant(env: Env, invoker: Address, merchant: Address) {
         Self::only_owner(&env, &invoker);
-        let mut merchants: Vec<Address> = env
-            .storage()
-            .instance()
-            .get(&MERCH)
-            .unwrap_or(Vec::new(&env));
-        assert!(!merchants.contains(&merchant), "already authorized");
-        merchants.push_back(merchant.clone());
-        env.storage().instance().set(&MERCH, &merchants);
-        env.events().publish((symbol_short!("MAdd"),), &merchant);
+        Self::insert_merchant(&env, &merchant);
     }
 
     pub fn remove_merchant(env: Env, invoker: Address, merchant: Address) {
         Self::only_owner(&env, &invoker);
-        let merchants: Vec<Address> = env
-            .storage()
-            .instance()
-            .get(&MERCH)
-            .unwrap_or(Vec::new(&env));
-        assert!(merchants.contains(&merchant), "not authorized");
-        // Remove merchant (no retain, manual loop)
-        let mut new_merchants = Vec::new(&env);
-        for i in 0..merchants.len() {
-            let m = merchants.get_unchecked(i);
-            if m != merchant {
-                new_merchants.push_back(m);
+        Self::assert_single_key(&env);
+        Self::assert_not_timelocked(&env, &AdminAction::RemoveMerchant(merchant.clone()));
+        Self::drop_merchant(&env, &merchant);
+    }
+
+    // Merchants already registered, or listed twice, are skipped. Returns how many were added.
+    pub fn add_merchants(env: Env, invoker: Address, merchants: Vec<Address>) -> u32 {
+        Self::only_owner(&env, &invoker);
+        assert!(merchants.len() <= BATCH_MAX, "batch too large");
+        let mut added = 0;
+        for merchant in merchants.iter() {
+            if !Self::is_merchant(&env, &merchant) {
+                Self::insert_merchant(&env, &merchant);
+                added += 1;
+            }
+        }
+        added
+    }
+
+    // Addresses that are not merchants are skipped. Returns how many were removed.
+    pub fn remove_merchants(env: Env, invoker: Address, merchants: Vec<Address>) -> u32 {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        assert!(merchants.len() <= BATCH_MAX, "batch too large");
+        let mut removed = 0;
+        for merchant in merchants.iter() {
+            if Self::is_merchant(&env, &merchant) {
+                Self::assert_not_timelocked(&env, &AdminAction::RemoveMerchant(merchant.clone()));
+                Self::drop_merchant(&env, &merchant);
+                removed += 1;
             }
         }
-        env.storage().instance().set(&MERCH, &new_merchants);
-        env.events().publish((symbol_short!("MRem"),), &merchant);
+        removed
+    }
+
+    fn insert_merchant(env: &Env, merchant: &Address) {
+        assert!(!Self::is_renounced(env.clone()), "registry frozen");
+        assert!(!Self::is_merchant(env, merchant), "already authorized");
+        Self::store_merchant(
+            env,
+            merchant,
+            &MerchantRecord {
+                added_at: Timepoint::from_unix(env, env.ledger().timestamp()),
+            },
+        );
+        Self::list_merchant(env, merchant, true);
+        Self::emit(env, (symbol_short!("MAdd"),), merchant);
+    }
+
+    fn drop_merchant(env: &Env, merchant: &Address) {
+        assert!(Self::is_merchant(env, merchant), "not authorized");
+        Self::drop_record(env, &(MREG, merchant.clone()));
+        let count = Self::merchant_count(env);
+        env.storage().instance().set(&MCOUNT, &(count - 1));
+        Self::list_merchant(env, merchant, false);
+        Self::emit(env, (symbol_short!("MRem"),), merchant);
+    }
+
+    pub(crate) fn store_merchant(env: &Env, merchant: &Address, record: &MerchantRecord) {
+        let key = (MREG, merchant.clone());
+        if !env.storage().persistent().has(&key) {
+            let count = Self::merchant_count(env);
+            env.storage().instance().set(&MCOUNT, &(count + 1));
+        }
+        Self::put_record(env, &key, record);
     }
 
     fn is_merchant(env: &Env, who: &Address) -> bool {
-        let merchants: Vec<Address> = env
-            .storage()
-            .instance()
-            .get(&MERCH)
-            .unwrap_or(Vec::new(&env));
-        merchants.contains(who)
+        env.storage().persistent().has(&(MREG, who.clone()))
+    }
+
+    pub fn get_merchant_record(env: Env, merchant: Address) -> Option<MerchantRecord> {
+        Self::get_record(&env, &(MREG, merchant))
     }
 
-    pub fn create_payment_link(env: Env, invoker: Address, amount: I256, description: Symbol) {
+    pub(crate) fn merchant_count(env: &Env) -> u32 {
+        env.storage().instance().get(&MCOUNT).unwrap_or(0)
+    }
+
+    pub fn create_payment_link(env: Env, invoker: Address, amount: i128, description: String) {
         invoker.require_auth();
-        assert!(Self::is_merchant(&env, &invoker), "not authorized");
-        assert!(amount > I256::from_i128(&env, 0), "amount>0");
-        let mut ctr: u32 = env.storage().instance().get(&LCTR).unwrap_or(0);
-        ctr += 1;
-        env.storage().instance().set(&LCTR, &ctr);
+        let merchant = Self::acting_merchant(&env, &invoker);
+        Self::assert_migrated(&env);
+        assert!(amount > 0, "amount>0");
+        Self::assert_min_price(&env, &amount);
+        Self::assert_amount_bounds(&env, &amount);
+        assert!(description.len() <= DESC_MAX, "description too long");
+        Self::rate_limit(&env, RateAction::CreateLink, &invoker);
+        let ctr = Self::next_wide_id(&env, &LCTR);
+        Self::count_link(&env, &merchant, true);
+        Self::index_link(&env, &merchant, ctr, true);
+        let code = Self::issue_link_code(&env, &merchant, ctr);
         let pl = PaymentLink {
-            merchant: invoker.clone(),
-            amount: amount.clone(),
+            merchant,
+            amount,
             active: true,
             description: description.clone(),
         };
-        let mut links: Map<u32, PaymentLink> = env
+        let mut links: Map<u64, PaymentLink> = env
             .storage()
             .instance()
             .get(&PLINK)
             .unwrap_or(Map::new(&env));
         links.set(ctr, pl);
         env.storage().instance().set(&PLINK, &links);
-        env.events().publish((symbol_short!("PLCr"), ctr), &ctr);
+        Self::emit(&env, (symbol_short!("PLCr"), ctr), ctr);
+        Self::emit(&env, (symbol_short!("LinkCode"), ctr), code);
     }
 
-    pub fn process_payment(env: Env, invoker: Address, link_id: u32) {
+    // The payer's signature covers the price it agreed to along with the order id and memo.
+    // Retrying with an `order_id` that already paid returns the original receipt id.
+    pub fn process_payment(
+        env: Env,
+        invoker: Address,
+        link_id: u64,
+        amount: i128,
+        order_id: Option<BytesN<32>>,
+        memo: Option<String>,
+    ) -> u32 {
         invoker.require_auth();
-        let links: Map<u32, PaymentLink> = env
+        Self::assert_link_price(&env, link_id, &amount);
+        if let Some(receipt_id) = Self::order_receipt(&env, link_id, &order_id) {
+            return receipt_id;
+        }
+        Self::pay_link(
+            &env, &invoker, &invoker, &invoker, link_id, &amount, order_id, memo,
+        )
+    }
+
+    pub fn process_payment_for(
+        env: Env,
+        sponsor: Address,
+        beneficiary: Address,
+        link_id: u64,
+        amount: i128,
+        order_id: Option<BytesN<32>>,
+        memo: Option<String>,
+    ) -> u32 {
+        sponsor.require_auth();
+        Self::assert_link_price(&env, link_id, &amount);
+        if let Some(receipt_id) = Self::order_receipt(&env, link_id, &order_id) {
+            return receipt_id;
+        }
+        let receipt_id = Self::pay_link(
+            &env,
+            &sponsor,
+            &sponsor,
+            &beneficiary,
+            link_id,
+            &amount,
+            order_id,
+            memo,
+        );
+        Self::emit(
+            &env,
+            (symbol_short!("Sponsd"), link_id),
+            (receipt_id, sponsor, beneficiary),
+        );
+        receipt_id
+    }
+
+    pub fn get_order_receipt(env: Env, merchant: Address, order_id: BytesN<32>) -> Option<u32> {
+        Self::get_record(&env, &(ORDERS, merchant, order_id))
+    }
+
+    // Attaches the hash of an off-chain order to a payment made without one, so either party
+    // can later prove what was agreed. Callable by the payer or the receipt's merchant, once.
+    pub fn attach_order(env: Env, invoker: Address, receipt_id: u32, order_id: BytesN<32>) {
+        invoker.require_auth();
+        let mut receipt = Self::get_receipt(env.clone(), receipt_id);
+        if invoker != receipt.payer {
+            assert!(
+                Self::acting_merchant(&env, &invoker) == receipt.merchant,
+                "not authorized"
+            );
+        }
+        assert!(receipt.order_id.is_none(), "order attached");
+        Self::index_order(&env, &receipt.merchant, order_id.clone(), receipt_id);
+        receipt.order_id = Some(order_id.clone());
+        Self::save_receipt(&env, receipt_id, &receipt);
+        Self::emit(&env, (symbol_short!("OrderSet"), receipt_id), order_id);
+    }
+
+    // An order hash maps to one receipt per merchant.
+    fn index_order(env: &Env, merchant: &Address, order_id: BytesN<32>, receipt_id: u32) {
+        let key = (ORDERS, merchant.clone(), order_id);
+        assert!(!env.storage().persistent().has(&key), "order exists");
+        Self::put_record(env, &key, &receipt_id);
+    }
+
+    fn assert_link_price(env: &Env, link_id: u64, amount: &i128) {
+        assert!(Self::link_price(env, link_id) == *amount, "price mismatch");
+    }
+
+    fn link_price(env: &Env, link_id: u64) -> i128 {
+        let links: Map<u64, PaymentLink> = env
             .storage()
             .instance()
             .get(&PLINK)
-            .unwrap_or(Map::new(&env));
+            .unwrap_or(Map::new(env));
+        links.get(link_id).expect("link not found").amount
+    }
+
+    fn order_receipt(env: &Env, link_id: u64, order_id: &Option<BytesN<32>>) -> Option<u32> {
+        let order_id = order_id.clone()?;
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(env));
+        let link = links.get(link_id).expect("link not found");
+        Self::get_order_receipt(env.clone(), link.merchant, order_id)
+    }
+
+    // `spender` is whoever authorizes the token pull: the payer itself, or the gateway when a
+    // keeper executes a payment the payer pre-approved. Callers settle the price: the link's
+    // own, or one the merchant quoted.
+    #[allow(clippy::too_many_arguments)]
+    fn pay_link(
+        env: &Env,
+        spender: &Address,
+        payer: &Address,
+        customer: &Address,
+        link_id: u64,
+        amount: &i128,
+        order_id: Option<BytesN<32>>,
+        memo: Option<String>,
+    ) -> u32 {
+        Self::check_memo(&memo);
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(env));
         let link = links.get(link_id).expect("link not found");
         assert!(link.active, "inactive link");
-        let payer = invoker;
-        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
-        env.invoke_contract::<()>(
-            &token,
-            &Symbol::new(&env,"transfer_from"),
-            Vec::from_array(
-                &env,
-                [
-                    payer.clone().to_val(),
-                    payer.clone().to_val(),
-                    link.merchant.clone().to_val(),
-                    link.amount.clone().into_val(&env),
-                ]
-            ),
+        Self::assert_not_blocked(env, &link.merchant, payer);
+        Self::assert_not_blocked(env, &link.merchant, customer);
+        Self::assert_allowed(env, link_id, payer);
+        Self::count_purchase(env, link_id, payer);
+        let surcharge = Self::surcharge_for(env, &link.merchant, amount);
+        // In a marketplace the platform settles (and is taxed on) only its commission.
+        let seller = Self::seller_share(env, link_id, &link.merchant, amount);
+        let platform_amount = match &seller {
+            Some((_, share)) => amount - share,
+            None => *amount,
+        };
+        let (mut fee, tax) = Self::settle_taxed(
+            env,
+            spender,
+            payer,
+            &link.merchant,
+            &(platform_amount + surcharge),
+            &platform_amount,
         );
-        env.events()
-            .publish((symbol_short!("Payd"), link_id), &link_id);
+        if let Some((seller, share)) = &seller {
+            fee += Self::settle_seller(env, spender, payer, &link.merchant, seller, share);
+        }
+        let receipt = Receipt {
+            order_id: order_id.clone(),
+            memo,
+            surcharge,
+            tax,
+            fee,
+            ..Receipt::new(
+                env,
+                PaymentSource::Link(link_id),
+                link.merchant.clone(),
+                payer.clone(),
+                customer.clone(),
+                *amount,
+            )
+        };
+        let receipt_id = Self::record_receipt(env, &receipt);
+        if let Some(order_id) = order_id {
+            Self::index_order(env, &link.merchant, order_id, receipt_id);
+        }
+        Self::emit(
+            env,
+            (symbol_short!("Payd"), link_id),
+            Self::payment_event(env, receipt_id, &receipt),
+        );
+        if let Some((seller, share)) = seller {
+            Self::emit(
+                env,
+                (symbol_short!("SellerPd"), seller),
+                (receipt_id, link_id, share),
+            );
+        }
+        receipt_id
+    }
+
+    fn check_memo(memo: &Option<String>) {
+        if let Some(memo) = memo {
+            assert!(memo.len() <= MEMO_MAX, "memo too long");
+        }
+    }
+
+    fn record_receipt(env: &Env, receipt: &Receipt) -> u32 {
+        let ctr = Self::store_receipt(env, receipt);
+        Self::accrue_points(env, receipt);
+        Self::apply_cashback(env, ctr, receipt);
+        Self::mint_receipt_nft(env, ctr, receipt);
+        Self::run_payment_hook(env, ctr, receipt);
+        ctr
+    }
+
+    // Files the receipt without the loyalty, cashback, NFT and hook side effects of a payment.
+    fn store_receipt(env: &Env, receipt: &Receipt) -> u32 {
+        let ctr = Self::next_id(env, &RCTR);
+        Self::save_receipt(env, ctr, receipt);
+        ctr
+    }
+
+    fn payment_event(env: &Env, receipt_id: u32, receipt: &Receipt) -> PaymentEvent {
+        PaymentEvent {
+            receipt_id,
+            source: receipt.source.clone(),
+            payer: receipt.payer.clone(),
+            customer: receipt.customer.clone(),
+            merchant: receipt.merchant.clone(),
+            token: env.storage().instance().get(&TOKEN).expect("Token"),
+            amount: receipt.amount,
+            late_fee: receipt.late_fee,
+            surcharge: receipt.surcharge,
+            tax: receipt.tax.clone(),
+            fee: receipt.fee,
+            memo: receipt.memo.clone(),
+            timestamp: receipt.timestamp.clone(),
+        }
+    }
+
+    fn save_receipt(env: &Env, receipt_id: u32, receipt: &Receipt) {
+        Self::put_record(env, &(RCPT, receipt_id), receipt);
+    }
+
+    pub fn get_receipt(env: Env, receipt_id: u32) -> Receipt {
+        Self::get_record(&env, &(RCPT, receipt_id)).expect("no receipt")
     }
 
     pub fn create_subscription_plan(
         env: Env,
         invoker: Address,
-        amount: I256,
+        amount: i128,
         interval: u32,
-        name: Symbol,
+        name: String,
     ) {
         invoker.require_auth();
-        assert!(Self::is_merchant(&env, &invoker), "not authorized");
-        assert!(amount > I256::from_i128(&env, 0), "amount>0");
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(amount > 0, "amount>0");
+        Self::assert_min_price(&env, &amount);
+        Self::assert_amount_bounds(&env, &amount);
         assert!(interval > 0, "interval>0");
-        let mut ctr: u32 = env.storage().instance().get(&PCTR).unwrap_or(0);
-        ctr += 1;
-        env.storage().instance().set(&PCTR, &ctr);
+        Self::rate_limit(&env, RateAction::CreatePlan, &invoker);
         let sp = SubscriptionPlan {
-            merchant: invoker.clone(),
-            amount: amount.clone(),
+            merchant,
+            amount,
             interval,
             active: true,
             name: name.clone(),
+            donation: false,
         };
-        let mut plans: Map<u32, SubscriptionPlan> = env
+        Self::add_plan(&env, sp);
+    }
+
+    fn add_plan(env: &Env, plan: SubscriptionPlan) -> u64 {
+        Self::assert_migrated(env);
+        assert!(plan.name.len() <= NAME_MAX, "name too long");
+        let ctr = Self::next_wide_id(env, &PCTR);
+        let mut plans: Map<u64, SubscriptionPlan> = env
             .storage()
             .instance()
             .get(&SPLAN)
-            .unwrap_or(Map::new(&env));
-        plans.set(ctr, sp);
+            .unwrap_or(Map::new(env));
+        Self::index_plan(env, &plan.merchant, ctr, true);
+        plans.set(ctr, plan);
         env.storage().instance().set(&SPLAN, &plans);
-        env.events().publish((symbol_short!("SPCr"), ctr), &ctr);
+        Self::emit(env, (symbol_short!("SPCr"), ctr), ctr);
+        ctr
     }
 
-    pub fn subscribe(env: Env, invoker: Address, plan_id: u32) {
+    // As with `process_payment`, the subscriber's signature covers the price.
+    pub fn subscribe(env: Env, invoker: Address, plan_id: u64, amount: i128) {
         invoker.require_auth();
-        let plans: Map<u32, SubscriptionPlan> = env
+        let plans: Map<u64, SubscriptionPlan> = env
             .storage()
             .instance()
             .get(&SPLAN)
             .unwrap_or(Map::new(&env));
         let plan = plans.get(plan_id).expect("plan not found");
+        assert!(plan.amount == amount, "price mismatch");
+        Self::rate_limit(&env, RateAction::Subscribe, &invoker);
+        Self::open_subscription(&env, &invoker, &invoker, plan_id, None, 1, 0);
+    }
+
+    pub fn subscribe_for(
+        env: Env,
+        payer: Address,
+        beneficiary: Address,
+        plan_id: u64,
+        periods: u32,
+    ) -> u64 {
+        payer.require_auth();
+        assert!(periods > 0, "periods>0");
+        Self::rate_limit(&env, RateAction::Subscribe, &payer);
+        let ctr = Self::open_subscription(&env, &payer, &beneficiary, plan_id, None, periods, 0);
+        Self::emit(&env, (symbol_short!("Gift"), ctr), (payer, beneficiary));
+        ctr
+    }
+
+    fn open_subscription(
+        env: &Env,
+        payer: &Address,
+        subscriber: &Address,
+        plan_id: u64,
+        amount: Option<i128>,
+        periods: u32,
+        discount_bps: u32,
+    ) -> u64 {
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(env));
+        let plan = plans.get(plan_id).expect("plan not found");
         assert!(plan.active, "plan not active");
-        let subber = invoker.clone();
-        let now = Timepoint::from_unix(&env, env.ledger().timestamp());
-        let mut ctr: u32 = env.storage().instance().get(&SCTR).unwrap_or(0);
-        ctr += 1;
-        env.storage().instance().set(&SCTR, &ctr);
+        assert!(!Self::is_metered(env.clone(), plan_id), "metered plan");
+        Self::assert_not_blocked(env, &plan.merchant, payer);
+        Self::assert_not_blocked(env, &plan.merchant, subscriber);
+        if let Some(term) = Self::get_plan_term(env.clone(), plan_id) {
+            assert!(periods <= term, "exceeds term");
+        }
+        // Donation plans let the subscriber pick any amount at or above the plan minimum.
+        let amount = match amount {
+            Some(a) => {
+                assert!(plan.donation, "fixed price plan");
+                assert!(a >= plan.amount, "below minimum");
+                Self::assert_amount_bounds(env, &a);
+                a
+            }
+            None => plan.amount,
+        };
+        // Bundled subscriptions keep the discounted price for every later cycle.
+        let amount = amount - Self::bps_of(env, &amount, discount_bps);
+        if plan.donation {
+            Self::track_supporter(env, &plan.merchant, plan_id, &amount, true);
+        }
+        Self::count_subscriber(env, &plan.merchant, true);
+        Self::count_plan_subscriber(env, plan_id, &amount, true);
+        let now = Timepoint::from_unix(env, env.ledger().timestamp());
+        let ctr = Self::next_subscription_id(env, subscriber);
+        Self::index_plan_subscriber(env, plan_id, subscriber, ctr);
         let sub = Subscription {
-            subscriber: subber.clone(),
+            subscriber: subscriber.clone(),
+            payer: payer.clone(),
             plan_id,
+            amount,
             start_time: now.clone(),
             last_payment: now,
-            active: true,
+            prepaid: periods - 1,
+            cycles: 1,
+            last_receipt: 0,
+            status: SubscriptionStatus::Active,
+            quantity: 1,
         };
-        let mut subs: Map<(Address, u32), Subscription> = env
-            .storage()
-            .instance()
-            .get(&SUBS)
-            .unwrap_or(Map::new(&env));
-        subs.set((subber.clone(), ctr), sub);
+        let total = Self::checked(env, amount.checked_mul(periods as i128));
+        let (shared, shared_fee) =
+            Self::pay_revenue_shares(env, Some((payer, payer)), plan_id, &total);
+        let rest = total - shared;
+        let (fee, tax) = Self::settle_taxed(env, payer, payer, &plan.merchant, &rest, &rest);
+        let receipt = Receipt {
+            tax,
+            fee: fee + shared_fee,
+            ..Receipt::new(
+                env,
+                PaymentSource::Subscription(subscriber.clone(), ctr),
+                plan.merchant.clone(),
+                payer.clone(),
+                subscriber.clone(),
+                total,
+            )
+        };
+        let receipt_id = Self::record_receipt(env, &receipt);
+        let mut subs: Map<(Address, u64), Subscription> =
+            env.storage().instance().get(&SUBS).unwrap_or(Map::new(env));
+        let sub = Subscription {
+            last_receipt: receipt_id,
+            ..sub
+        };
+        subs.set((subscriber.clone(), ctr), sub.clone());
         env.storage().instance().set(&SUBS, &subs);
-        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
-        env.invoke_contract::<()>(
-            &token,
-            &Symbol::new(&env,"transfer_from"),
-            // vec![
-            //     // &env,
-                // subber.clone().to_val(),
-                // subber.clone().to_val(),
-                // plan.merchant.clone().to_val(),
-                // plan.amount.clone().to_val(),
-            // ],
+        Self::issue_pass(env, ctr, &sub, &plan);
+        Self::emit(env, (symbol_short!("Subd"), ctr), ctr);
+        Self::emit(
+            env,
+            (symbol_short!("SPay"), ctr),
+            Self::payment_event(env, receipt_id, &receipt),
+        );
+        ctr
+    }
+
+    // Sends tokens held by the gateway itself.
+    fn payout(env: &Env, to: &Address, amount: &i128) {
+        Self::ensure_liquid(env, amount);
+        Self::call_token(
+            env,
+            "transfer",
             Vec::from_array(
-                &env,
+                env,
+                [
+                    env.current_contract_address().to_val(),
+                    to.to_val(),
+                    amount.into_val(env),
+                ],
+            ),
+        );
+    }
+
+    // A payer spending its own funds authorizes a plain `transfer`; only pulls made by the
+    // gateway itself go through `transfer_from` against the payer's allowance.
+    fn transfer_from(env: &Env, spender: &Address, from: &Address, to: &Address, amount: &i128) {
+        if spender == from {
+            Self::call_token(
+                env,
+                "transfer",
+                Vec::from_array(env, [from.to_val(), to.to_val(), amount.into_val(env)]),
+            );
+            return;
+        }
+        Self::call_token(
+            env,
+            "transfer_from",
+            Vec::from_array(
+                env,
                 [
-                    subber.clone().to_val(),
-                    subber.clone().to_val(),
-                    plan.merchant.clone().to_val(),
-                    plan.amount.clone().into_val(&env),
-                ]
+                    spender.to_val(),
+                    from.to_val(),
+                    to.to_val(),
+                    amount.into_val(env),
+                ],
             ),
         );
-        env.events().publish((symbol_short!("Subd"), ctr), &ctr);
-        env.events().publish((symbol_short!("SPay"), ctr), &ctr);
+    }
+
+    // The lock is held for the duration of every token call; see `assert_not_entered`.
+    fn call_token(env: &Env, func: &str, args: Vec<Val>) {
+        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        Self::invoke_token(env, &token, func, args);
+    }
+
+    fn invoke_token(env: &Env, token: &Address, func: &str, args: Vec<Val>) {
+        Self::assert_not_entered(env);
+        env.storage().instance().set(&LOCK, &true);
+        env.invoke_contract::<()>(token, &Symbol::new(env, func), args);
+        env.storage().instance().remove(&LOCK);
+    }
+
+    // Every state-changing entrypoint publishes an event through `emit`, which checks the
+    // lock too, so a token that calls back into the gateway mid-transfer aborts the transaction.
+    pub(crate) fn assert_not_entered(env: &Env) {
+        if env.storage().instance().has(&LOCK) {
+            panic_with_error!(env, Error::Reentrant);
+        }
     }
 
     pub fn process_subscription_payment(
         env: Env,
         invoker: Address,
         subscriber: Address,
-        subscription_id: u32,
+        subscription_id: u64,
+        memo: Option<String>,
     ) {
         invoker.require_auth();
-        let mut subs: Map<(Address, u32), Subscription> = env
-            .storage()
-            .instance()
-            .get(&SUBS)
-            .unwrap_or(Map::new(&env));
-        let mut sub = subs
-            .get((subscriber.clone(), subscription_id))
-            .expect("subscription not found");
-        assert!(sub.active, "sub inactive");
-        let plans: Map<u32, SubscriptionPlan> = env
+        Self::check_memo(&memo);
+        let mut sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        assert!(sub.status.is_billable(), "not billable");
+        let plans: Map<u64, SubscriptionPlan> = env
             .storage()
             .instance()
             .get(&SPLAN)
             .unwrap_or(Map::new(&env));
         let plan = plans.get(sub.plan_id).expect("plan not found");
         assert!(plan.active, "plan inactive");
+        assert!(!Self::is_metered(env.clone(), sub.plan_id), "metered plan");
+        Self::assert_can_charge(&env, &invoker, &plan.merchant, &subscriber);
         let now = Timepoint::from_unix(&env, env.ledger().timestamp());
-        let next_due =
-            Timepoint::from_unix(&env, sub.last_payment.to_unix() + (plan.interval as u64));
-        assert!(now.to_unix() >= next_due.to_unix(), "not due");
-        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
-        env.invoke_contract::<()>(
-            &token,
-            &Symbol::new(&env,"transfer_from"),
-            // vec![
-            //     // &env,
-            //     subscriber.clone().to_val(),
-            //     subscriber.clone().to_val(),
-            //     plan.merchant.clone().to_val(),
-            //     plan.amount.clone().to_val(),
-            // ],
-            Vec::from_array(
+        let next_due = Self::due_at(&sub, &plan);
+        assert!(now.to_unix() >= next_due, "not due");
+        // Gifted periods were funded up front; only charge the payer once they run out.
+        let prepaid = sub.prepaid > 0;
+        if prepaid {
+            sub.prepaid -= 1;
+        }
+        // a late fee is only owed once the charge has been flagged past due
+        let past_due = sub.status == SubscriptionStatus::PastDue;
+        if past_due {
+            Self::transition(&env, subscription_id, &mut sub, SubscriptionStatus::Active);
+        }
+        sub.cycles += 1;
+        sub.last_payment = now;
+        if Self::term_reached(&env, &sub) {
+            Self::transition(&env, subscription_id, &mut sub, SubscriptionStatus::Expired);
+            Self::retire_subscription(&env, subscription_id, &sub, &plan);
+        } else {
+            Self::issue_pass(&env, subscription_id, &sub, &plan);
+        }
+        // Persist the advanced period before calling out to the token.
+        Self::store_subscription(&env, subscription_id, &sub);
+        if prepaid {
+            Self::emit(
                 &env,
-                [
-                    subscriber.clone().to_val(),
-                    subscriber.clone().to_val(),
-                    plan.merchant.clone().to_val(),
-                    plan.amount.clone().into_val(&env),
-                ]
-            ),
+                (symbol_short!("SPre"), subscription_id),
+                (sub.prepaid, memo),
+            );
+            return;
+        }
+        let charge = Self::seat_total(&env, &sub);
+        let late_fee = if past_due {
+            Self::late_fee(&env, &plan.merchant, &charge, next_due)
+        } else {
+            0
+        };
+        let due = charge + late_fee;
+        let held = Self::draw_deposit(&env, &subscriber, subscription_id, &due);
+        // recurring charges are pulled against the payer's allowance to the gateway, so
+        // whoever `assert_can_charge` admits can trigger them
+        let gateway = env.current_contract_address();
+        let source = if held {
+            None
+        } else {
+            Some((&gateway, &sub.payer))
+        };
+        // co-payees share the plan price; late fees stay with the merchant
+        let (shared, shared_fee) = Self::pay_revenue_shares(&env, source, sub.plan_id, &charge);
+        let (rest_due, rest) = (due - shared, charge - shared);
+        let (fee, tax) = if held {
+            Self::settle_held_taxed(&env, &plan.merchant, &rest_due, &rest)
+        } else {
+            Self::settle_taxed(&env, &gateway, &sub.payer, &plan.merchant, &rest_due, &rest)
+        };
+        let receipt = Receipt {
+            late_fee,
+            tax,
+            fee: fee + shared_fee,
+            memo,
+            ..Receipt::new(
+                &env,
+                PaymentSource::Subscription(subscriber.clone(), subscription_id),
+                plan.merchant.clone(),
+                sub.payer.clone(),
+                subscriber.clone(),
+                charge,
+            )
+        };
+        let receipt_id = Self::record_receipt(&env, &receipt);
+        sub.last_receipt = receipt_id;
+        Self::store_subscription(&env, subscription_id, &sub);
+        Self::emit(
+            &env,
+            (symbol_short!("SPay"), subscription_id),
+            Self::payment_event(&env, receipt_id, &receipt),
         );
-        sub.last_payment = now;
-        subs.set((subscriber.clone(), subscription_id), sub.clone());
-        env.storage().instance().set(&SUBS, &subs);
-        env.events()
-            .publish((symbol_short!("SPay"), subscription_id), &subscription_id);
     }
 
-    pub fn cancel_subscription(env: Env, invoker: Address, subscription_id: u32) {
+    pub fn cancel_subscription(env: Env, invoker: Address, subscription_id: u64) {
         invoker.require_auth();
         let subber = invoker.clone();
-        let mut subs: Map<(Address, u32), Subscription> = env
-            .storage()
-            .instance()
-            .get(&SUBS)
-            .unwrap_or(Map::new(&env));
-        let mut sub = subs.get((subber.clone(), subscription_id)).expect("no sub");
-        assert!(sub.active, "already inactive");
+        let sub = Self::load_subscription(&env, &subber, subscription_id);
         assert!(
             sub.subscriber == subber.clone() || Self::is_merchant(&env, &invoker),
             "not authorized"
         );
-        sub.active = false;
-        subs.set((subber.clone(), subscription_id), sub.clone());
-        env.storage().instance().set(&SUBS, &subs);
-        env.events()
-            .publish((symbol_short!("SCnl"), subscription_id), &subscription_id);
+        Self::end_subscription(&env, subscription_id, sub);
+        Self::cancel_bundle_siblings(&env, &subber, subscription_id);
+    }
+
+    fn end_subscription(env: &Env, subscription_id: u64, mut sub: Subscription) {
+        Self::transition(env, subscription_id, &mut sub, SubscriptionStatus::Canceled);
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(env));
+        let plan = plans.get(sub.plan_id).expect("plan not found");
+        Self::retire_subscription(env, subscription_id, &sub, &plan);
+        Self::store_subscription(env, subscription_id, &sub);
+        if Self::is_metered(env.clone(), sub.plan_id) {
+            // usage reported so far in the open period is still owed
+            Self::bill_usage(env, subscription_id, &sub, &plan);
+        }
+        Self::charge_early_exit(env, subscription_id, &sub, &plan);
+        Self::refund_unused_period(env, subscription_id, &sub, &plan);
+        Self::refund_prepaid_periods(env, subscription_id, &sub, &plan);
+        Self::return_deposit(env, subscription_id, &sub);
+        Self::emit(
+            env,
+            (symbol_short!("SCnl"), subscription_id),
+            subscription_id,
+        );
+    }
+
+    // Drops a subscription that has left the live states from supporter and subscriber counts
+    // and invalidates its membership pass.
+    fn retire_subscription(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+    ) {
+        Self::unindex_plan_subscriber(env, sub.plan_id, &sub.subscriber, subscription_id);
+        Self::mark_subscription_closed(env, &sub.subscriber, subscription_id);
+        Self::clear_renewal_notice(env, &sub.subscriber, subscription_id);
+        if plan.donation {
+            Self::track_supporter(env, &plan.merchant, sub.plan_id, &sub.amount, false);
+        }
+        Self::count_subscriber(env, &plan.merchant, false);
+        Self::count_plan_subscriber(env, sub.plan_id, &Self::seat_total(env, sub), false);
+        Self::revoke_pass(env, subscription_id, sub);
     }
 
-    pub fn deactivate_payment_link(env: Env, invoker: Address, link_id: u32) {
+    pub fn deactivate_payment_link(env: Env, invoker: Address, link_id: u64) {
         invoker.require_auth();
-        let m = invoker;
-        let mut links: Map<u32, PaymentLink> = env
+        let m = Self::acting_merchant(&env, &invoker);
+        let mut links: Map<u64, PaymentLink> = env
             .storage()
             .instance()
             .get(&PLINK)
@@ -331,14 +1008,18 @@ impl PaymentGateway {
         assert!(link.merchant == m, "not merchant");
         assert!(link.active, "already inactive");
         link.active = false;
+        Self::count_link(&env, &m, false);
+        Self::index_link(&env, &m, link_id, false);
+        Self::mark_link_closed(&env, link_id);
         links.set(link_id, link);
         env.storage().instance().set(&PLINK, &links);
+        Self::emit(&env, (symbol_short!("LinkOff"), link_id), m);
     }
 
-    pub fn deactivate_subscription_plan(env: Env, invoker: Address, plan_id: u32) {
+    pub fn deactivate_subscription_plan(env: Env, invoker: Address, plan_id: u64) {
         invoker.require_auth();
-        let m = invoker;
-        let mut plans: Map<u32, SubscriptionPlan> = env
+        let m = Self::acting_merchant(&env, &invoker);
+        let mut plans: Map<u64, SubscriptionPlan> = env
             .storage()
             .instance()
             .get(&SPLAN)
@@ -347,7 +1028,10 @@ impl PaymentGateway {
         assert!(plan.merchant == m, "not merchant");
         assert!(plan.active, "already inactive");
         plan.active = false;
+        Self::index_plan(&env, &m, plan_id, false);
+        Self::mark_plan_closed(&env, plan_id);
         plans.set(plan_id, plan);
         env.storage().instance().set(&SPLAN, &plans);
+        Self::emit(&env, (symbol_short!("PlanOff"), plan_id), m);
     }
 }
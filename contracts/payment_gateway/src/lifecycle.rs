@@ -0,0 +1,197 @@
+use soroban_sdk::{
+    contractimpl, panic_with_error, symbol_short, Address, Env, Map, Symbol, Timepoint, Vec,
+};
+
+use crate::{
+    Error, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Subscription,
+    SubscriptionPlan, SubscriptionStatus, SPLAN, SUBS,
+};
+
+// Subscription ids are sequential per subscriber: this maps a subscriber to its last id.
+pub(crate) const SUBCTR: Symbol = symbol_short!("SUBCTR");
+// plan id -> (subscriber, subscription id) of every live subscription to it
+const PLSUBS: Symbol = symbol_short!("PLSUBS");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Paused subscriptions cannot be charged until resumed.
+    pub fn pause_subscription(env: Env, invoker: Address, subscription_id: u64) {
+        invoker.require_auth();
+        let mut sub = Self::load_subscription(&env, &invoker, subscription_id);
+        Self::transition(&env, subscription_id, &mut sub, SubscriptionStatus::Paused);
+        Self::store_subscription(&env, subscription_id, &sub);
+    }
+
+    pub fn resume_subscription(env: Env, invoker: Address, subscription_id: u64) {
+        invoker.require_auth();
+        let mut sub = Self::load_subscription(&env, &invoker, subscription_id);
+        assert!(sub.status == SubscriptionStatus::Paused, "not paused");
+        Self::transition(&env, subscription_id, &mut sub, SubscriptionStatus::Active);
+        Self::store_subscription(&env, subscription_id, &sub);
+    }
+
+    // Anyone may flag a subscription whose charge is due but unpaid.
+    pub fn mark_subscription_past_due(env: Env, subscriber: Address, subscription_id: u64) {
+        let mut sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        let plan = plans.get(sub.plan_id).expect("plan not found");
+        assert!(sub.prepaid == 0, "prepaid");
+        assert!(
+            env.ledger().timestamp() >= Self::due_at(&sub, &plan),
+            "not due"
+        );
+        Self::transition(&env, subscription_id, &mut sub, SubscriptionStatus::PastDue);
+        Self::store_subscription(&env, subscription_id, &sub);
+    }
+
+    pub fn get_subscription_status(
+        env: Env,
+        subscriber: Address,
+        subscription_id: u64,
+    ) -> SubscriptionStatus {
+        Self::load_subscription(&env, &subscriber, subscription_id).status
+    }
+
+    pub fn get_subscription(env: Env, subscriber: Address, subscription_id: u64) -> Subscription {
+        Self::load_subscription(&env, &subscriber, subscription_id)
+    }
+
+    // When the current period ends, whether or not the subscription can still be charged.
+    pub fn next_charge_at(env: Env, subscriber: Address, subscription_id: u64) -> Timepoint {
+        let sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        let plan = Self::plan_of(&env, &sub);
+        Timepoint::from_unix(&env, Self::due_at(&sub, &plan))
+    }
+
+    // Whether the period can be billed now, by `process_subscription_payment` or, on metered
+    // plans, `close_usage_period`; caller authorization aside. A due prepaid period is
+    // consumed without charging the payer.
+    pub fn is_due(env: Env, subscriber: Address, subscription_id: u64) -> bool {
+        let sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        let plan = Self::plan_of(&env, &sub);
+        // metered usage already incurred is billed even if the plan was deactivated since
+        let chargeable = plan.active || Self::is_metered(env.clone(), sub.plan_id);
+        sub.status.is_billable()
+            && chargeable
+            && env.ledger().timestamp() >= Self::due_at(&sub, &plan)
+    }
+
+    // Ids run from 1 to the returned count.
+    pub fn subscription_count(env: Env, subscriber: Address) -> u64 {
+        let counters: Map<Address, u64> = env
+            .storage()
+            .instance()
+            .get(&SUBCTR)
+            .unwrap_or(Map::new(&env));
+        counters.get(subscriber).unwrap_or(0)
+    }
+
+    pub fn plan_subscribers(env: Env, plan_id: u64) -> Vec<(Address, u64)> {
+        let all: Map<u64, Vec<(Address, u64)>> = env
+            .storage()
+            .instance()
+            .get(&PLSUBS)
+            .unwrap_or(Map::new(&env));
+        all.get(plan_id).unwrap_or(Vec::new(&env))
+    }
+
+    pub(crate) fn due_at(sub: &Subscription, plan: &SubscriptionPlan) -> u64 {
+        sub.last_payment.to_unix() + plan.interval as u64
+    }
+
+    fn plan_of(env: &Env, sub: &Subscription) -> SubscriptionPlan {
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(env));
+        plans.get(sub.plan_id).expect("plan not found")
+    }
+
+    pub(crate) fn next_subscription_id(env: &Env, subscriber: &Address) -> u64 {
+        let id = match Self::subscription_count(env.clone(), subscriber.clone()).checked_add(1) {
+            Some(id) => id,
+            None => panic_with_error!(env, Error::CounterOverflow),
+        };
+        let mut counters: Map<Address, u64> = env
+            .storage()
+            .instance()
+            .get(&SUBCTR)
+            .unwrap_or(Map::new(env));
+        counters.set(subscriber.clone(), id);
+        env.storage().instance().set(&SUBCTR, &counters);
+        id
+    }
+
+    pub(crate) fn index_plan_subscriber(
+        env: &Env,
+        plan_id: u64,
+        subscriber: &Address,
+        subscription_id: u64,
+    ) {
+        let mut entries = Self::plan_subscribers(env.clone(), plan_id);
+        entries.push_back((subscriber.clone(), subscription_id));
+        Self::save_plan_subscribers(env, plan_id, &entries);
+    }
+
+    pub(crate) fn unindex_plan_subscriber(
+        env: &Env,
+        plan_id: u64,
+        subscriber: &Address,
+        subscription_id: u64,
+    ) {
+        let mut entries = Self::plan_subscribers(env.clone(), plan_id);
+        if let Some(i) = entries.first_index_of((subscriber.clone(), subscription_id)) {
+            entries.remove(i);
+        }
+        Self::save_plan_subscribers(env, plan_id, &entries);
+    }
+
+    fn save_plan_subscribers(env: &Env, plan_id: u64, entries: &Vec<(Address, u64)>) {
+        let mut all: Map<u64, Vec<(Address, u64)>> = env
+            .storage()
+            .instance()
+            .get(&PLSUBS)
+            .unwrap_or(Map::new(env));
+        all.set(plan_id, entries.clone());
+        env.storage().instance().set(&PLSUBS, &all);
+    }
+
+    pub(crate) fn transition(
+        env: &Env,
+        subscription_id: u64,
+        sub: &mut Subscription,
+        next: SubscriptionStatus,
+    ) {
+        let prev = sub.status;
+        assert!(prev.can_become(next), "bad transition");
+        sub.status = next;
+        Self::emit(
+            env,
+            (symbol_short!("SStatus"), subscription_id),
+            (prev, next),
+        );
+    }
+
+    pub(crate) fn load_subscription(
+        env: &Env,
+        subscriber: &Address,
+        subscription_id: u64,
+    ) -> Subscription {
+        let subs: Map<(Address, u64), Subscription> =
+            env.storage().instance().get(&SUBS).unwrap_or(Map::new(env));
+        subs.get((subscriber.clone(), subscription_id))
+            .expect("subscription not found")
+    }
+
+    pub(crate) fn store_subscription(env: &Env, subscription_id: u64, sub: &Subscription) {
+        let mut subs: Map<(Address, u64), Subscription> =
+            env.storage().instance().get(&SUBS).unwrap_or(Map::new(env));
+        subs.set((sub.subscriber.clone(), subscription_id), sub.clone());
+        env.storage().instance().set(&SUBS, &subs);
+    }
+}
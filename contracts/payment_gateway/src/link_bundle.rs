@@ -0,0 +1,146 @@
+use soroban_sdk::{
+    contractimpl, contracttype, symbol_short, Address, Env, IntoVal, Map, Symbol, Vec,
+};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PaymentSource, Receipt,
+    BATCH_MAX, PLINK,
+};
+
+// Several of a merchant's links sold together for less than their combined price. Paying the
+// bundle counts as one purchase of every link in it, under a single receipt.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LinkBundle {
+    merchant: Address,
+    link_ids: Vec<u64>,
+    price: i128,
+    active: bool,
+}
+
+const LBCTR: Symbol = symbol_short!("LBCTR");
+const LBUNDLE: Symbol = symbol_short!("LBUNDLE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn create_link_bundle(env: Env, invoker: Address, link_ids: Vec<u64>, price: i128) -> u32 {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(link_ids.len() > 1, "bundle needs 2+ links");
+        assert!(link_ids.len() <= BATCH_MAX, "batch too large");
+        assert!(price > 0, "amount>0");
+        Self::assert_min_price(&env, &price);
+        Self::assert_amount_bounds(&env, &price);
+        let links = Self::bundle_links(&env);
+        let mut full_price: i128 = 0;
+        for (i, link_id) in link_ids.iter().enumerate() {
+            assert!(
+                link_ids.first_index_of(link_id) == Some(i as u32),
+                "duplicate link"
+            );
+            let link = links.get(link_id).expect("link not found");
+            assert!(link.merchant == merchant, "not merchant");
+            assert!(link.active, "inactive link");
+            Self::assert_no_seller(&env, link_id);
+            full_price = Self::checked(&env, full_price.checked_add(link.amount));
+        }
+        assert!(price < full_price, "no discount");
+        let ctr = Self::next_id(&env, &LBCTR);
+        Self::save_link_bundle(
+            &env,
+            ctr,
+            &LinkBundle {
+                merchant,
+                link_ids,
+                price,
+                active: true,
+            },
+        );
+        Self::emit(&env, (symbol_short!("LBndlCr"), ctr), (price, full_price));
+        ctr
+    }
+
+    pub fn deactivate_link_bundle(env: Env, invoker: Address, bundle_id: u32) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let mut bundle = Self::get_link_bundle(env.clone(), bundle_id);
+        assert!(bundle.merchant == merchant, "not merchant");
+        assert!(bundle.active, "already inactive");
+        bundle.active = false;
+        Self::save_link_bundle(&env, bundle_id, &bundle);
+        Self::emit(&env, (symbol_short!("LBndlOff"), bundle_id), merchant);
+    }
+
+    pub fn get_link_bundle(env: Env, bundle_id: u32) -> LinkBundle {
+        let bundles: Map<u32, LinkBundle> = env
+            .storage()
+            .instance()
+            .get(&LBUNDLE)
+            .unwrap_or(Map::new(&env));
+        bundles.get(bundle_id).expect("no bundle")
+    }
+
+    // The payer signs the bundle id and its price. Every link must still be active and open
+    // to the payer, and each counts against its purchase limit.
+    pub fn pay_link_bundle(env: Env, invoker: Address, bundle_id: u32, price: i128) -> u32 {
+        invoker.require_auth_for_args((bundle_id, price).into_val(&env));
+        let bundle = Self::get_link_bundle(env.clone(), bundle_id);
+        assert!(bundle.active, "bundle not active");
+        assert!(bundle.price == price, "price mismatch");
+        Self::assert_not_blocked(&env, &bundle.merchant, &invoker);
+        let links = Self::bundle_links(&env);
+        for link_id in bundle.link_ids.iter() {
+            let link = links.get(link_id).expect("link not found");
+            assert!(link.active, "inactive link");
+            Self::assert_no_seller(&env, link_id);
+            Self::assert_allowed(&env, link_id, &invoker);
+            Self::count_purchase(&env, link_id, &invoker);
+        }
+        let surcharge = Self::surcharge_for(&env, &bundle.merchant, &price);
+        let (fee, tax) = Self::settle_taxed(
+            &env,
+            &invoker,
+            &invoker,
+            &bundle.merchant,
+            &(price + surcharge),
+            &price,
+        );
+        let receipt = Receipt {
+            surcharge,
+            tax,
+            fee,
+            ..Receipt::new(
+                &env,
+                PaymentSource::LinkBundle(bundle_id),
+                bundle.merchant,
+                invoker.clone(),
+                invoker,
+                price,
+            )
+        };
+        let receipt_id = Self::record_receipt(&env, &receipt);
+        Self::emit(
+            &env,
+            (symbol_short!("LBndlPaid"), bundle_id),
+            Self::payment_event(&env, receipt_id, &receipt),
+        );
+        receipt_id
+    }
+
+    fn bundle_links(env: &Env) -> Map<u64, PaymentLink> {
+        env.storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(env))
+    }
+
+    fn save_link_bundle(env: &Env, bundle_id: u32, bundle: &LinkBundle) {
+        let mut bundles: Map<u32, LinkBundle> = env
+            .storage()
+            .instance()
+            .get(&LBUNDLE)
+            .unwrap_or(Map::new(env));
+        bundles.set(bundle_id, bundle.clone());
+        env.storage().instance().set(&LBUNDLE, &bundles);
+    }
+}
@@ -0,0 +1,156 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Receipt};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LoyaltyProgram {
+    // points earned per 10_000 token units paid
+    earn_bps: u32,
+    // token units one point takes off a payment
+    point_value: i128,
+    // share of a single payment that points may cover
+    max_redeem_bps: u32,
+    // most points a single payment can earn
+    max_points: i128,
+}
+
+const LOYALTY: Symbol = symbol_short!("LOYALTY");
+// (merchant, payer) -> points balance
+const POINTS: Symbol = symbol_short!("POINTS");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_loyalty_program(
+        env: Env,
+        invoker: Address,
+        earn_bps: u32,
+        point_value: i128,
+        max_redeem_bps: u32,
+        max_points: i128,
+    ) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(earn_bps <= 10_000, "earn_bps<=10000");
+        assert!(max_redeem_bps <= 10_000, "max_redeem_bps<=10000");
+        assert!(point_value > 0, "point_value>0");
+        assert!(max_points > 0, "max_points>0");
+        let mut programs = Self::loyalty_programs(&env);
+        programs.set(
+            merchant.clone(),
+            LoyaltyProgram {
+                earn_bps,
+                point_value,
+                max_redeem_bps,
+                max_points,
+            },
+        );
+        env.storage().instance().set(&LOYALTY, &programs);
+        Self::emit(&env, (symbol_short!("LoyalSet"), merchant), earn_bps);
+    }
+
+    // Existing balances are kept but stop growing and can no longer be redeemed.
+    pub fn clear_loyalty_program(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let mut programs = Self::loyalty_programs(&env);
+        assert!(programs.contains_key(merchant.clone()), "no program");
+        programs.remove(merchant.clone());
+        env.storage().instance().set(&LOYALTY, &programs);
+        Self::emit(&env, (symbol_short!("LoyalOff"),), merchant);
+    }
+
+    pub fn get_loyalty_program(env: Env, merchant: Address) -> Option<LoyaltyProgram> {
+        Self::loyalty_programs(&env).get(merchant)
+    }
+
+    pub fn points_balance(env: Env, merchant: Address, payer: Address) -> i128 {
+        let points: Map<(Address, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&POINTS)
+            .unwrap_or(Map::new(&env));
+        points.get((merchant, payer)).unwrap_or(0)
+    }
+
+    pub(crate) fn accrue_points(env: &Env, receipt: &Receipt) {
+        let earned = match Self::points_for(env, &receipt.merchant, &receipt.amount) {
+            Some(p) => p,
+            None => return,
+        };
+        let balance =
+            Self::points_balance(env.clone(), receipt.merchant.clone(), receipt.payer.clone());
+        Self::set_points(env, &receipt.merchant, &receipt.payer, &(balance + earned));
+        Self::emit(
+            env,
+            (symbol_short!("PtsEarn"), receipt.merchant.clone()),
+            (receipt.payer.clone(), earned),
+        );
+    }
+
+    // Takes back what a refunded amount earned, without going below 0.
+    pub(crate) fn claw_back_points(env: &Env, receipt: &Receipt, refunded: &i128) {
+        let earned = match Self::points_for(env, &receipt.merchant, refunded) {
+            Some(p) => p,
+            None => return,
+        };
+        let balance =
+            Self::points_balance(env.clone(), receipt.merchant.clone(), receipt.payer.clone());
+        let left = (balance - earned).max(0);
+        Self::set_points(env, &receipt.merchant, &receipt.payer, &left);
+    }
+
+    // Spends `points` against `price` and returns the discount they buy.
+    pub(crate) fn redeem_points(
+        env: &Env,
+        merchant: &Address,
+        payer: &Address,
+        price: &i128,
+        points: &i128,
+    ) -> i128 {
+        assert!(*points > 0, "points>0");
+        let program = Self::get_loyalty_program(env.clone(), merchant.clone()).expect("no program");
+        let balance = Self::points_balance(env.clone(), merchant.clone(), payer.clone());
+        assert!(balance >= *points, "insufficient points");
+        let discount = points * program.point_value;
+        let cap = Self::bps_of(env, price, program.max_redeem_bps);
+        assert!(discount <= cap, "redeem cap");
+        Self::set_points(env, merchant, payer, &(balance - points));
+        Self::emit(
+            env,
+            (symbol_short!("PtsRedeem"), merchant.clone()),
+            (payer.clone(), *points),
+        );
+        discount
+    }
+
+    fn points_for(env: &Env, merchant: &Address, amount: &i128) -> Option<i128> {
+        let program = Self::get_loyalty_program(env.clone(), merchant.clone())?;
+        let points = Self::bps_of(env, amount, program.earn_bps);
+        if points <= 0 {
+            return None;
+        }
+        Some(if points > program.max_points {
+            program.max_points
+        } else {
+            points
+        })
+    }
+
+    fn set_points(env: &Env, merchant: &Address, payer: &Address, balance: &i128) {
+        let mut points: Map<(Address, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&POINTS)
+            .unwrap_or(Map::new(env));
+        points.set((merchant.clone(), payer.clone()), *balance);
+        env.storage().instance().set(&POINTS, &points);
+    }
+
+    fn loyalty_programs(env: &Env) -> Map<Address, LoyaltyProgram> {
+        env.storage()
+            .instance()
+            .get(&LOYALTY)
+            .unwrap_or(Map::new(env))
+    }
+}
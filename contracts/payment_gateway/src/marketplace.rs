@@ -0,0 +1,140 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PLINK};
+
+// platform merchant -> commission it keeps on its sellers' links
+const MKTFEE: Symbol = symbol_short!("MKTFEE");
+// link id -> seller paid the rest of the link's price
+const LSELLER: Symbol = symbol_short!("LSELLER");
+// (SELLEARN, platform, seller) -> lifetime amount paid to the seller, after protocol fees
+const SELLEARN: Symbol = symbol_short!("SELLEARN");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Turns marketplace mode on for the calling merchant, or off with `None`. While off, links
+    // with a seller settle entirely with the platform.
+    pub fn set_marketplace(env: Env, invoker: Address, commission_bps: Option<u32>) {
+        invoker.require_auth();
+        let platform = Self::acting_merchant(&env, &invoker);
+        let mut fees = Self::marketplace_fees(&env);
+        match commission_bps {
+            Some(bps) => {
+                assert!(bps <= 10_000, "bps<=10000");
+                fees.set(platform.clone(), bps);
+            }
+            None => {
+                fees.remove(platform.clone());
+            }
+        }
+        env.storage().instance().set(&MKTFEE, &fees);
+        Self::emit(&env, (symbol_short!("Mkt"), platform), commission_bps);
+    }
+
+    pub fn get_marketplace_commission(env: Env, platform: Address) -> Option<u32> {
+        Self::marketplace_fees(&env).get(platform)
+    }
+
+    // Sellers need no registration: any address can be named, and is paid directly from
+    // each payment. Refunds still come out of the platform.
+    pub fn set_link_seller(env: Env, invoker: Address, link_id: u64, seller: Option<Address>) {
+        invoker.require_auth();
+        let platform = Self::acting_merchant(&env, &invoker);
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("no link");
+        assert!(link.merchant == platform, "not merchant");
+        let mut sellers = Self::link_sellers(&env);
+        match &seller {
+            Some(s) => {
+                assert!(
+                    Self::get_marketplace_commission(env.clone(), platform).is_some(),
+                    "marketplace off"
+                );
+                assert!(*s != link.merchant, "seller is platform");
+                sellers.set(link_id, s.clone());
+            }
+            None => {
+                sellers.remove(link_id);
+            }
+        }
+        env.storage().instance().set(&LSELLER, &sellers);
+        Self::emit(&env, (symbol_short!("LSeller"), link_id), seller);
+    }
+
+    pub fn get_link_seller(env: Env, link_id: u64) -> Option<Address> {
+        Self::link_sellers(&env).get(link_id)
+    }
+
+    pub fn seller_earnings(env: Env, platform: Address, seller: Address) -> i128 {
+        Self::get_record(&env, &(SELLEARN, platform, seller)).unwrap_or(0)
+    }
+
+    // The seller and its share of `amount` for a link in an active marketplace.
+    pub(crate) fn seller_share(
+        env: &Env,
+        link_id: u64,
+        platform: &Address,
+        amount: &i128,
+    ) -> Option<(Address, i128)> {
+        let seller = Self::link_sellers(env).get(link_id)?;
+        let bps = Self::marketplace_fees(env).get(platform.clone())?;
+        let share = amount - Self::bps_of(env, amount, bps);
+        if share <= 0 {
+            return None;
+        }
+        Some((seller, share))
+    }
+
+    // Pays a seller's share straight from the payer, less the protocol fee on it. Returns the fee.
+    pub(crate) fn settle_seller(
+        env: &Env,
+        spender: &Address,
+        from: &Address,
+        platform: &Address,
+        seller: &Address,
+        amount: &i128,
+    ) -> i128 {
+        Self::assert_not_paused(env);
+        Self::track_volume(env, amount);
+        Self::track_spending(env, from, amount);
+        let fee = Self::fee_for(env, amount);
+        let paid = amount - fee;
+        Self::transfer_from(env, spender, from, seller, &paid);
+        if fee > 0 {
+            Self::transfer_from(env, spender, from, &env.current_contract_address(), &fee);
+            Self::accrue_fee(env, &fee);
+        }
+        let earned = Self::seller_earnings(env.clone(), platform.clone(), seller.clone());
+        Self::put_record(
+            env,
+            &(SELLEARN, platform.clone(), seller.clone()),
+            &Self::checked(env, earned.checked_add(paid)),
+        );
+        fee
+    }
+
+    // Payment paths that cannot split funds at payment time refuse seller links.
+    pub(crate) fn assert_no_seller(env: &Env, link_id: u64) {
+        assert!(
+            !Self::link_sellers(env).contains_key(link_id),
+            "marketplace link"
+        );
+    }
+
+    fn marketplace_fees(env: &Env) -> Map<Address, u32> {
+        env.storage()
+            .instance()
+            .get(&MKTFEE)
+            .unwrap_or(Map::new(env))
+    }
+
+    fn link_sellers(env: &Env) -> Map<u64, Address> {
+        env.storage()
+            .instance()
+            .get(&LSELLER)
+            .unwrap_or(Map::new(env))
+    }
+}
@@ -0,0 +1,277 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, String, Symbol, Timepoint, Vec};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentSource, Receipt, Subscription,
+    SubscriptionPlan, SubscriptionStatus, SPLAN,
+};
+
+// plan ids billed per unit of usage; the plan amount is the unit price
+const METERED: Symbol = symbol_short!("METERED");
+// merchant -> addresses allowed to report usage for its plans
+const METERS: Symbol = symbol_short!("METERS");
+// (USAGE, subscriber, subscription id) -> units used in the open period
+const USAGE: Symbol = symbol_short!("USAGE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn create_metered_plan(
+        env: Env,
+        invoker: Address,
+        unit_price: i128,
+        interval: u32,
+        name: String,
+    ) -> u64 {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        assert!(unit_price > 0, "amount>0");
+        assert!(interval > 0, "interval>0");
+        let plan_id = Self::add_plan(
+            &env,
+            SubscriptionPlan {
+                merchant,
+                amount: unit_price,
+                interval,
+                active: true,
+                name,
+                donation: false,
+            },
+        );
+        let mut metered: Map<u64, bool> = env
+            .storage()
+            .instance()
+            .get(&METERED)
+            .unwrap_or(Map::new(&env));
+        metered.set(plan_id, true);
+        env.storage().instance().set(&METERED, &metered);
+        plan_id
+    }
+
+    pub fn is_metered(env: Env, plan_id: u64) -> bool {
+        let metered: Map<u64, bool> = env
+            .storage()
+            .instance()
+            .get(&METERED)
+            .unwrap_or(Map::new(&env));
+        metered.contains_key(plan_id)
+    }
+
+    // Nothing is charged up front; `max_charge` caps what a single period can cost and is
+    // tracked as the subscription amount.
+    pub fn subscribe_metered(env: Env, invoker: Address, plan_id: u64, max_charge: i128) -> u64 {
+        invoker.require_auth();
+        assert!(Self::is_metered(env.clone(), plan_id), "not metered");
+        assert!(max_charge > 0, "max>0");
+        let plan = Self::metered_plan(&env, plan_id);
+        assert!(plan.active, "plan not active");
+        Self::assert_not_blocked(&env, &plan.merchant, &invoker);
+        Self::count_subscriber(&env, &plan.merchant, true);
+        Self::count_plan_subscriber(&env, plan_id, &max_charge, true);
+        let now = Timepoint::from_unix(&env, env.ledger().timestamp());
+        let ctr = Self::next_subscription_id(&env, &invoker);
+        Self::index_plan_subscriber(&env, plan_id, &invoker, ctr);
+        let sub = Subscription {
+            subscriber: invoker.clone(),
+            payer: invoker.clone(),
+            plan_id,
+            amount: max_charge,
+            start_time: now.clone(),
+            last_payment: now,
+            prepaid: 0,
+            cycles: 1,
+            last_receipt: 0,
+            status: SubscriptionStatus::Active,
+            quantity: 1,
+        };
+        Self::store_subscription(&env, ctr, &sub);
+        Self::issue_pass(&env, ctr, &sub, &plan);
+        Self::emit(&env, (symbol_short!("Subd"), ctr), ctr);
+        ctr
+    }
+
+    pub fn set_usage_cap(env: Env, invoker: Address, subscription_id: u64, max_charge: i128) {
+        invoker.require_auth();
+        assert!(max_charge > 0, "max>0");
+        let mut sub = Self::load_subscription(&env, &invoker, subscription_id);
+        assert!(sub.status.is_live(), "sub inactive");
+        assert!(Self::is_metered(env.clone(), sub.plan_id), "not metered");
+        Self::count_plan_subscriber(&env, sub.plan_id, &sub.amount, false);
+        Self::count_plan_subscriber(&env, sub.plan_id, &max_charge, true);
+        sub.amount = max_charge;
+        Self::store_subscription(&env, subscription_id, &sub);
+        Self::emit(
+            &env,
+            (symbol_short!("UsageCap"), subscription_id),
+            max_charge,
+        );
+    }
+
+    pub fn add_meter(env: Env, invoker: Address, meter: Address) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not merchant");
+        let mut meters = Self::get_meters(env.clone(), invoker.clone());
+        if !meters.contains(&meter) {
+            meters.push_back(meter.clone());
+        }
+        Self::save_meters(&env, &invoker, &meters);
+        Self::emit(&env, (symbol_short!("MeterAdd"), invoker), meter);
+    }
+
+    pub fn remove_meter(env: Env, invoker: Address, meter: Address) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not merchant");
+        let mut meters = Self::get_meters(env.clone(), invoker.clone());
+        let idx = meters.first_index_of(&meter).expect("not meter");
+        meters.remove(idx);
+        Self::save_meters(&env, &invoker, &meters);
+        Self::emit(&env, (symbol_short!("MeterRm"), invoker), meter);
+    }
+
+    pub fn get_meters(env: Env, merchant: Address) -> Vec<Address> {
+        let all: Map<Address, Vec<Address>> = env
+            .storage()
+            .instance()
+            .get(&METERS)
+            .unwrap_or(Map::new(&env));
+        all.get(merchant).unwrap_or(Vec::new(&env))
+    }
+
+    pub fn record_usage(env: Env, invoker: Address, subscriber: Address, plan_id: u64, units: u64) {
+        invoker.require_auth();
+        assert!(units > 0, "units>0");
+        assert!(Self::is_metered(env.clone(), plan_id), "not metered");
+        let plan = Self::metered_plan(&env, plan_id);
+        assert!(
+            invoker == plan.merchant
+                || Self::get_meters(env.clone(), plan.merchant.clone()).contains(&invoker),
+            "not meter"
+        );
+        let mut found = None;
+        for (who, id) in Self::plan_subscribers(env.clone(), plan_id).iter() {
+            if who == subscriber {
+                found = Some(id);
+            }
+        }
+        let subscription_id = found.expect("no sub");
+        let sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        assert!(sub.status.is_billable(), "not billable");
+        let total = Self::usage(env.clone(), subscriber.clone(), subscription_id) + units;
+        Self::set_usage(&env, &subscriber, subscription_id, total);
+        Self::emit(
+            &env,
+            (symbol_short!("Usage"), subscription_id),
+            (subscriber, units),
+        );
+    }
+
+    pub fn usage(env: Env, subscriber: Address, subscription_id: u64) -> u64 {
+        Self::get_record(&env, &(USAGE, subscriber, subscription_id)).unwrap_or(0)
+    }
+
+    // Bills the period that just ended and opens the next one.
+    pub fn close_usage_period(
+        env: Env,
+        invoker: Address,
+        subscriber: Address,
+        subscription_id: u64,
+    ) {
+        invoker.require_auth();
+        let mut sub = Self::load_subscription(&env, &subscriber, subscription_id);
+        assert!(sub.status.is_billable(), "not billable");
+        assert!(Self::is_metered(env.clone(), sub.plan_id), "not metered");
+        let plan = Self::metered_plan(&env, sub.plan_id);
+        Self::assert_can_charge(&env, &invoker, &plan.merchant, &subscriber);
+        let now = env.ledger().timestamp();
+        assert!(now >= Self::due_at(&sub, &plan), "not due");
+        if sub.status == SubscriptionStatus::PastDue {
+            Self::transition(&env, subscription_id, &mut sub, SubscriptionStatus::Active);
+        }
+        sub.cycles += 1;
+        sub.last_payment = Timepoint::from_unix(&env, now);
+        if Self::term_reached(&env, &sub) {
+            Self::transition(&env, subscription_id, &mut sub, SubscriptionStatus::Expired);
+            Self::retire_subscription(&env, subscription_id, &sub, &plan);
+        } else {
+            Self::issue_pass(&env, subscription_id, &sub, &plan);
+        }
+        Self::store_subscription(&env, subscription_id, &sub);
+        if let Some(receipt_id) = Self::bill_usage(&env, subscription_id, &sub, &plan) {
+            sub.last_receipt = receipt_id;
+            Self::store_subscription(&env, subscription_id, &sub);
+        }
+    }
+
+    // Charges `units * unit_price`, capped by the subscription amount, against the payer's
+    // allowance to the gateway and resets the meter.
+    pub(crate) fn bill_usage(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+    ) -> Option<u32> {
+        let units = Self::usage(env.clone(), sub.subscriber.clone(), subscription_id);
+        if units == 0 {
+            return None;
+        }
+        Self::set_usage(env, &sub.subscriber, subscription_id, 0);
+        let mut charge = Self::checked(env, plan.amount.checked_mul(units as i128));
+        if charge > sub.amount {
+            charge = sub.amount;
+        }
+        let gateway = env.current_contract_address();
+        let (shared, shared_fee) =
+            Self::pay_revenue_shares(env, Some((&gateway, &sub.payer)), sub.plan_id, &charge);
+        let fee = Self::settle(
+            env,
+            &gateway,
+            &sub.payer,
+            &plan.merchant,
+            &(charge - shared),
+        );
+        let receipt = Receipt {
+            fee: fee + shared_fee,
+            ..Receipt::new(
+                env,
+                PaymentSource::Subscription(sub.subscriber.clone(), subscription_id),
+                plan.merchant.clone(),
+                sub.payer.clone(),
+                sub.subscriber.clone(),
+                charge,
+            )
+        };
+        let receipt_id = Self::record_receipt(env, &receipt);
+        Self::emit(
+            env,
+            (symbol_short!("SPay"), subscription_id),
+            Self::payment_event(env, receipt_id, &receipt),
+        );
+        Some(receipt_id)
+    }
+
+    fn metered_plan(env: &Env, plan_id: u64) -> SubscriptionPlan {
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(env));
+        plans.get(plan_id).expect("plan not found")
+    }
+
+    fn set_usage(env: &Env, subscriber: &Address, subscription_id: u64, units: u64) {
+        let key = (USAGE, subscriber.clone(), subscription_id);
+        if units == 0 {
+            Self::drop_record(env, &key);
+        } else {
+            Self::put_record(env, &key, &units);
+        }
+    }
+
+    fn save_meters(env: &Env, merchant: &Address, meters: &Vec<Address>) {
+        let mut all: Map<Address, Vec<Address>> = env
+            .storage()
+            .instance()
+            .get(&METERS)
+            .unwrap_or(Map::new(env));
+        all.set(merchant.clone(), meters.clone());
+        env.storage().instance().set(&METERS, &all);
+    }
+}
@@ -0,0 +1,214 @@
+use soroban_sdk::{
+    contractimpl, contracttype, panic_with_error, symbol_short, Address, Env, Map, String, Symbol,
+    SymbolStr, Timepoint, TryFromVal, Vec, I256,
+};
+
+use crate::{
+    lifecycle, Error, MerchantRecord, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient,
+    PaymentLink, Subscription, SubscriptionPlan, SubscriptionStatus, LCTR, PCTR, PLINK, SPLAN,
+    SUBS,
+};
+
+// Storage layout version; deployments of the original release have none set.
+pub(crate) const SCHEMA: Symbol = symbol_short!("SCHEMA");
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+// keys only the original release wrote
+const LEGACY_MERCH: Symbol = symbol_short!("MERCH");
+const LEGACY_SCTR: Symbol = symbol_short!("SCTR");
+
+// Layouts written by the original release, which stored amounts as I256, names as Symbols
+// and ids as u32, and tracked subscriptions with an `active` flag.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct LegacyPaymentLink {
+    merchant: Address,
+    amount: I256,
+    active: bool,
+    description: Symbol,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct LegacySubscriptionPlan {
+    merchant: Address,
+    amount: I256,
+    interval: u32,
+    active: bool,
+    name: Symbol,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct LegacySubscription {
+    subscriber: Address,
+    plan_id: u32,
+    start_time: Timepoint,
+    last_payment: Timepoint,
+    active: bool,
+}
+
+#[contractimpl]
+impl PaymentGateway {
+    // Brings a deployment of the original release up to the current layout in one call. It
+    // fails with AmountOverflow if a stored amount does not fit an i128. Creating links and
+    // plans is blocked on an upgraded deployment until this has run, and until then no
+    // merchant is recognised.
+    pub fn migrate(env: Env, invoker: Address) {
+        Self::only_owner(&env, &invoker);
+        assert!(!env.storage().instance().has(&SCHEMA), "already migrated");
+        Self::migrate_merchants(&env);
+        Self::migrate_links(&env);
+        let plans = Self::migrate_plans(&env);
+        Self::migrate_subscriptions(&env, &plans);
+        env.storage().instance().set(&SCHEMA, &SCHEMA_VERSION);
+        Self::emit(&env, (symbol_short!("Migrated"),), SCHEMA_VERSION);
+    }
+
+    pub(crate) fn assert_migrated(env: &Env) {
+        let version: Option<u32> = env.storage().instance().get(&SCHEMA);
+        assert!(version == Some(SCHEMA_VERSION), "migration pending");
+    }
+
+    fn migrate_merchants(env: &Env) {
+        let legacy: Vec<Address> = env
+            .storage()
+            .instance()
+            .get(&LEGACY_MERCH)
+            .unwrap_or(Vec::new(env));
+        // the Vec never recorded when merchants joined
+        let added_at = Timepoint::from_unix(env, env.ledger().timestamp());
+        for merchant in legacy.iter() {
+            Self::store_merchant(
+                env,
+                &merchant,
+                &MerchantRecord {
+                    added_at: added_at.clone(),
+                },
+            );
+        }
+        env.storage().instance().remove(&LEGACY_MERCH);
+    }
+
+    // Active links are also entered in the merchant's catalog and stats, and every link gets
+    // a short code, as if it had been created on this release.
+    fn migrate_links(env: &Env) {
+        let legacy: Map<u32, LegacyPaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(env));
+        let mut links: Map<u64, PaymentLink> = Map::new(env);
+        for (id, l) in legacy.iter() {
+            let id = id as u64;
+            if l.active {
+                Self::count_link(env, &l.merchant, true);
+                Self::index_link(env, &l.merchant, id, true);
+            }
+            Self::issue_link_code(env, &l.merchant, id);
+            links.set(
+                id,
+                PaymentLink {
+                    merchant: l.merchant,
+                    amount: Self::narrow(env, &l.amount),
+                    active: l.active,
+                    description: Self::symbol_text(env, &l.description),
+                },
+            );
+        }
+        env.storage().instance().set(&PLINK, &links);
+        let ctr: u32 = env.storage().instance().get(&LCTR).unwrap_or(0);
+        env.storage().instance().set(&LCTR, &(ctr as u64));
+    }
+
+    fn migrate_plans(env: &Env) -> Map<u64, SubscriptionPlan> {
+        let legacy: Map<u32, LegacySubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(env));
+        let mut plans: Map<u64, SubscriptionPlan> = Map::new(env);
+        for (id, p) in legacy.iter() {
+            let id = id as u64;
+            if p.active {
+                Self::index_plan(env, &p.merchant, id, true);
+            }
+            plans.set(
+                id,
+                SubscriptionPlan {
+                    merchant: p.merchant,
+                    amount: Self::narrow(env, &p.amount),
+                    interval: p.interval,
+                    active: p.active,
+                    name: Self::symbol_text(env, &p.name),
+                    donation: false,
+                },
+            );
+        }
+        env.storage().instance().set(&SPLAN, &plans);
+        let ctr: u32 = env.storage().instance().get(&PCTR).unwrap_or(0);
+        env.storage().instance().set(&PCTR, &(ctr as u64));
+        plans
+    }
+
+    // Subscriptions were paid by their subscriber at the plan price. The old counter was
+    // shared by all subscribers; each subscriber's own counter starts from the highest id
+    // they already hold so new ids never land on old ones.
+    fn migrate_subscriptions(env: &Env, plans: &Map<u64, SubscriptionPlan>) {
+        let legacy: Map<(Address, u32), LegacySubscription> =
+            env.storage().instance().get(&SUBS).unwrap_or(Map::new(env));
+        let mut subs: Map<(Address, u64), Subscription> = Map::new(env);
+        let mut counters: Map<Address, u64> = Map::new(env);
+        for ((subscriber, id), s) in legacy.iter() {
+            let id = id as u64;
+            let plan_id = s.plan_id as u64;
+            let plan = plans.get(plan_id).expect("plan not found");
+            if s.active {
+                Self::count_subscriber(env, &plan.merchant, true);
+                Self::count_plan_subscriber(env, plan_id, &plan.amount, true);
+                Self::index_plan_subscriber(env, plan_id, &subscriber, id);
+            }
+            if counters.get(subscriber.clone()).unwrap_or(0) < id {
+                counters.set(subscriber.clone(), id);
+            }
+            // no period count was kept; each charge came at least one interval after the last
+            let elapsed = s.last_payment.to_unix() - s.start_time.to_unix();
+            let cycles = elapsed / plan.interval as u64 + 1;
+            let status = if s.active {
+                SubscriptionStatus::Active
+            } else {
+                SubscriptionStatus::Canceled
+            };
+            subs.set(
+                (subscriber.clone(), id),
+                Subscription {
+                    subscriber: s.subscriber,
+                    payer: subscriber,
+                    plan_id,
+                    amount: plan.amount,
+                    start_time: s.start_time,
+                    last_payment: s.last_payment,
+                    prepaid: 0,
+                    cycles: u32::try_from(cycles).unwrap_or(u32::MAX),
+                    last_receipt: 0,
+                    status,
+                    quantity: 1,
+                },
+            );
+        }
+        env.storage().instance().set(&SUBS, &subs);
+        env.storage().instance().set(&lifecycle::SUBCTR, &counters);
+        env.storage().instance().remove(&LEGACY_SCTR);
+    }
+
+    fn narrow(env: &Env, amount: &I256) -> i128 {
+        match amount.to_i128() {
+            Some(amount) => amount,
+            None => panic_with_error!(env, Error::AmountOverflow),
+        }
+    }
+
+    fn symbol_text(env: &Env, sym: &Symbol) -> String {
+        let s = SymbolStr::try_from_val(env, &sym.to_symbol_val()).expect("bad symbol");
+        String::from_str(env, s.as_ref())
+    }
+}
@@ -0,0 +1,193 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MilestoneStatus {
+    Pending,
+    Released,
+    Refunded,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Milestone {
+    name: Symbol,
+    amount: i128,
+    status: MilestoneStatus,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MilestoneLink {
+    merchant: Address,
+    payer: Option<Address>,
+    arbiter: Option<Address>,
+    milestones: Vec<Milestone>,
+    total: i128,
+    active: bool,
+}
+
+const MCTR: Symbol = symbol_short!("MCTR");
+const MLINK: Symbol = symbol_short!("MLINK");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn create_milestone_link(
+        env: Env,
+        invoker: Address,
+        names: Vec<Symbol>,
+        amounts: Vec<i128>,
+        arbiter: Option<Address>,
+    ) -> u32 {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(!names.is_empty(), "no milestones");
+        assert!(names.len() == amounts.len(), "length mismatch");
+        if let Some(arbiter) = &arbiter {
+            assert!(
+                Self::is_arbiter(env.clone(), arbiter.clone()),
+                "not arbiter"
+            );
+        }
+        let mut milestones = Vec::new(&env);
+        let mut total: i128 = 0;
+        for i in 0..names.len() {
+            let amount = amounts.get_unchecked(i);
+            assert!(amount > 0, "amount>0");
+            total = Self::checked(&env, total.checked_add(amount));
+            milestones.push_back(Milestone {
+                name: names.get_unchecked(i),
+                amount,
+                status: MilestoneStatus::Pending,
+            });
+        }
+        let ctr = Self::next_id(&env, &MCTR);
+        Self::save_milestone_link(
+            &env,
+            ctr,
+            &MilestoneLink {
+                merchant: invoker,
+                payer: None,
+                arbiter,
+                milestones,
+                total,
+                active: true,
+            },
+        );
+        Self::emit(&env, (symbol_short!("MsCr"), ctr), ctr);
+        ctr
+    }
+
+    // The payer escrows the full amount up front; releases then happen per milestone.
+    pub fn fund_milestones(env: Env, invoker: Address, link_id: u32) {
+        invoker.require_auth();
+        let mut link = Self::get_milestone_link(env.clone(), link_id);
+        assert!(link.active, "inactive link");
+        assert!(link.payer.is_none(), "already funded");
+        link.payer = Some(invoker.clone());
+        Self::save_milestone_link(&env, link_id, &link);
+        Self::collect(&env, &invoker, &link.total);
+        Self::emit(&env, (symbol_short!("MsFund"), link_id), &invoker);
+    }
+
+    pub fn approve_milestone(env: Env, invoker: Address, link_id: u32, index: u32) {
+        invoker.require_auth();
+        let link = Self::get_milestone_link(env.clone(), link_id);
+        assert!(link.payer == Some(invoker), "not payer");
+        Self::emit(&env, (symbol_short!("MsAppr"), link_id), index);
+        Self::settle_milestone(&env, link_id, link, index, true, None);
+    }
+
+    pub fn resolve_milestone(env: Env, invoker: Address, link_id: u32, index: u32, release: bool) {
+        invoker.require_auth();
+        let link = Self::get_milestone_link(env.clone(), link_id);
+        assert!(link.arbiter == Some(invoker.clone()), "not arbiter");
+        Self::settle_milestone(&env, link_id, link, index, release, Some(invoker));
+    }
+
+    pub fn refund_milestone(env: Env, invoker: Address, link_id: u32, index: u32) {
+        invoker.require_auth();
+        let link = Self::get_milestone_link(env.clone(), link_id);
+        assert!(link.merchant == invoker, "not merchant");
+        Self::settle_milestone(&env, link_id, link, index, false, None);
+    }
+
+    pub fn get_milestone_link(env: Env, link_id: u32) -> MilestoneLink {
+        let links: Map<u32, MilestoneLink> = env
+            .storage()
+            .instance()
+            .get(&MLINK)
+            .unwrap_or(Map::new(&env));
+        links.get(link_id).expect("no link")
+    }
+
+    fn settle_milestone(
+        env: &Env,
+        link_id: u32,
+        mut link: MilestoneLink,
+        index: u32,
+        release: bool,
+        arbiter: Option<Address>,
+    ) {
+        assert!(link.active, "inactive link");
+        let payer = link.payer.clone().expect("not funded");
+        let mut m = link.milestones.get(index).expect("no milestone");
+        assert!(m.status == MilestoneStatus::Pending, "already settled");
+        m.status = if release {
+            MilestoneStatus::Released
+        } else {
+            MilestoneStatus::Refunded
+        };
+        link.milestones.set(index, m.clone());
+        link.active = link
+            .milestones
+            .iter()
+            .any(|m| m.status == MilestoneStatus::Pending);
+        Self::save_milestone_link(env, link_id, &link);
+        // a ruling costs the arbitration fee, taken from the disputed milestone
+        let amount = match arbiter {
+            Some(arbiter) => Self::pay_arbiter(env, &arbiter, &m.amount),
+            None => m.amount,
+        };
+        if release {
+            Self::release(env, &link.merchant, &amount);
+            Self::emit(env, (symbol_short!("MsRel"), link_id), (index, amount));
+        } else {
+            Self::payout(env, &payer, &amount);
+            Self::emit(env, (symbol_short!("MsRefd"), link_id), (index, amount));
+        }
+    }
+
+    fn save_milestone_link(env: &Env, link_id: u32, link: &MilestoneLink) {
+        let mut links: Map<u32, MilestoneLink> = env
+            .storage()
+            .instance()
+            .get(&MLINK)
+            .unwrap_or(Map::new(env));
+        links.set(link_id, link.clone());
+        env.storage().instance().set(&MLINK, &links);
+    }
+
+    // Funded milestones still waiting to be released or refunded.
+    pub(crate) fn milestones_held(env: &Env) -> i128 {
+        let links: Map<u32, MilestoneLink> = env
+            .storage()
+            .instance()
+            .get(&MLINK)
+            .unwrap_or(Map::new(env));
+        let mut total = 0;
+        for (_, link) in links.iter() {
+            if link.payer.is_none() {
+                continue;
+            }
+            for m in link.milestones.iter() {
+                if m.status == MilestoneStatus::Pending {
+                    total += m.amount;
+                }
+            }
+        }
+        total
+    }
+}
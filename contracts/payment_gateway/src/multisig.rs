@@ -0,0 +1,198 @@
+use soroban_sdk::{
+    contractimpl, contracttype, symbol_short, Address, BytesN, Env, Map, Symbol, Vec,
+};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// Owner operations that, once a multisig is configured, run only through an approved proposal.
+// Sensitive ones also wait out the timelock delay, see `schedule_op`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AdminAction {
+    SetFee(u32, Address),
+    RemoveMerchant(Address),
+    Upgrade(BytesN<32>),
+    SetAdmins(Vec<Address>, u32),
+    SetToken(Address),
+    SetTimelockDelay(u64),
+    RescueTokens(Address, Address, i128),
+    SetFeeShares(Vec<(Address, u32)>),
+    SetPaused(bool),
+    SetGovernor(Option<Address>),
+    SetInsurance(u32, i128),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Proposal {
+    action: AdminAction,
+    approvals: Vec<Address>,
+    expires_at: u64,
+    executed: bool,
+}
+
+const ADMINS: Symbol = symbol_short!("ADMINS");
+const THRESH: Symbol = symbol_short!("THRESH");
+const MSCTR: Symbol = symbol_short!("MSCTR");
+const PROPS: Symbol = symbol_short!("PROPS");
+// proposals that have not reached the threshold by then can no longer be approved
+const PROPOSAL_TTL: u64 = 7 * 86400;
+
+#[contractimpl]
+impl PaymentGateway {
+    // One-off hand-over from the single owner key; later changes go through `SetAdmins`.
+    pub fn configure_multisig(env: Env, invoker: Address, admins: Vec<Address>, threshold: u32) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        Self::store_admins(&env, &admins, threshold);
+    }
+
+    pub fn get_admins(env: Env) -> (Vec<Address>, u32) {
+        (
+            env.storage()
+                .instance()
+                .get(&ADMINS)
+                .unwrap_or(Vec::new(&env)),
+            env.storage().instance().get(&THRESH).unwrap_or(0),
+        )
+    }
+
+    // The proposer's approval counts towards the threshold.
+    pub fn propose(env: Env, invoker: Address, action: AdminAction) -> u32 {
+        Self::only_admin(&env, &invoker);
+        Self::assert_not_governed(&env, &action);
+        let ctr = Self::next_id(&env, &MSCTR);
+        let proposal = Proposal {
+            action,
+            approvals: Vec::new(&env),
+            expires_at: env.ledger().timestamp() + PROPOSAL_TTL,
+            executed: false,
+        };
+        Self::emit(&env, (symbol_short!("Proposed"), ctr), &invoker);
+        Self::add_approval(&env, ctr, proposal, &invoker);
+        ctr
+    }
+
+    // Executes the action as soon as the approval reaches the threshold.
+    pub fn approve(env: Env, invoker: Address, proposal_id: u32) {
+        Self::only_admin(&env, &invoker);
+        let proposal = Self::get_proposal(env.clone(), proposal_id);
+        assert!(!proposal.executed, "already executed");
+        assert!(
+            env.ledger().timestamp() < proposal.expires_at,
+            "proposal expired"
+        );
+        assert!(!proposal.approvals.contains(&invoker), "already approved");
+        Self::add_approval(&env, proposal_id, proposal, &invoker);
+    }
+
+    // Anyone may clear out a proposal that expired without executing.
+    pub fn drop_expired_proposal(env: Env, proposal_id: u32) {
+        let proposal = Self::get_proposal(env.clone(), proposal_id);
+        assert!(!proposal.executed, "already executed");
+        assert!(
+            env.ledger().timestamp() >= proposal.expires_at,
+            "not expired"
+        );
+        let mut proposals: Map<u32, Proposal> = env
+            .storage()
+            .instance()
+            .get(&PROPS)
+            .unwrap_or(Map::new(&env));
+        proposals.remove(proposal_id);
+        env.storage().instance().set(&PROPS, &proposals);
+        Self::emit(&env, (symbol_short!("PropDrop"), proposal_id), proposal_id);
+    }
+
+    pub fn get_proposal(env: Env, proposal_id: u32) -> Proposal {
+        let proposals: Map<u32, Proposal> = env
+            .storage()
+            .instance()
+            .get(&PROPS)
+            .unwrap_or(Map::new(&env));
+        proposals.get(proposal_id).expect("no proposal")
+    }
+
+    // Guards the owner entrypoints that a configured multisig takes over.
+    pub(crate) fn assert_single_key(env: &Env) {
+        assert!(!env.storage().instance().has(&ADMINS), "multisig required");
+    }
+
+    fn only_admin(env: &Env, invoker: &Address) {
+        invoker.require_auth();
+        let (admins, _) = Self::get_admins(env.clone());
+        assert!(admins.contains(invoker), "not admin");
+    }
+
+    fn store_admins(env: &Env, admins: &Vec<Address>, threshold: u32) {
+        assert!(threshold > 0, "threshold>0");
+        assert!(threshold <= admins.len(), "threshold>admins");
+        for (i, admin) in admins.iter().enumerate() {
+            assert!(
+                admins.first_index_of(&admin) == Some(i as u32),
+                "duplicate admin"
+            );
+        }
+        env.storage().instance().set(&ADMINS, admins);
+        env.storage().instance().set(&THRESH, &threshold);
+        Self::emit(env, (symbol_short!("Admins"),), (admins.clone(), threshold));
+    }
+
+    // Approvals from admins that have since been removed no longer count.
+    fn add_approval(env: &Env, proposal_id: u32, mut proposal: Proposal, admin: &Address) {
+        proposal.approvals.push_back(admin.clone());
+        proposal.executed = Self::has_quorum(env, &proposal);
+        let mut proposals: Map<u32, Proposal> = env
+            .storage()
+            .instance()
+            .get(&PROPS)
+            .unwrap_or(Map::new(env));
+        proposals.set(proposal_id, proposal.clone());
+        env.storage().instance().set(&PROPS, &proposals);
+        Self::emit(env, (symbol_short!("Approved"), proposal_id), admin);
+        if proposal.executed {
+            // a governor may have taken the parameter over since the proposal was made
+            Self::assert_not_governed(env, &proposal.action);
+            Self::run_or_schedule(env, proposal.action, Some(proposal_id));
+            Self::emit(env, (symbol_short!("Executed"), proposal_id), proposal_id);
+        }
+    }
+
+    // Re-checked when a timelocked proposal comes due, against the admins at that time.
+    pub(crate) fn assert_quorum(env: &Env, proposal_id: u32) {
+        let proposal = Self::get_proposal(env.clone(), proposal_id);
+        assert!(Self::has_quorum(env, &proposal), "approvals lapsed");
+    }
+
+    fn has_quorum(env: &Env, proposal: &Proposal) -> bool {
+        let (admins, threshold) = Self::get_admins(env.clone());
+        let current = proposal
+            .approvals
+            .iter()
+            .filter(|a| admins.contains(a))
+            .count() as u32;
+        current >= threshold
+    }
+
+    pub(crate) fn run_action(env: &Env, action: AdminAction) {
+        match action {
+            AdminAction::SetFee(fee_bps, treasury) => Self::store_fee(env, fee_bps, treasury),
+            AdminAction::RemoveMerchant(merchant) => Self::drop_merchant(env, &merchant),
+            AdminAction::Upgrade(wasm_hash) => {
+                env.deployer().update_current_contract_wasm(wasm_hash)
+            }
+            AdminAction::SetAdmins(admins, threshold) => {
+                Self::store_admins(env, &admins, threshold)
+            }
+            AdminAction::SetToken(token) => Self::set_token(env, token),
+            AdminAction::SetTimelockDelay(seconds) => Self::store_delay(env, seconds),
+            AdminAction::RescueTokens(token, to, amount) => Self::sweep(env, &token, &to, &amount),
+            AdminAction::SetFeeShares(shares) => Self::store_fee_shares(env, shares),
+            AdminAction::SetPaused(paused) => Self::store_paused(env, paused),
+            AdminAction::SetGovernor(governor) => Self::store_governor(env, governor),
+            AdminAction::SetInsurance(fee_share_bps, cap) => {
+                Self::store_insurance(env, fee_share_bps, cap)
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, IntoVal, InvokeError, Symbol, Vec};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentSource, Receipt, LOCK,
+};
+
+// receipt-nft contract minting a proof-of-purchase token per recorded payment
+const RNFT: Symbol = symbol_short!("RNFT");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_receipt_nft(env: Env, invoker: Address, nft: Option<Address>) {
+        Self::only_owner(&env, &invoker);
+        match nft.clone() {
+            Some(n) => env.storage().instance().set(&RNFT, &n),
+            None => env.storage().instance().remove(&RNFT),
+        }
+        Self::emit(&env, (symbol_short!("RcptNft"),), nft);
+    }
+
+    pub fn get_receipt_nft(env: Env) -> Option<Address> {
+        env.storage().instance().get(&RNFT)
+    }
+
+    // The token goes to the customer the payment was made for, not a sponsoring payer. A
+    // failed mint is reported with an event and never reverts the payment it records.
+    pub(crate) fn mint_receipt_nft(env: &Env, receipt_id: u32, receipt: &Receipt) {
+        let nft = match Self::get_receipt_nft(env.clone()) {
+            Some(n) => n,
+            None => return,
+        };
+        let link = match receipt.source {
+            PaymentSource::Link(id) => Some(id),
+            _ => None,
+        };
+        env.storage().instance().set(&LOCK, &true);
+        let minted = env.try_invoke_contract::<(), InvokeError>(
+            &nft,
+            &Symbol::new(env, "mint"),
+            Vec::from_array(
+                env,
+                [
+                    receipt_id.into_val(env),
+                    receipt.customer.into_val(env),
+                    receipt.merchant.into_val(env),
+                    link.into_val(env),
+                    receipt.amount.into_val(env),
+                    receipt.timestamp.into_val(env),
+                ],
+            ),
+        );
+        env.storage().instance().remove(&LOCK);
+        if !matches!(minted, Ok(Ok(()))) {
+            Self::emit(env, (symbol_short!("NftFail"), receipt_id), nft);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// merchant -> operators, and operator -> merchant for auth lookups
+const OPS: Symbol = symbol_short!("OPS");
+const OPOF: Symbol = symbol_short!("OPOF");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn add_operator(env: Env, invoker: Address, operator: Address) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(operator != invoker, "merchant is not an operator");
+        let mut owners: Map<Address, Address> = env
+            .storage()
+            .instance()
+            .get(&OPOF)
+            .unwrap_or(Map::new(&env));
+        assert!(!owners.contains_key(operator.clone()), "operator taken");
+        owners.set(operator.clone(), invoker.clone());
+        env.storage().instance().set(&OPOF, &owners);
+        let mut all: Map<Address, Vec<Address>> =
+            env.storage().instance().get(&OPS).unwrap_or(Map::new(&env));
+        let mut ops = all.get(invoker.clone()).unwrap_or(Vec::new(&env));
+        ops.push_back(operator.clone());
+        all.set(invoker.clone(), ops);
+        env.storage().instance().set(&OPS, &all);
+        Self::emit(&env, (symbol_short!("OpAdd"), invoker), &operator);
+    }
+
+    pub fn remove_operator(env: Env, invoker: Address, operator: Address) {
+        invoker.require_auth();
+        let mut owners: Map<Address, Address> = env
+            .storage()
+            .instance()
+            .get(&OPOF)
+            .unwrap_or(Map::new(&env));
+        assert!(
+            owners.get(operator.clone()) == Some(invoker.clone()),
+            "not operator"
+        );
+        owners.remove(operator.clone());
+        env.storage().instance().set(&OPOF, &owners);
+        let mut all: Map<Address, Vec<Address>> =
+            env.storage().instance().get(&OPS).unwrap_or(Map::new(&env));
+        let mut ops = all.get(invoker.clone()).unwrap_or(Vec::new(&env));
+        if let Some(i) = ops.first_index_of(&operator) {
+            ops.remove(i);
+        }
+        all.set(invoker.clone(), ops);
+        env.storage().instance().set(&OPS, &all);
+        Self::emit(&env, (symbol_short!("OpRem"), invoker), &operator);
+    }
+
+    pub fn get_operators(env: Env, merchant: Address) -> Vec<Address> {
+        let all: Map<Address, Vec<Address>> =
+            env.storage().instance().get(&OPS).unwrap_or(Map::new(&env));
+        all.get(merchant).unwrap_or(Vec::new(&env))
+    }
+
+    // Resolves the merchant an authorized caller acts for: itself, or the merchant it operates for.
+    pub(crate) fn acting_merchant(env: &Env, invoker: &Address) -> Address {
+        if Self::is_merchant(env, invoker) {
+            return invoker.clone();
+        }
+        let owners: Map<Address, Address> =
+            env.storage().instance().get(&OPOF).unwrap_or(Map::new(env));
+        let merchant = owners.get(invoker.clone()).expect("not authorized");
+        assert!(Self::is_merchant(env, &merchant), "not authorized");
+        merchant
+    }
+}
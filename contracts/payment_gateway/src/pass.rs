@@ -0,0 +1,70 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, IntoVal, Symbol, Timepoint, Val, Vec};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Subscription, SubscriptionPlan, LOCK,
+};
+
+// membership-pass contract tracking which subscribers currently hold a plan
+const MPASS: Symbol = symbol_short!("MPASS");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_membership_pass(env: Env, invoker: Address, pass: Option<Address>) {
+        Self::only_owner(&env, &invoker);
+        match pass.clone() {
+            Some(p) => env.storage().instance().set(&MPASS, &p),
+            None => env.storage().instance().remove(&MPASS),
+        }
+        Self::emit(&env, (symbol_short!("MPass"),), pass);
+    }
+
+    pub fn get_membership_pass(env: Env) -> Option<Address> {
+        env.storage().instance().get(&MPASS)
+    }
+
+    // The pass runs to the end of the current period plus any prepaid ones.
+    pub(crate) fn issue_pass(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+    ) {
+        let periods = (sub.prepaid as u64) + 1;
+        let expires_at = sub.last_payment.to_unix() + periods * (plan.interval as u64);
+        Self::call_pass(
+            env,
+            "issue",
+            Vec::from_array(
+                env,
+                [
+                    sub.subscriber.into_val(env),
+                    subscription_id.into_val(env),
+                    plan.merchant.into_val(env),
+                    sub.plan_id.into_val(env),
+                    Timepoint::from_unix(env, expires_at).into_val(env),
+                ],
+            ),
+        );
+    }
+
+    pub(crate) fn revoke_pass(env: &Env, subscription_id: u64, sub: &Subscription) {
+        Self::call_pass(
+            env,
+            "revoke",
+            Vec::from_array(
+                env,
+                [sub.subscriber.into_val(env), subscription_id.into_val(env)],
+            ),
+        );
+    }
+
+    fn call_pass(env: &Env, func: &str, args: Vec<Val>) {
+        let pass = match Self::get_membership_pass(env.clone()) {
+            Some(p) => p,
+            None => return,
+        };
+        env.storage().instance().set(&LOCK, &true);
+        env.invoke_contract::<()>(&pass, &Symbol::new(env, func), args);
+        env.storage().instance().remove(&LOCK);
+    }
+}
@@ -0,0 +1,138 @@
+use soroban_sdk::{
+    contractimpl, contracttype, symbol_short, Address, BytesN, Env, Map, String, Symbol, Vec,
+};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MerchantCategory {
+    Commerce,
+    Saas,
+    Donations,
+    Services,
+    Digital,
+    Other,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantProfile {
+    name: String,
+    metadata_uri: String,
+    category: MerchantCategory,
+}
+
+const PROF: Symbol = symbol_short!("PROF");
+// category -> merchants whose profile is filed under it
+const CATIDX: Symbol = symbol_short!("CATIDX");
+// ed25519 public key a merchant signs off-chain quotes with
+const QKEY: Symbol = symbol_short!("QKEY");
+const MAX_NAME_LEN: u32 = 64;
+const MAX_URI_LEN: u32 = 256;
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_merchant_profile(
+        env: Env,
+        invoker: Address,
+        name: String,
+        metadata_uri: String,
+        category: MerchantCategory,
+    ) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(!name.is_empty(), "empty name");
+        assert!(name.len() <= MAX_NAME_LEN, "name too long");
+        assert!(metadata_uri.len() <= MAX_URI_LEN, "uri too long");
+        let mut profiles: Map<Address, MerchantProfile> = env
+            .storage()
+            .instance()
+            .get(&PROF)
+            .unwrap_or(Map::new(&env));
+        if let Some(previous) = profiles.get(invoker.clone()) {
+            Self::file_category(&env, previous.category, &invoker, false);
+        }
+        Self::file_category(&env, category, &invoker, true);
+        profiles.set(
+            invoker.clone(),
+            MerchantProfile {
+                name,
+                metadata_uri,
+                category,
+            },
+        );
+        env.storage().instance().set(&PROF, &profiles);
+        Self::emit(&env, (symbol_short!("ProfSet"),), &invoker);
+    }
+
+    pub fn get_merchant_profile(env: Env, merchant: Address) -> Option<MerchantProfile> {
+        let profiles: Map<Address, MerchantProfile> = env
+            .storage()
+            .instance()
+            .get(&PROF)
+            .unwrap_or(Map::new(&env));
+        profiles.get(merchant)
+    }
+
+    pub fn list_merchants_by_category(env: Env, category: MerchantCategory) -> Vec<Address> {
+        let index: Map<MerchantCategory, Vec<Address>> = env
+            .storage()
+            .instance()
+            .get(&CATIDX)
+            .unwrap_or(Map::new(&env));
+        index.get(category).unwrap_or(Vec::new(&env))
+    }
+
+    pub(crate) fn merchant_category(env: &Env, merchant: &Address) -> Option<MerchantCategory> {
+        Self::get_merchant_profile(env.clone(), merchant.clone()).map(|p| p.category)
+    }
+
+    // Removed merchants leave the directory but keep their profile, and rejoin it under that
+    // profile if they are added back.
+    pub(crate) fn list_merchant(env: &Env, merchant: &Address, listed: bool) {
+        if let Some(category) = Self::merchant_category(env, merchant) {
+            Self::file_category(env, category, merchant, listed);
+        }
+    }
+
+    fn file_category(env: &Env, category: MerchantCategory, merchant: &Address, listed: bool) {
+        let mut index: Map<MerchantCategory, Vec<Address>> = env
+            .storage()
+            .instance()
+            .get(&CATIDX)
+            .unwrap_or(Map::new(env));
+        let mut merchants = index.get(category).unwrap_or(Vec::new(env));
+        match merchants.first_index_of(merchant) {
+            Some(i) if !listed => {
+                merchants.remove(i);
+            }
+            None if listed => merchants.push_back(merchant.clone()),
+            _ => {}
+        }
+        index.set(category, merchants);
+        env.storage().instance().set(&CATIDX, &index);
+    }
+
+    pub fn set_quote_key(env: Env, invoker: Address, key: BytesN<32>) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        let mut keys: Map<Address, BytesN<32>> = env
+            .storage()
+            .instance()
+            .get(&QKEY)
+            .unwrap_or(Map::new(&env));
+        keys.set(invoker.clone(), key.clone());
+        env.storage().instance().set(&QKEY, &keys);
+        Self::emit(&env, (symbol_short!("QKeySet"), invoker), key);
+    }
+
+    pub fn get_quote_key(env: Env, merchant: Address) -> Option<BytesN<32>> {
+        let keys: Map<Address, BytesN<32>> = env
+            .storage()
+            .instance()
+            .get(&QKEY)
+            .unwrap_or(Map::new(&env));
+        keys.get(merchant)
+    }
+}
@@ -0,0 +1,117 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Subscription, SubscriptionPlan,
+};
+
+// plans that refund the unused part of the current period on cancellation
+const PRORATE: Symbol = symbol_short!("PRORATE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_prorated_cancel(env: Env, invoker: Address, plan_id: u64, enabled: bool) {
+        invoker.require_auth();
+        Self::assert_plan_owner(&env, &invoker, plan_id);
+        assert!(!Self::is_metered(env.clone(), plan_id), "metered plan");
+        let mut plans: Map<u64, bool> = env
+            .storage()
+            .instance()
+            .get(&PRORATE)
+            .unwrap_or(Map::new(&env));
+        if enabled {
+            plans.set(plan_id, true);
+        } else {
+            plans.remove(plan_id);
+        }
+        env.storage().instance().set(&PRORATE, &plans);
+        Self::emit(&env, (symbol_short!("Prorate"), plan_id), enabled);
+    }
+
+    pub fn is_prorated_cancel(env: Env, plan_id: u64) -> bool {
+        let plans: Map<u64, bool> = env
+            .storage()
+            .instance()
+            .get(&PRORATE)
+            .unwrap_or(Map::new(&env));
+        plans.contains_key(plan_id)
+    }
+
+    // `amount * part / whole`, for splitting a period's price by time.
+    pub(crate) fn prorate(env: &Env, amount: &i128, part: u64, whole: u64) -> i128 {
+        Self::checked(env, amount.checked_mul(part as i128)) / whole as i128
+    }
+
+    // Returns the unused share of the period in progress to the payer. The merchant must
+    // have approved the gateway to draw refunds from its payout address when not custodial.
+    pub(crate) fn refund_unused_period(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+    ) {
+        if !Self::is_prorated_cancel(env.clone(), sub.plan_id) {
+            return;
+        }
+        let unused = Self::unused_share(env, sub, plan, &Self::seat_total(env, sub));
+        Self::credit_period(env, subscription_id, sub, plan, &unused);
+    }
+
+    // Periods a gift paid for up front that have not started yet go back to the payer in full.
+    pub(crate) fn refund_prepaid_periods(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+    ) {
+        if sub.prepaid == 0 {
+            return;
+        }
+        let unstarted = Self::checked(env, sub.amount.checked_mul(sub.prepaid as i128));
+        Self::credit_period(env, subscription_id, sub, plan, &unstarted);
+    }
+
+    // The part of `amount` covering what is left of the period in progress.
+    pub(crate) fn unused_share(
+        env: &Env,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+        amount: &i128,
+    ) -> i128 {
+        let now = env.ledger().timestamp();
+        let due = Self::due_at(sub, plan);
+        if now >= due {
+            return 0;
+        }
+        Self::prorate(env, amount, due - now, plan.interval as u64)
+    }
+
+    // Refunds up to `amount` of the receipt for the period in progress to the payer.
+    pub(crate) fn credit_period(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+        amount: &i128,
+    ) {
+        let mut receipt = Self::get_receipt(env.clone(), sub.last_receipt);
+        let open = receipt.amount - receipt.refunded;
+        let amount = if *amount < open { *amount } else { open };
+        if amount <= 0 {
+            return;
+        }
+        receipt.refunded += amount;
+        Self::save_receipt(env, sub.last_receipt, &receipt);
+        Self::refund_from_merchant(
+            env,
+            &env.current_contract_address(),
+            &plan.merchant,
+            &sub.payer,
+            &amount,
+        );
+        Self::emit(
+            env,
+            (symbol_short!("ProRefund"), subscription_id),
+            (sub.last_receipt, amount),
+        );
+    }
+}
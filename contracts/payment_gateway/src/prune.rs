@@ -0,0 +1,128 @@
+use soroban_sdk::{
+    contractimpl, symbol_short, Address, Env, IntoVal, Map, Symbol, TryFromVal, Val,
+};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, Subscription,
+    SubscriptionPlan, PLINK, SPLAN, SUBS,
+};
+
+// when each link, plan and subscription stopped being live
+const LCLOSE: Symbol = symbol_short!("LCLOSE");
+const PCLOSE: Symbol = symbol_short!("PCLOSE");
+const SCLOSE: Symbol = symbol_short!("SCLOSE");
+const RETAIN: Symbol = symbol_short!("RETAIN");
+const DEFAULT_RETENTION: u64 = 30 * 86400;
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_retention(env: Env, invoker: Address, seconds: u64) {
+        Self::only_owner(&env, &invoker);
+        env.storage().instance().set(&RETAIN, &seconds);
+        Self::emit(&env, (symbol_short!("Retain"),), seconds);
+    }
+
+    pub fn get_retention(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&RETAIN)
+            .unwrap_or(DEFAULT_RETENTION)
+    }
+
+    // The pruning entrypoints are open to anyone; each publishes the deleted record.
+    pub fn prune_link(env: Env, link_id: u64) {
+        Self::assert_retired(&env, &LCLOSE, link_id);
+        let mut links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("no link");
+        links.remove(link_id);
+        env.storage().instance().set(&PLINK, &links);
+        Self::forget_closure(&env, &LCLOSE, link_id);
+        Self::forget_link_code(&env, link_id);
+        Self::emit(&env, (symbol_short!("LinkArch"), link_id), link);
+    }
+
+    pub fn prune_plan(env: Env, plan_id: u64) {
+        Self::assert_retired(&env, &PCLOSE, plan_id);
+        assert!(
+            Self::plan_subscribers(env.clone(), plan_id).is_empty(),
+            "plan has subscribers"
+        );
+        let mut plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        let plan = plans.get(plan_id).expect("no plan");
+        plans.remove(plan_id);
+        env.storage().instance().set(&SPLAN, &plans);
+        Self::forget_closure(&env, &PCLOSE, plan_id);
+        Self::emit(&env, (symbol_short!("PlanArch"), plan_id), plan);
+    }
+
+    pub fn prune_subscription(env: Env, subscriber: Address, subscription_id: u64) {
+        let key = (subscriber.clone(), subscription_id);
+        Self::assert_retired(&env, &SCLOSE, key.clone());
+        assert!(
+            Self::subscription_deposit(env.clone(), subscriber, subscription_id) == 0,
+            "deposit not withdrawn"
+        );
+        let mut subs: Map<(Address, u64), Subscription> = env
+            .storage()
+            .instance()
+            .get(&SUBS)
+            .unwrap_or(Map::new(&env));
+        let sub = subs.get(key.clone()).expect("no sub");
+        subs.remove(key.clone());
+        env.storage().instance().set(&SUBS, &subs);
+        Self::forget_closure(&env, &SCLOSE, key);
+        Self::emit(&env, (symbol_short!("SubArch"), subscription_id), sub);
+    }
+
+    pub(crate) fn mark_link_closed(env: &Env, link_id: u64) {
+        Self::mark_closed(env, &LCLOSE, link_id);
+    }
+
+    pub(crate) fn mark_plan_closed(env: &Env, plan_id: u64) {
+        Self::mark_closed(env, &PCLOSE, plan_id);
+    }
+
+    pub(crate) fn mark_subscription_closed(env: &Env, subscriber: &Address, subscription_id: u64) {
+        Self::mark_closed(env, &SCLOSE, (subscriber.clone(), subscription_id));
+    }
+}
+
+impl PaymentGateway {
+    fn mark_closed<K>(env: &Env, map: &Symbol, key: K)
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    {
+        let mut closed: Map<K, u64> = env.storage().instance().get(map).unwrap_or(Map::new(env));
+        closed.set(key, env.ledger().timestamp());
+        env.storage().instance().set(map, &closed);
+    }
+
+    fn assert_retired<K>(env: &Env, map: &Symbol, key: K)
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    {
+        let closed: Map<K, u64> = env.storage().instance().get(map).unwrap_or(Map::new(env));
+        let at = closed.get(key).expect("still active");
+        assert!(
+            env.ledger().timestamp() >= at + Self::get_retention(env.clone()),
+            "within retention"
+        );
+    }
+
+    fn forget_closure<K>(env: &Env, map: &Symbol, key: K)
+    where
+        K: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+    {
+        let mut closed: Map<K, u64> = env.storage().instance().get(map).unwrap_or(Map::new(env));
+        closed.remove(key);
+        env.storage().instance().set(map, &closed);
+    }
+}
@@ -0,0 +1,60 @@
+use soroban_sdk::{
+    contractimpl, contracttype, symbol_short, xdr::ToXdr, Address, BytesN, Env, Map, Symbol,
+};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PLINK};
+
+// The merchant signs the XDR of `(gateway address, quote)` with its registered quote key.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Quote {
+    link_id: u64,
+    amount: i128,
+    expires_at: u64,
+    nonce: u64,
+}
+
+// (QNONCE, merchant, nonce) -> true once the quote has been redeemed
+const QNONCE: Symbol = symbol_short!("QNONCE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn pay_quote(env: Env, invoker: Address, quote: Quote, signature: BytesN<64>) -> u32 {
+        invoker.require_auth();
+        assert!(quote.amount > 0, "amount>0");
+        Self::assert_min_price(&env, &quote.amount);
+        Self::assert_amount_bounds(&env, &quote.amount);
+        assert!(
+            env.ledger().timestamp() <= quote.expires_at,
+            "quote expired"
+        );
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let merchant = links.get(quote.link_id).expect("link not found").merchant;
+        let key = Self::get_quote_key(env.clone(), merchant.clone()).expect("no quote key");
+        let message = (env.current_contract_address(), quote.clone()).to_xdr(&env);
+        env.crypto().ed25519_verify(&key, &message, &signature);
+        let used = (QNONCE, merchant, quote.nonce);
+        assert!(!env.storage().persistent().has(&used), "nonce used");
+        Self::put_record(&env, &used, &true);
+        let receipt_id = Self::pay_link(
+            &env,
+            &invoker,
+            &invoker,
+            &invoker,
+            quote.link_id,
+            &quote.amount,
+            None,
+            None,
+        );
+        Self::emit(
+            &env,
+            (symbol_short!("QuotePaid"), quote.link_id),
+            (quote.nonce, receipt_id),
+        );
+        receipt_id
+    }
+}
@@ -0,0 +1,81 @@
+use soroban_sdk::{
+    contractimpl, contracttype, panic_with_error, symbol_short, Address, Env, Map, Symbol,
+};
+
+use crate::{Error, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, LEDGER_SECS};
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RateAction {
+    CreateLink,
+    CreatePlan,
+    Subscribe,
+}
+
+// At most `max` calls per address in each fixed window of `window` seconds.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    max: u32,
+    window: u64,
+}
+
+const RLIMITS: Symbol = symbol_short!("RLIMITS");
+// (RUSAGE, action, address) -> (start of the current window, calls made in it), as a temporary
+// entry that lives until the window closes
+const RUSAGE: Symbol = symbol_short!("RUSAGE");
+
+#[contractimpl]
+impl PaymentGateway {
+    // A `max` of 0 lifts the limit.
+    pub fn set_rate_limit(env: Env, invoker: Address, action: RateAction, max: u32, window: u64) {
+        Self::only_owner(&env, &invoker);
+        let mut limits: Map<RateAction, RateLimit> = env
+            .storage()
+            .instance()
+            .get(&RLIMITS)
+            .unwrap_or(Map::new(&env));
+        if max == 0 {
+            limits.remove(action);
+        } else {
+            assert!(window > 0, "window>0");
+            assert!(
+                window.div_ceil(LEDGER_SECS) <= env.storage().max_ttl() as u64,
+                "window too long"
+            );
+            limits.set(action, RateLimit { max, window });
+        }
+        env.storage().instance().set(&RLIMITS, &limits);
+        Self::emit(&env, (symbol_short!("RateLim"),), (action, max, window));
+    }
+
+    pub fn get_rate_limit(env: Env, action: RateAction) -> Option<RateLimit> {
+        let limits: Map<RateAction, RateLimit> = env
+            .storage()
+            .instance()
+            .get(&RLIMITS)
+            .unwrap_or(Map::new(&env));
+        limits.get(action)
+    }
+
+    pub(crate) fn rate_limit(env: &Env, action: RateAction, who: &Address) {
+        let limit = match Self::get_rate_limit(env.clone(), action) {
+            Some(l) => l,
+            None => return,
+        };
+        let now = env.ledger().timestamp();
+        let start = now - now % limit.window;
+        let key = (RUSAGE, action, who.clone());
+        let usage: Option<(u64, u32)> = env.storage().temporary().get(&key);
+        let count = match usage {
+            Some((at, count)) if at == start => count,
+            _ => 0,
+        };
+        if count >= limit.max {
+            panic_with_error!(env, Error::RateLimited);
+        }
+        env.storage().temporary().set(&key, &(start, count + 1));
+        let ledgers = (start + limit.window - now).div_ceil(LEDGER_SECS) as u32;
+        env.storage().temporary().extend_ttl(&key, ledgers, ledgers);
+    }
+}
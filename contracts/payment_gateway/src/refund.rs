@@ -0,0 +1,126 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentSource, Receipt};
+
+// merchant -> seconds after payment during which the merchant may refund
+const RFWIN: Symbol = symbol_short!("RFWIN");
+// link id -> window overriding the merchant's for payments made through that link
+const RFLINK: Symbol = symbol_short!("RFLINK");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Funds are returned to the original payer, from the custodial balance when it covers the
+    // refund and otherwise from the merchant's payout address (see `refund_from_merchant`).
+    pub fn refund_payment(env: Env, invoker: Address, receipt_id: u32, amount: i128) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let receipt = Self::get_receipt(env.clone(), receipt_id);
+        assert!(receipt.merchant == merchant, "not merchant");
+        Self::assert_refund_window(&env, &receipt);
+        Self::issue_refund(&env, &invoker, receipt_id, amount);
+    }
+
+    // Owner and arbiter refunds are not bound by the merchant's refund window.
+    pub fn force_refund(env: Env, invoker: Address, receipt_id: u32, amount: i128) {
+        if Self::is_arbiter(env.clone(), invoker.clone()) {
+            invoker.require_auth();
+        } else {
+            Self::only_owner(&env, &invoker);
+        }
+        Self::issue_refund(&env, &invoker, receipt_id, amount);
+    }
+
+    // `None` lifts the window, leaving refunds open indefinitely.
+    pub fn set_refund_window(env: Env, invoker: Address, window: Option<u64>) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        let mut windows: Map<Address, u64> = env
+            .storage()
+            .instance()
+            .get(&RFWIN)
+            .unwrap_or(Map::new(&env));
+        match window {
+            Some(window) => windows.set(invoker.clone(), window),
+            None => {
+                windows.remove(invoker.clone());
+            }
+        }
+        env.storage().instance().set(&RFWIN, &windows);
+        Self::emit(&env, (symbol_short!("RfWinSet"),), (invoker, window));
+    }
+
+    pub fn set_link_refund_window(env: Env, invoker: Address, link_id: u64, window: Option<u64>) {
+        invoker.require_auth();
+        Self::assert_link_manager(&env, &invoker, link_id);
+        let mut windows: Map<u64, u64> = env
+            .storage()
+            .instance()
+            .get(&RFLINK)
+            .unwrap_or(Map::new(&env));
+        match window {
+            Some(window) => windows.set(link_id, window),
+            None => {
+                windows.remove(link_id);
+            }
+        }
+        env.storage().instance().set(&RFLINK, &windows);
+        Self::emit(&env, (symbol_short!("RfWinSet"), link_id), window);
+    }
+
+    // The window that applies to a payment: the link's own if it has one, else the merchant's.
+    pub fn get_refund_window(env: Env, merchant: Address, link_id: Option<u64>) -> Option<u64> {
+        if let Some(link_id) = link_id {
+            let links: Map<u64, u64> = env
+                .storage()
+                .instance()
+                .get(&RFLINK)
+                .unwrap_or(Map::new(&env));
+            if let Some(window) = links.get(link_id) {
+                return Some(window);
+            }
+        }
+        let windows: Map<Address, u64> = env
+            .storage()
+            .instance()
+            .get(&RFWIN)
+            .unwrap_or(Map::new(&env));
+        windows.get(merchant)
+    }
+
+    pub(crate) fn assert_refund_window(env: &Env, receipt: &Receipt) {
+        let link_id = match receipt.source {
+            PaymentSource::Link(link_id) => Some(link_id),
+            _ => None,
+        };
+        if let Some(window) =
+            Self::get_refund_window(env.clone(), receipt.merchant.clone(), link_id)
+        {
+            assert!(
+                env.ledger().timestamp() <= receipt.timestamp.to_unix() + window,
+                "refund window closed"
+            );
+        }
+    }
+
+    // Marks `amount` of the receipt refunded and returns it; the caller moves the funds.
+    pub(crate) fn take_refund(env: &Env, receipt_id: u32, amount: &i128) -> Receipt {
+        let mut receipt = Self::get_receipt(env.clone(), receipt_id);
+        assert!(
+            !matches!(receipt.source, PaymentSource::StoreCredit(_)),
+            "credit receipt"
+        );
+        assert!(*amount > 0, "amount>0");
+        let refunded = receipt.refunded + amount;
+        assert!(refunded <= receipt.amount, "exceeds payment");
+        receipt.refunded = refunded;
+        Self::save_receipt(env, receipt_id, &receipt);
+        Self::claw_back_points(env, &receipt, amount);
+        receipt
+    }
+
+    fn issue_refund(env: &Env, spender: &Address, receipt_id: u32, amount: i128) {
+        let receipt = Self::take_refund(env, receipt_id, &amount);
+        Self::refund_from_merchant(env, spender, &receipt.merchant, &receipt.payer, &amount);
+        Self::emit(env, (symbol_short!("Refund"), receipt_id), amount);
+    }
+}
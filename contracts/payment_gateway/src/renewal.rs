@@ -0,0 +1,102 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol, Timepoint, Vec};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, Subscription, SubscriptionPlan,
+    BATCH_MAX, SPLAN, SUBS,
+};
+
+// how long before a charge falls due its renewal notice may go out
+const RNWINDOW: Symbol = symbol_short!("RNWINDOW");
+const DEFAULT_NOTICE_WINDOW: u64 = 3 * 86_400;
+// (subscriber, subscription id) -> due time the last notice was sent for
+const RNOTICE: Symbol = symbol_short!("RNOTICE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_renewal_notice_window(env: Env, invoker: Address, seconds: u64) {
+        Self::only_owner(&env, &invoker);
+        assert!(seconds > 0, "window>0");
+        env.storage().instance().set(&RNWINDOW, &seconds);
+        Self::emit(&env, (symbol_short!("RnWindow"),), seconds);
+    }
+
+    pub fn get_renewal_notice_window(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&RNWINDOW)
+            .unwrap_or(DEFAULT_NOTICE_WINDOW)
+    }
+
+    // Emits `RenewSoon` once per period for each listed subscription whose payer will be
+    // charged within the window. Keys that are unknown, not billable, prepaid, outside the
+    // window or already notified are skipped. Returns the number of notices emitted.
+    pub fn emit_renewal_notices(env: Env, invoker: Address, keys: Vec<(Address, u64)>) -> u32 {
+        invoker.require_auth();
+        assert!(
+            Self::get_keepers(env.clone()).contains(&invoker),
+            "not keeper"
+        );
+        assert!(keys.len() <= BATCH_MAX, "batch too large");
+        let subs: Map<(Address, u64), Subscription> = env
+            .storage()
+            .instance()
+            .get(&SUBS)
+            .unwrap_or(Map::new(&env));
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        let mut notices = Self::renewal_notices(&env);
+        let now = env.ledger().timestamp();
+        let window = Self::get_renewal_notice_window(env.clone());
+        let mut sent = 0;
+        for (subscriber, subscription_id) in keys.iter() {
+            let key = (subscriber.clone(), subscription_id);
+            let sub = match subs.get(key.clone()) {
+                Some(sub) => sub,
+                None => continue,
+            };
+            let plan = match plans.get(sub.plan_id) {
+                Some(plan) => plan,
+                None => continue,
+            };
+            if !sub.status.is_billable() || sub.prepaid > 0 || !plan.active {
+                continue;
+            }
+            let due = Self::due_at(&sub, &plan);
+            if now >= due || due - now > window || notices.get(key.clone()) == Some(due) {
+                continue;
+            }
+            notices.set(key, due);
+            Self::emit(
+                &env,
+                (symbol_short!("RenewSoon"), subscriber),
+                (
+                    subscription_id,
+                    sub.plan_id,
+                    Self::seat_total(&env, &sub),
+                    Timepoint::from_unix(&env, due),
+                ),
+            );
+            sent += 1;
+        }
+        env.storage().instance().set(&RNOTICE, &notices);
+        sent
+    }
+
+    pub(crate) fn clear_renewal_notice(env: &Env, subscriber: &Address, subscription_id: u64) {
+        let mut notices = Self::renewal_notices(env);
+        if notices.contains_key((subscriber.clone(), subscription_id)) {
+            notices.remove((subscriber.clone(), subscription_id));
+            env.storage().instance().set(&RNOTICE, &notices);
+        }
+    }
+
+    fn renewal_notices(env: &Env) -> Map<(Address, u64), u64> {
+        env.storage()
+            .instance()
+            .get(&RNOTICE)
+            .unwrap_or(Map::new(env))
+    }
+}
@@ -0,0 +1,131 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Timepoint};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentSource, Receipt};
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RequestStatus {
+    Pending,
+    Paid,
+    Rejected,
+    Expired,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentRequest {
+    merchant: Address,
+    customer: Address,
+    amount: i128,
+    description: Symbol,
+    expires_at: Timepoint,
+    status: RequestStatus,
+}
+
+const RQCTR: Symbol = symbol_short!("RQCTR");
+const PREQ: Symbol = symbol_short!("PREQ");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn request_payment(
+        env: Env,
+        invoker: Address,
+        customer: Address,
+        amount: i128,
+        description: Symbol,
+        expires_in: u64,
+    ) -> u32 {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(amount > 0, "amount>0");
+        Self::assert_min_price(&env, &amount);
+        Self::assert_amount_bounds(&env, &amount);
+        assert!(expires_in > 0, "expires_in>0");
+        let ctr = Self::next_id(&env, &RQCTR);
+        Self::save_payment_request(
+            &env,
+            ctr,
+            &PaymentRequest {
+                merchant: invoker,
+                customer: customer.clone(),
+                amount,
+                description,
+                expires_at: Timepoint::from_unix(&env, env.ledger().timestamp() + expires_in),
+                status: RequestStatus::Pending,
+            },
+        );
+        Self::emit(&env, (symbol_short!("ReqCr"), ctr), &customer);
+        ctr
+    }
+
+    pub fn approve_payment_request(env: Env, invoker: Address, request_id: u32) -> u32 {
+        invoker.require_auth();
+        let mut req = Self::get_payment_request(env.clone(), request_id);
+        assert!(req.customer == invoker, "not customer");
+        assert!(req.status == RequestStatus::Pending, "not pending");
+        assert!(
+            env.ledger().timestamp() <= req.expires_at.to_unix(),
+            "request expired"
+        );
+        req.status = RequestStatus::Paid;
+        Self::save_payment_request(&env, request_id, &req);
+        let fee = Self::settle(&env, &invoker, &invoker, &req.merchant, &req.amount);
+        let receipt = Receipt {
+            fee,
+            ..Receipt::new(
+                &env,
+                PaymentSource::Request(request_id),
+                req.merchant,
+                invoker.clone(),
+                invoker,
+                req.amount,
+            )
+        };
+        let receipt_id = Self::record_receipt(&env, &receipt);
+        Self::emit(
+            &env,
+            (symbol_short!("ReqPaid"), request_id),
+            Self::payment_event(&env, receipt_id, &receipt),
+        );
+        receipt_id
+    }
+
+    pub fn reject_payment_request(env: Env, invoker: Address, request_id: u32) {
+        invoker.require_auth();
+        let mut req = Self::get_payment_request(env.clone(), request_id);
+        assert!(req.customer == invoker, "not customer");
+        assert!(req.status == RequestStatus::Pending, "not pending");
+        req.status = RequestStatus::Rejected;
+        Self::save_payment_request(&env, request_id, &req);
+        Self::emit(&env, (symbol_short!("ReqRej"), request_id), request_id);
+    }
+
+    // Anyone may close out a request once it has passed its expiry.
+    pub fn expire_payment_request(env: Env, request_id: u32) {
+        let mut req = Self::get_payment_request(env.clone(), request_id);
+        assert!(req.status == RequestStatus::Pending, "not pending");
+        assert!(
+            env.ledger().timestamp() > req.expires_at.to_unix(),
+            "not expired"
+        );
+        req.status = RequestStatus::Expired;
+        Self::save_payment_request(&env, request_id, &req);
+        Self::emit(&env, (symbol_short!("ReqExp"), request_id), request_id);
+    }
+
+    pub fn get_payment_request(env: Env, request_id: u32) -> PaymentRequest {
+        let reqs: Map<u32, PaymentRequest> = env
+            .storage()
+            .instance()
+            .get(&PREQ)
+            .unwrap_or(Map::new(&env));
+        reqs.get(request_id).expect("no request")
+    }
+
+    fn save_payment_request(env: &Env, request_id: u32, req: &PaymentRequest) {
+        let mut reqs: Map<u32, PaymentRequest> =
+            env.storage().instance().get(&PREQ).unwrap_or(Map::new(env));
+        reqs.set(request_id, req.clone());
+        env.storage().instance().set(&PREQ, &reqs);
+    }
+}
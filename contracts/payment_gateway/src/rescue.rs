@@ -0,0 +1,81 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, IntoVal, Symbol, Vec};
+
+use crate::{AdminAction, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, TOKEN};
+
+#[contractimpl]
+impl PaymentGateway {
+    // Queued behind the timelock when one is set. Only the part of the gateway token's balance
+    // not owed to anyone can leave; other tokens were never accounted and can be swept in full.
+    pub fn rescue_tokens(env: Env, invoker: Address, token: Address, to: Address, amount: i128) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        assert!(amount > 0, "amount>0");
+        Self::assert_rescuable(&env, &token, &amount);
+        Self::run_or_schedule(&env, AdminAction::RescueTokens(token, to, amount), None);
+    }
+
+    pub fn rescuable_balance(env: Env, token: Address) -> i128 {
+        let balance: i128 = env.invoke_contract(
+            &token,
+            &Symbol::new(&env, "balance"),
+            Vec::from_array(&env, [env.current_contract_address().to_val()]),
+        );
+        let gateway_token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        if token != gateway_token {
+            return balance;
+        }
+        // principal supplied for yield is still held, just not in the token balance
+        let free = balance + Self::supplied(env.clone()) - Self::held_total(&env);
+        if free > 0 {
+            free
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn sweep(env: &Env, token: &Address, to: &Address, amount: &i128) {
+        Self::assert_rescuable(env, token, amount);
+        let gateway_token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        if *token == gateway_token {
+            Self::ensure_liquid(env, amount);
+        }
+        Self::invoke_token(
+            env,
+            token,
+            "transfer",
+            Vec::from_array(
+                env,
+                [
+                    env.current_contract_address().to_val(),
+                    to.to_val(),
+                    amount.into_val(env),
+                ],
+            ),
+        );
+        Self::emit(
+            env,
+            (symbol_short!("Rescued"), token.clone()),
+            (to.clone(), *amount),
+        );
+    }
+
+    fn assert_rescuable(env: &Env, token: &Address, amount: &i128) {
+        assert!(
+            *amount <= Self::rescuable_balance(env.clone(), token.clone()),
+            "exceeds rescuable"
+        );
+    }
+
+    // Everything the gateway holds on someone's behalf: escrows, deposits, bonds, custodial
+    // balances, uncollected protocol fees and the insurance pool.
+    pub(crate) fn held_total(env: &Env) -> i128 {
+        Self::custodial_held(env)
+            + Self::accrued_fees(env.clone())
+            + Self::bonds_held(env)
+            + Self::campaigns_held(env)
+            + Self::deposits_held(env)
+            + Self::milestones_held(env)
+            + Self::streams_held(env)
+            + Self::insurance_pool(env.clone())
+    }
+}
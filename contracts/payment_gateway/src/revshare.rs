@@ -0,0 +1,120 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, SubscriptionPlan, SPLAN};
+
+// plan id -> co-payees and their bps of every charge; the plan's merchant keeps the rest
+const REVSHARE: Symbol = symbol_short!("REVSHARE");
+const REVSHARE_MAX: u32 = 10;
+
+#[contractimpl]
+impl PaymentGateway {
+    // The table can be set once, before anyone subscribes, and is fixed for the plan's life:
+    // a different split means a new plan. Refunds and proration credits still come out of
+    // the merchant.
+    pub fn set_revenue_shares(
+        env: Env,
+        invoker: Address,
+        plan_id: u64,
+        shares: Vec<(Address, u32)>,
+    ) {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        let plan = plans.get(plan_id).expect("plan not found");
+        assert!(plan.merchant == merchant, "not merchant");
+        let mut all = Self::all_revenue_shares(&env);
+        assert!(!all.contains_key(plan_id), "shares locked");
+        assert!(
+            Self::plan_subscribers(env.clone(), plan_id).is_empty(),
+            "plan has subscribers"
+        );
+        assert!(!shares.is_empty(), "no shares");
+        assert!(shares.len() <= REVSHARE_MAX, "too many payees");
+        let mut total: u32 = 0;
+        for (i, (payee, bps)) in shares.iter().enumerate() {
+            assert!(payee != merchant, "payee is merchant");
+            assert!(bps > 0, "bps>0");
+            for (other, _) in shares.iter().skip(i + 1) {
+                assert!(other != payee, "duplicate payee");
+            }
+            total += bps;
+        }
+        assert!(total <= 10_000, "bps<=10000");
+        all.set(plan_id, shares.clone());
+        env.storage().instance().set(&REVSHARE, &all);
+        Self::emit(&env, (symbol_short!("RevShare"), plan_id), shares);
+    }
+
+    pub fn get_revenue_shares(env: Env, plan_id: u64) -> Vec<(Address, u32)> {
+        Self::all_revenue_shares(&env)
+            .get(plan_id)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    // Pays each co-payee its share of `amount`, less the protocol fee on it, from `source`
+    // (spender, payer) or, without one, from funds the gateway holds. Returns the total
+    // shared out and the fees taken, for the caller to settle the rest with the merchant.
+    pub(crate) fn pay_revenue_shares(
+        env: &Env,
+        source: Option<(&Address, &Address)>,
+        plan_id: u64,
+        amount: &i128,
+    ) -> (i128, i128) {
+        let shares = match Self::all_revenue_shares(env).get(plan_id) {
+            Some(shares) => shares,
+            None => return (0, 0),
+        };
+        Self::assert_not_paused(env);
+        let mut shared = 0;
+        let mut fees = 0;
+        let mut paid_out = Vec::new(env);
+        for (payee, bps) in shares.iter() {
+            let share = Self::bps_of(env, amount, bps);
+            if share <= 0 {
+                continue;
+            }
+            let fee = Self::fee_for(env, &share);
+            let paid = share - fee;
+            match source {
+                Some((spender, from)) => {
+                    Self::transfer_from(env, spender, from, &payee, &paid);
+                    if fee > 0 {
+                        Self::transfer_from(
+                            env,
+                            spender,
+                            from,
+                            &env.current_contract_address(),
+                            &fee,
+                        );
+                    }
+                }
+                None => Self::payout(env, &payee, &paid),
+            }
+            if fee > 0 {
+                Self::accrue_fee(env, &fee);
+            }
+            shared += share;
+            fees += fee;
+            paid_out.push_back((payee, paid));
+        }
+        if shared > 0 {
+            Self::track_volume(env, &shared);
+            if let Some((_, from)) = source {
+                Self::track_spending(env, from, &shared);
+            }
+            Self::emit(env, (symbol_short!("RevPaid"), plan_id), paid_out);
+        }
+        (shared, fees)
+    }
+
+    fn all_revenue_shares(env: &Env) -> Map<u64, Vec<(Address, u32)>> {
+        env.storage()
+            .instance()
+            .get(&REVSHARE)
+            .unwrap_or(Map::new(env))
+    }
+}
@@ -0,0 +1,129 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Timepoint};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PLINK};
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScheduleStatus {
+    Pending,
+    Executed,
+    Cancelled,
+    Expired,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScheduledPayment {
+    link_id: u64,
+    payer: Address,
+    // link price when the payment was scheduled; execution fails if it has changed since
+    amount: i128,
+    execute_at: Timepoint,
+    expires_at: Timepoint,
+    receipt_id: Option<u32>,
+    status: ScheduleStatus,
+}
+
+const SCHCTR: Symbol = symbol_short!("SCHCTR");
+// (SCHED, schedule_id) -> ScheduledPayment
+const SCHED: Symbol = symbol_short!("SCHED");
+
+#[contractimpl]
+impl PaymentGateway {
+    // The payer must approve the gateway as spender; any keeper may then execute the payment
+    // between `execute_at` and `expires_at`, at the link's price as of scheduling.
+    pub fn schedule_payment(
+        env: Env,
+        invoker: Address,
+        link_id: u64,
+        execute_at: u64,
+        expires_at: u64,
+    ) -> u32 {
+        invoker.require_auth();
+        assert!(execute_at > env.ledger().timestamp(), "execute_at in past");
+        assert!(expires_at > execute_at, "expires_at<=execute_at");
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("link not found");
+        assert!(link.active, "inactive link");
+        let ctr = Self::next_id(&env, &SCHCTR);
+        Self::save_scheduled_payment(
+            &env,
+            ctr,
+            &ScheduledPayment {
+                link_id,
+                payer: invoker.clone(),
+                amount: link.amount,
+                execute_at: Timepoint::from_unix(&env, execute_at),
+                expires_at: Timepoint::from_unix(&env, expires_at),
+                receipt_id: None,
+                status: ScheduleStatus::Pending,
+            },
+        );
+        Self::emit(
+            &env,
+            (symbol_short!("SchedCr"), ctr),
+            (link_id, invoker, execute_at),
+        );
+        ctr
+    }
+
+    pub fn execute_scheduled_payment(env: Env, schedule_id: u32) -> u32 {
+        let mut sched = Self::get_scheduled_payment(env.clone(), schedule_id);
+        assert!(sched.status == ScheduleStatus::Pending, "not pending");
+        let now = env.ledger().timestamp();
+        assert!(now >= sched.execute_at.to_unix(), "not due");
+        assert!(now <= sched.expires_at.to_unix(), "schedule expired");
+        Self::assert_link_price(&env, sched.link_id, &sched.amount);
+        sched.status = ScheduleStatus::Executed;
+        Self::save_scheduled_payment(&env, schedule_id, &sched);
+        let receipt_id = Self::pay_link(
+            &env,
+            &env.current_contract_address(),
+            &sched.payer,
+            &sched.payer,
+            sched.link_id,
+            &sched.amount,
+            None,
+            None,
+        );
+        sched.receipt_id = Some(receipt_id);
+        Self::save_scheduled_payment(&env, schedule_id, &sched);
+        Self::emit(&env, (symbol_short!("SchedExec"), schedule_id), receipt_id);
+        receipt_id
+    }
+
+    pub fn cancel_scheduled_payment(env: Env, invoker: Address, schedule_id: u32) {
+        invoker.require_auth();
+        let mut sched = Self::get_scheduled_payment(env.clone(), schedule_id);
+        assert!(sched.payer == invoker, "not payer");
+        assert!(sched.status == ScheduleStatus::Pending, "not pending");
+        sched.status = ScheduleStatus::Cancelled;
+        Self::save_scheduled_payment(&env, schedule_id, &sched);
+        Self::emit(&env, (symbol_short!("SchedCnl"), schedule_id), schedule_id);
+    }
+
+    // Anyone may close out a schedule whose execution window has passed.
+    pub fn expire_scheduled_payment(env: Env, schedule_id: u32) {
+        let mut sched = Self::get_scheduled_payment(env.clone(), schedule_id);
+        assert!(sched.status == ScheduleStatus::Pending, "not pending");
+        assert!(
+            env.ledger().timestamp() > sched.expires_at.to_unix(),
+            "not expired"
+        );
+        sched.status = ScheduleStatus::Expired;
+        Self::save_scheduled_payment(&env, schedule_id, &sched);
+        Self::emit(&env, (symbol_short!("SchedExp"), schedule_id), schedule_id);
+    }
+
+    pub fn get_scheduled_payment(env: Env, schedule_id: u32) -> ScheduledPayment {
+        Self::get_record(&env, &(SCHED, schedule_id)).expect("no schedule")
+    }
+
+    fn save_scheduled_payment(env: &Env, schedule_id: u32, sched: &ScheduledPayment) {
+        Self::put_record(env, &(SCHED, schedule_id), sched);
+    }
+}
@@ -0,0 +1,89 @@
+use soroban_sdk::{contractimpl, symbol_short, Address, Env, Map};
+
+use crate::{
+    PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentSource, Receipt, Subscription,
+    SubscriptionPlan, SPLAN,
+};
+
+#[contractimpl]
+impl PaymentGateway {
+    // Added seats are charged and removed seats credited for what is left of the current
+    // period. Credits come from the merchant the same way prorated cancellations do. Gifted
+    // periods were bought for a fixed seat count, so seats stay put until they are used up.
+    pub fn set_seats(env: Env, invoker: Address, subscription_id: u64, quantity: u32) {
+        invoker.require_auth();
+        assert!(quantity > 0, "quantity>0");
+        let mut sub = Self::load_subscription(&env, &invoker, subscription_id);
+        assert!(sub.status.is_billable(), "not billable");
+        assert!(quantity != sub.quantity, "unchanged");
+        assert!(!Self::is_metered(env.clone(), sub.plan_id), "metered plan");
+        assert!(sub.prepaid == 0, "prepaid");
+        let plans: Map<u64, SubscriptionPlan> = env
+            .storage()
+            .instance()
+            .get(&SPLAN)
+            .unwrap_or(Map::new(&env));
+        let plan = plans.get(sub.plan_id).expect("plan not found");
+        let previous = sub.quantity;
+        Self::count_plan_subscriber(&env, sub.plan_id, &Self::seat_total(&env, &sub), false);
+        sub.quantity = quantity;
+        Self::count_plan_subscriber(&env, sub.plan_id, &Self::seat_total(&env, &sub), true);
+        Self::store_subscription(&env, subscription_id, &sub);
+        let changed = quantity.abs_diff(previous);
+        let changed = Self::checked(&env, sub.amount.checked_mul(changed as i128));
+        let delta = Self::unused_share(&env, &sub, &plan, &changed);
+        if delta > 0 {
+            if quantity > previous {
+                Self::charge_seats(&env, subscription_id, &sub, &plan, delta);
+            } else {
+                Self::credit_period(&env, subscription_id, &sub, &plan, &delta);
+            }
+        }
+        Self::emit(
+            &env,
+            (symbol_short!("Seats"), subscription_id),
+            (previous, quantity),
+        );
+    }
+
+    pub(crate) fn seat_total(env: &Env, sub: &Subscription) -> i128 {
+        Self::checked(env, sub.amount.checked_mul(sub.quantity as i128))
+    }
+
+    fn charge_seats(
+        env: &Env,
+        subscription_id: u64,
+        sub: &Subscription,
+        plan: &SubscriptionPlan,
+        amount: i128,
+    ) {
+        // the subscriber changes seats, but a gift's payer is charged via its allowance
+        let gateway = env.current_contract_address();
+        let (shared, shared_fee) =
+            Self::pay_revenue_shares(env, Some((&gateway, &sub.payer)), sub.plan_id, &amount);
+        let fee = Self::settle(
+            env,
+            &gateway,
+            &sub.payer,
+            &plan.merchant,
+            &(amount - shared),
+        );
+        let receipt = Receipt {
+            fee: fee + shared_fee,
+            ..Receipt::new(
+                env,
+                PaymentSource::Subscription(sub.subscriber.clone(), subscription_id),
+                plan.merchant.clone(),
+                sub.payer.clone(),
+                sub.subscriber.clone(),
+                amount,
+            )
+        };
+        let receipt_id = Self::record_receipt(env, &receipt);
+        Self::emit(
+            env,
+            (symbol_short!("SPay"), subscription_id),
+            Self::payment_event(env, receipt_id, &receipt),
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PeriodAccount {
+    sales: i128,
+    refunds: i128,
+    paid_out: i128,
+    settled: bool,
+}
+
+const PAYOUT: Symbol = symbol_short!("PAYOUT");
+const CUSTOD: Symbol = symbol_short!("CUSTOD");
+const BAL: Symbol = symbol_short!("BAL");
+const PERLEN: Symbol = symbol_short!("PERLEN");
+// (PERIODS, merchant, period) -> PeriodAccount
+const PERIODS: Symbol = symbol_short!("PERIODS");
+const DEFAULT_PERIOD: u64 = 86_400;
+
+#[contractimpl]
+impl PaymentGateway {
+    // Only the merchant key itself may move where its revenue lands; operators cannot.
+    pub fn set_payout_address(env: Env, invoker: Address, payout: Address) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        let mut payouts: Map<Address, Address> = env
+            .storage()
+            .instance()
+            .get(&PAYOUT)
+            .unwrap_or(Map::new(&env));
+        if payout == invoker {
+            payouts.remove(invoker.clone());
+        } else {
+            payouts.set(invoker.clone(), payout.clone());
+        }
+        env.storage().instance().set(&PAYOUT, &payouts);
+        Self::emit(&env, (symbol_short!("PayoutSet"), invoker), &payout);
+    }
+
+    pub fn get_payout_address(env: Env, merchant: Address) -> Address {
+        let payouts: Map<Address, Address> = env
+            .storage()
+            .instance()
+            .get(&PAYOUT)
+            .unwrap_or(Map::new(&env));
+        payouts.get(merchant.clone()).unwrap_or(merchant)
+    }
+
+    // In custodial mode revenue is credited to an internal balance and paid out on `withdraw`.
+    pub fn set_custodial(env: Env, invoker: Address, enabled: bool) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        let mut custodial: Map<Address, bool> = env
+            .storage()
+            .instance()
+            .get(&CUSTOD)
+            .unwrap_or(Map::new(&env));
+        if enabled {
+            custodial.set(invoker.clone(), true);
+        } else {
+            custodial.remove(invoker.clone());
+        }
+        env.storage().instance().set(&CUSTOD, &custodial);
+        Self::emit(&env, (symbol_short!("Custody"), invoker), enabled);
+    }
+
+    pub fn is_custodial(env: Env, merchant: Address) -> bool {
+        let custodial: Map<Address, bool> = env
+            .storage()
+            .instance()
+            .get(&CUSTOD)
+            .unwrap_or(Map::new(&env));
+        custodial.get(merchant).unwrap_or(false)
+    }
+
+    pub fn merchant_balance(env: Env, merchant: Address) -> i128 {
+        let balances: Map<Address, i128> =
+            env.storage().instance().get(&BAL).unwrap_or(Map::new(&env));
+        balances.get(merchant).unwrap_or(0)
+    }
+
+    // The protocol fee on custodial revenue is taken here rather than at payment time.
+    pub fn withdraw(env: Env, invoker: Address, amount: i128) -> i128 {
+        invoker.require_auth();
+        assert!(amount > 0, "amount>0");
+        Self::debit_balance(&env, &invoker, &amount);
+        let fee = Self::fee_for(&env, &amount);
+        let net = amount - fee;
+        Self::accrue_fee(&env, &fee);
+        let to = Self::get_payout_address(env.clone(), invoker.clone());
+        Self::payout(&env, &to, &net);
+        Self::emit(&env, (symbol_short!("Withdraw"), invoker), (net, fee));
+        net
+    }
+
+    pub fn set_settlement_period(env: Env, invoker: Address, seconds: u64) {
+        Self::only_owner(&env, &invoker);
+        assert!(seconds > 0, "period>0");
+        env.storage().instance().set(&PERLEN, &seconds);
+        Self::emit(&env, (symbol_short!("PerLen"),), seconds);
+    }
+
+    pub fn current_period(env: Env) -> u64 {
+        let len: u64 = env
+            .storage()
+            .instance()
+            .get(&PERLEN)
+            .unwrap_or(DEFAULT_PERIOD);
+        env.ledger().timestamp() / len
+    }
+
+    pub fn period_account(env: Env, merchant: Address, period: u64) -> PeriodAccount {
+        Self::get_record(&env, &(PERIODS, merchant, period)).unwrap_or(PeriodAccount {
+            sales: 0,
+            refunds: 0,
+            paid_out: 0,
+            settled: false,
+        })
+    }
+
+    // Pays a closed period's net (sales minus refunds) to the merchant in one transfer. Anyone
+    // may trigger it since funds only ever move to the merchant's payout address.
+    pub fn settle_period(env: Env, merchant: Address, period: u64) -> i128 {
+        assert!(period < Self::current_period(env.clone()), "period open");
+        let mut account = Self::period_account(env.clone(), merchant.clone(), period);
+        assert!(!account.settled, "already settled");
+        let mut net = account.sales - account.refunds;
+        let balance = Self::merchant_balance(env.clone(), merchant.clone());
+        if net > balance {
+            net = balance;
+        }
+        account.settled = true;
+        let mut paid = 0;
+        let mut fee = 0;
+        if net > 0 {
+            Self::debit_balance(&env, &merchant, &net);
+            fee = Self::fee_for(&env, &net);
+            Self::accrue_fee(&env, &fee);
+            paid = net - fee;
+            let to = Self::get_payout_address(env.clone(), merchant.clone());
+            Self::payout(&env, &to, &paid);
+        }
+        account.paid_out = paid;
+        Self::save_period(&env, &merchant, period, &account);
+        Self::emit(
+            &env,
+            (symbol_short!("PerSettl"), merchant, period),
+            (paid, fee),
+        );
+        paid
+    }
+
+    // Charges a customer payment on behalf of `merchant`, returning the fee taken up front
+    // (custodial balances are charged at withdrawal instead).
+    pub(crate) fn settle(
+        env: &Env,
+        spender: &Address,
+        from: &Address,
+        merchant: &Address,
+        amount: &i128,
+    ) -> i128 {
+        Self::assert_not_paused(env);
+        Self::track_volume(env, amount);
+        Self::track_spending(env, from, amount);
+        Self::record_sale(env, merchant, amount, true);
+        if Self::is_custodial(env.clone(), merchant.clone()) {
+            Self::transfer_from(env, spender, from, &env.current_contract_address(), amount);
+            Self::credit_balance(env, merchant, amount);
+            return 0;
+        }
+        let fee = Self::fee_for(env, amount);
+        let to = Self::get_payout_address(env.clone(), merchant.clone());
+        Self::transfer_from(env, spender, from, &to, &(amount - fee));
+        if fee > 0 {
+            Self::transfer_from(env, spender, from, &env.current_contract_address(), &fee);
+            Self::accrue_fee(env, &fee);
+        }
+        fee
+    }
+
+    // Takes a customer's funds into the gateway's custody (escrows, streams, campaigns,
+    // deposits). They count as volume and against the payer's spending limit on the way in,
+    // not again when later released or drawn.
+    pub(crate) fn collect(env: &Env, from: &Address, amount: &i128) {
+        Self::assert_not_paused(env);
+        Self::track_volume(env, amount);
+        Self::track_spending(env, from, amount);
+        Self::transfer_from(env, from, from, &env.current_contract_address(), amount);
+    }
+
+    // Releases funds the gateway holds on a merchant's behalf (escrows, streams, campaigns).
+    pub(crate) fn release(env: &Env, merchant: &Address, amount: &i128) {
+        Self::record_sale(env, merchant, amount, false);
+        Self::pay_held(env, merchant, amount);
+    }
+
+    // Like `settle`, for a customer payment whose funds the gateway already holds; they were
+    // counted as volume and spending by `collect`.
+    pub(crate) fn settle_held(env: &Env, merchant: &Address, amount: &i128) -> i128 {
+        Self::assert_not_paused(env);
+        Self::record_sale(env, merchant, amount, true);
+        Self::pay_held(env, merchant, amount)
+    }
+
+    fn pay_held(env: &Env, merchant: &Address, amount: &i128) -> i128 {
+        if Self::is_custodial(env.clone(), merchant.clone()) {
+            Self::credit_balance(env, merchant, amount);
+            return 0;
+        }
+        let fee = Self::fee_for(env, amount);
+        Self::accrue_fee(env, &fee);
+        let to = Self::get_payout_address(env.clone(), merchant.clone());
+        Self::payout(env, &to, &(amount - fee));
+        fee
+    }
+
+    // Refunds come out of the custodial balance when it covers them, otherwise from the
+    // payout address via an allowance granted to `spender` (or its own auth if it is that address).
+    pub(crate) fn refund_from_merchant(
+        env: &Env,
+        spender: &Address,
+        merchant: &Address,
+        to: &Address,
+        amount: &i128,
+    ) {
+        Self::record_refund(env, merchant, amount);
+        Self::pay_from_merchant(env, spender, merchant, to, amount);
+    }
+
+    // Money leaving the merchant after a sale is counted against the current period's refunds.
+    pub(crate) fn pay_from_merchant(
+        env: &Env,
+        spender: &Address,
+        merchant: &Address,
+        to: &Address,
+        amount: &i128,
+    ) {
+        if Self::merchant_balance(env.clone(), merchant.clone()) >= *amount {
+            Self::debit_balance(env, merchant, amount);
+            let period = Self::current_period(env.clone());
+            let mut account = Self::period_account(env.clone(), merchant.clone(), period);
+            account.refunds += amount;
+            Self::save_period(env, merchant, period, &account);
+            Self::payout(env, to, amount);
+        } else {
+            let source = Self::get_payout_address(env.clone(), merchant.clone());
+            Self::transfer_from(env, spender, &source, to, amount);
+        }
+    }
+
+    fn credit_balance(env: &Env, merchant: &Address, amount: &i128) {
+        let mut balances: Map<Address, i128> =
+            env.storage().instance().get(&BAL).unwrap_or(Map::new(env));
+        let bal = balances.get(merchant.clone()).unwrap_or(0);
+        balances.set(merchant.clone(), bal + amount);
+        env.storage().instance().set(&BAL, &balances);
+        let period = Self::current_period(env.clone());
+        let mut account = Self::period_account(env.clone(), merchant.clone(), period);
+        account.sales += amount;
+        Self::save_period(env, merchant, period, &account);
+        Self::emit(env, (symbol_short!("Credit"), merchant.clone()), *amount);
+    }
+
+    fn save_period(env: &Env, merchant: &Address, period: u64, account: &PeriodAccount) {
+        Self::put_record(env, &(PERIODS, merchant.clone(), period), account);
+    }
+
+    fn debit_balance(env: &Env, merchant: &Address, amount: &i128) {
+        let mut balances: Map<Address, i128> =
+            env.storage().instance().get(&BAL).unwrap_or(Map::new(env));
+        let bal = balances.get(merchant.clone()).unwrap_or(0);
+        assert!(bal >= *amount, "insufficient balance");
+        balances.set(merchant.clone(), bal - amount);
+        env.storage().instance().set(&BAL, &balances);
+    }
+
+    // Splits `amount` across custodial balances pro rata and returns what was handed out; the
+    // rounding dust is left to the caller.
+    pub(crate) fn share_with_custodial(env: &Env, amount: &i128) -> i128 {
+        let total = Self::custodial_held(env);
+        if total <= 0 {
+            return 0;
+        }
+        let mut balances: Map<Address, i128> =
+            env.storage().instance().get(&BAL).unwrap_or(Map::new(env));
+        let mut shared = 0;
+        for (merchant, bal) in balances.clone().iter() {
+            let cut = Self::checked(env, amount.checked_mul(bal)) / total;
+            balances.set(merchant, bal + cut);
+            shared += cut;
+        }
+        env.storage().instance().set(&BAL, &balances);
+        shared
+    }
+
+    // Sum of all custodial merchant balances.
+    pub(crate) fn custodial_held(env: &Env) -> i128 {
+        let balances: Map<Address, i128> =
+            env.storage().instance().get(&BAL).unwrap_or(Map::new(env));
+        let mut total = 0;
+        for (_, bal) in balances.iter() {
+            total += bal;
+        }
+        total
+    }
+}
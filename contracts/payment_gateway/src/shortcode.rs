@@ -0,0 +1,99 @@
+use soroban_sdk::{contractimpl, symbol_short, xdr::ToXdr, Address, BytesN, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, PaymentLink, PLINK};
+
+// random per-gateway salt, so codes cannot be enumerated from merchant and link counts
+const CODESALT: Symbol = symbol_short!("CODESALT");
+// code -> link id
+const CODES: Symbol = symbol_short!("CODES");
+// link id -> code
+const LCODES: Symbol = symbol_short!("LCODES");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn resolve_code(env: Env, code: BytesN<8>) -> u64 {
+        let codes: Map<BytesN<8>, u64> = env
+            .storage()
+            .instance()
+            .get(&CODES)
+            .unwrap_or(Map::new(&env));
+        codes.get(code).expect("unknown code")
+    }
+
+    pub fn get_link_code(env: Env, link_id: u64) -> Option<BytesN<8>> {
+        Self::link_codes(&env).get(link_id)
+    }
+
+    // Backfills a code for a link created before codes existed.
+    pub fn assign_link_code(env: Env, invoker: Address, link_id: u64) -> BytesN<8> {
+        invoker.require_auth();
+        let merchant = Self::acting_merchant(&env, &invoker);
+        let links: Map<u64, PaymentLink> = env
+            .storage()
+            .instance()
+            .get(&PLINK)
+            .unwrap_or(Map::new(&env));
+        let link = links.get(link_id).expect("no link");
+        assert!(link.merchant == merchant, "not merchant");
+        let code = Self::issue_link_code(&env, &merchant, link_id);
+        Self::emit(&env, (symbol_short!("LinkCode"), link_id), code.clone());
+        code
+    }
+
+    // The code is the first 8 bytes of sha256(merchant, link id, salt).
+    pub(crate) fn issue_link_code(env: &Env, merchant: &Address, link_id: u64) -> BytesN<8> {
+        let mut by_link = Self::link_codes(env);
+        assert!(!by_link.contains_key(link_id), "code exists");
+        let mut codes: Map<BytesN<8>, u64> = env
+            .storage()
+            .instance()
+            .get(&CODES)
+            .unwrap_or(Map::new(env));
+        let digest: BytesN<32> = env
+            .crypto()
+            .sha256(&(merchant.clone(), link_id, Self::code_salt(env)).to_xdr(env))
+            .into();
+        let mut short = [0u8; 8];
+        short.copy_from_slice(&digest.to_array()[..8]);
+        let code = BytesN::from_array(env, &short);
+        assert!(!codes.contains_key(code.clone()), "code collision");
+        codes.set(code.clone(), link_id);
+        by_link.set(link_id, code.clone());
+        env.storage().instance().set(&CODES, &codes);
+        env.storage().instance().set(&LCODES, &by_link);
+        code
+    }
+
+    pub(crate) fn forget_link_code(env: &Env, link_id: u64) {
+        let mut by_link = Self::link_codes(env);
+        let code = match by_link.get(link_id) {
+            Some(code) => code,
+            None => return,
+        };
+        let mut codes: Map<BytesN<8>, u64> = env
+            .storage()
+            .instance()
+            .get(&CODES)
+            .unwrap_or(Map::new(env));
+        codes.remove(code);
+        by_link.remove(link_id);
+        env.storage().instance().set(&CODES, &codes);
+        env.storage().instance().set(&LCODES, &by_link);
+    }
+
+    fn code_salt(env: &Env) -> BytesN<32> {
+        if let Some(salt) = env.storage().instance().get(&CODESALT) {
+            return salt;
+        }
+        let salt: BytesN<32> = env.prng().gen();
+        env.storage().instance().set(&CODESALT, &salt);
+        salt
+    }
+
+    fn link_codes(env: &Env) -> Map<u64, BytesN<8>> {
+        env.storage()
+            .instance()
+            .get(&LCODES)
+            .unwrap_or(Map::new(env))
+    }
+}
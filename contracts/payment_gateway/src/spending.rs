@@ -0,0 +1,111 @@
+use soroban_sdk::{
+    contractimpl, contracttype, panic_with_error, symbol_short, Address, Env, Map, Symbol, Vec,
+};
+
+use crate::{Error, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LimitWindow {
+    Daily,
+    Weekly,
+}
+
+impl LimitWindow {
+    fn seconds(self) -> u64 {
+        match self {
+            LimitWindow::Daily => 86_400,
+            LimitWindow::Weekly => 7 * 86_400,
+        }
+    }
+}
+
+// A payer's own cap on what the gateway may pull from it over a rolling window.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpendingLimit {
+    amount: i128,
+    window: LimitWindow,
+}
+
+const SPLIMIT: Symbol = symbol_short!("SPLIMIT");
+// (SPENT, payer) -> (timestamp, amount) of each payment still inside its window
+const SPENT: Symbol = symbol_short!("SPENT");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_spending_limit(env: Env, invoker: Address, amount: i128, window: LimitWindow) {
+        invoker.require_auth();
+        assert!(amount > 0, "amount>0");
+        let mut limits: Map<Address, SpendingLimit> = env
+            .storage()
+            .instance()
+            .get(&SPLIMIT)
+            .unwrap_or(Map::new(&env));
+        limits.set(invoker.clone(), SpendingLimit { amount, window });
+        env.storage().instance().set(&SPLIMIT, &limits);
+        Self::emit(&env, (symbol_short!("SpendLim"), invoker), (amount, window));
+    }
+
+    pub fn clear_spending_limit(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let mut limits: Map<Address, SpendingLimit> = env
+            .storage()
+            .instance()
+            .get(&SPLIMIT)
+            .unwrap_or(Map::new(&env));
+        assert!(limits.contains_key(invoker.clone()), "no limit");
+        limits.remove(invoker.clone());
+        env.storage().instance().set(&SPLIMIT, &limits);
+        Self::drop_record(&env, &(SPENT, invoker.clone()));
+        Self::emit(&env, (symbol_short!("SpendClr"),), &invoker);
+    }
+
+    pub fn get_spending_limit(env: Env, payer: Address) -> Option<SpendingLimit> {
+        let limits: Map<Address, SpendingLimit> = env
+            .storage()
+            .instance()
+            .get(&SPLIMIT)
+            .unwrap_or(Map::new(&env));
+        limits.get(payer)
+    }
+
+    // `None` when the payer has not set a limit.
+    pub fn remaining_budget(env: Env, payer: Address) -> Option<i128> {
+        let limit = Self::get_spending_limit(env.clone(), payer.clone())?;
+        let spent = Self::spent_within(&env, &payer, limit.window);
+        let mut total = 0;
+        for (_, amount) in spent.iter() {
+            total += amount;
+        }
+        Some(limit.amount - total)
+    }
+
+    // Counts a payment against the payer's limit, if any, rejecting it once over budget.
+    pub(crate) fn track_spending(env: &Env, payer: &Address, amount: &i128) {
+        let limit = match Self::get_spending_limit(env.clone(), payer.clone()) {
+            Some(l) => l,
+            None => return,
+        };
+        let remaining = Self::remaining_budget(env.clone(), payer.clone()).unwrap_or(0);
+        if *amount > remaining {
+            panic_with_error!(env, Error::SpendingLimitExceeded);
+        }
+        let mut entries = Self::spent_within(env, payer, limit.window);
+        entries.push_back((env.ledger().timestamp(), *amount));
+        Self::put_record(env, &(SPENT, payer.clone()), &entries);
+    }
+
+    fn spent_within(env: &Env, payer: &Address, window: LimitWindow) -> Vec<(u64, i128)> {
+        let spent: Vec<(u64, i128)> =
+            Self::get_record(env, &(SPENT, payer.clone())).unwrap_or(Vec::new(env));
+        let since = env.ledger().timestamp().saturating_sub(window.seconds());
+        let mut recent = Vec::new(env);
+        for (at, amount) in spent.iter() {
+            if at > since {
+                recent.push_back((at, amount));
+            }
+        }
+        recent
+    }
+}
@@ -0,0 +1,169 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantStats {
+    volume: i128,
+    payments: u32,
+    refunded: i128,
+    active_links: u32,
+    active_subscribers: u32,
+}
+
+// Active subscribers and their summed per-interval amount.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanStats {
+    subscribers: u32,
+    recurring: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GlobalStats {
+    volume: i128,
+    fees: i128,
+    merchants: u32,
+    active_links: u32,
+    active_subscriptions: u32,
+}
+
+const MSTATS: Symbol = symbol_short!("MSTATS");
+const PSTATS: Symbol = symbol_short!("PSTATS");
+const GSTATS: Symbol = symbol_short!("GSTATS");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn merchant_stats(env: Env, merchant: Address) -> MerchantStats {
+        let all: Map<Address, MerchantStats> = env
+            .storage()
+            .instance()
+            .get(&MSTATS)
+            .unwrap_or(Map::new(&env));
+        all.get(merchant).unwrap_or(MerchantStats {
+            volume: 0,
+            payments: 0,
+            refunded: 0,
+            active_links: 0,
+            active_subscribers: 0,
+        })
+    }
+
+    // `counted` is false for escrow releases, whose payment was never a single charge.
+    pub(crate) fn record_sale(env: &Env, merchant: &Address, amount: &i128, counted: bool) {
+        Self::update_stats(env, merchant, |s| {
+            s.volume += amount;
+            if counted {
+                s.payments += 1;
+            }
+        });
+        Self::update_global(env, |g| g.volume += amount);
+    }
+
+    pub(crate) fn record_fee(env: &Env, fee: &i128) {
+        Self::update_global(env, |g| g.fees += fee);
+    }
+
+    pub(crate) fn record_refund(env: &Env, merchant: &Address, amount: &i128) {
+        Self::update_stats(env, merchant, |s| s.refunded += amount);
+    }
+
+    pub(crate) fn count_link(env: &Env, merchant: &Address, opened: bool) {
+        Self::update_stats(env, merchant, |s| {
+            s.active_links = if opened {
+                s.active_links + 1
+            } else {
+                s.active_links - 1
+            }
+        });
+        Self::update_global(env, |g| {
+            g.active_links = if opened {
+                g.active_links + 1
+            } else {
+                g.active_links - 1
+            }
+        });
+    }
+
+    pub(crate) fn count_subscriber(env: &Env, merchant: &Address, joined: bool) {
+        Self::update_stats(env, merchant, |s| {
+            s.active_subscribers = if joined {
+                s.active_subscribers + 1
+            } else {
+                s.active_subscribers - 1
+            }
+        });
+        Self::update_global(env, |g| {
+            g.active_subscriptions = if joined {
+                g.active_subscriptions + 1
+            } else {
+                g.active_subscriptions - 1
+            }
+        });
+    }
+
+    pub fn plan_stats(env: Env, plan_id: u64) -> PlanStats {
+        let all: Map<u64, PlanStats> = env
+            .storage()
+            .instance()
+            .get(&PSTATS)
+            .unwrap_or(Map::new(&env));
+        all.get(plan_id).unwrap_or(PlanStats {
+            subscribers: 0,
+            recurring: 0,
+        })
+    }
+
+    pub(crate) fn count_plan_subscriber(env: &Env, plan_id: u64, amount: &i128, joined: bool) {
+        let mut stats = Self::plan_stats(env.clone(), plan_id);
+        if joined {
+            stats.subscribers += 1;
+            stats.recurring += amount;
+        } else {
+            stats.subscribers -= 1;
+            stats.recurring -= amount;
+        }
+        let mut all: Map<u64, PlanStats> = env
+            .storage()
+            .instance()
+            .get(&PSTATS)
+            .unwrap_or(Map::new(env));
+        all.set(plan_id, stats);
+        env.storage().instance().set(&PSTATS, &all);
+    }
+
+    // `fees` counts every fee ever accrued, including those already collected.
+    pub fn global_stats(env: Env) -> GlobalStats {
+        let stats: Option<GlobalStats> = env.storage().instance().get(&GSTATS);
+        GlobalStats {
+            merchants: Self::merchant_count(&env),
+            ..stats.unwrap_or(GlobalStats {
+                volume: 0,
+                fees: 0,
+                merchants: 0,
+                active_links: 0,
+                active_subscriptions: 0,
+            })
+        }
+    }
+
+    fn update_global(env: &Env, f: impl FnOnce(&mut GlobalStats)) {
+        let mut stats = Self::global_stats(env.clone());
+        f(&mut stats);
+        env.storage().instance().set(&GSTATS, &stats);
+    }
+
+    fn update_stats(env: &Env, merchant: &Address, f: impl FnOnce(&mut MerchantStats)) {
+        let mut stats = Self::merchant_stats(env.clone(), merchant.clone());
+        f(&mut stats);
+        let mut all: Map<Address, MerchantStats> = env
+            .storage()
+            .instance()
+            .get(&MSTATS)
+            .unwrap_or(Map::new(env));
+        all.set(merchant.clone(), stats);
+        env.storage().instance().set(&MSTATS, &all);
+    }
+}
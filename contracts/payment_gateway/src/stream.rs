@@ -0,0 +1,148 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Timepoint};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Stream {
+    payer: Address,
+    merchant: Address,
+    amount: i128,
+    start_time: Timepoint,
+    end_time: Timepoint,
+    withdrawn: i128,
+    active: bool,
+}
+
+const STCTR: Symbol = symbol_short!("STCTR");
+const STRM: Symbol = symbol_short!("STRM");
+
+impl Stream {
+    // Linear accrual between start and end, clamped to the window.
+    fn accrued(&self, env: &Env) -> i128 {
+        let now = env.ledger().timestamp();
+        let start = self.start_time.to_unix();
+        let end = self.end_time.to_unix();
+        if now <= start {
+            0
+        } else if now >= end {
+            self.amount
+        } else {
+            let elapsed = self.amount.checked_mul((now - start) as i128);
+            PaymentGateway::checked(env, elapsed) / (end - start) as i128
+        }
+    }
+}
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn create_stream(
+        env: Env,
+        invoker: Address,
+        merchant: Address,
+        amount: i128,
+        start_time: u64,
+        end_time: u64,
+    ) -> u32 {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &merchant), "not merchant");
+        assert!(amount > 0, "amount>0");
+        assert!(start_time >= env.ledger().timestamp(), "start in past");
+        assert!(end_time > start_time, "end>start");
+        let ctr = Self::next_id(&env, &STCTR);
+        let stream = Stream {
+            payer: invoker.clone(),
+            merchant,
+            amount,
+            start_time: Timepoint::from_unix(&env, start_time),
+            end_time: Timepoint::from_unix(&env, end_time),
+            withdrawn: 0,
+            active: true,
+        };
+        Self::save_stream(&env, ctr, &stream);
+        Self::collect(&env, &invoker, &amount);
+        Self::emit(&env, (symbol_short!("StrmCr"), ctr), ctr);
+        ctr
+    }
+
+    pub fn withdraw_streamed(env: Env, invoker: Address, stream_id: u32) -> i128 {
+        invoker.require_auth();
+        let mut stream = Self::get_stream(env.clone(), stream_id);
+        assert!(stream.active, "stream inactive");
+        assert!(stream.merchant == invoker, "not merchant");
+        let due = stream.accrued(&env) - stream.withdrawn;
+        assert!(due > 0, "nothing accrued");
+        stream.withdrawn += due;
+        if stream.withdrawn == stream.amount {
+            stream.active = false;
+        }
+        Self::save_stream(&env, stream_id, &stream);
+        Self::release(&env, &stream.merchant, &due);
+        Self::emit(&env, (symbol_short!("StrmWd"), stream_id), due);
+        due
+    }
+
+    // Either party may stop the stream; accrued funds go to the merchant, the rest back to the payer.
+    pub fn cancel_stream(env: Env, invoker: Address, stream_id: u32) {
+        invoker.require_auth();
+        let mut stream = Self::get_stream(env.clone(), stream_id);
+        assert!(stream.active, "stream inactive");
+        assert!(
+            invoker == stream.payer || invoker == stream.merchant,
+            "not authorized"
+        );
+        let accrued = stream.accrued(&env);
+        let to_merchant = accrued - stream.withdrawn;
+        let to_payer = stream.amount - accrued;
+        stream.withdrawn = accrued;
+        stream.active = false;
+        Self::save_stream(&env, stream_id, &stream);
+        if to_merchant > 0 {
+            Self::release(&env, &stream.merchant, &to_merchant);
+        }
+        if to_payer > 0 {
+            Self::payout(&env, &stream.payer, &to_payer);
+        }
+        Self::emit(
+            &env,
+            (symbol_short!("StrmCnl"), stream_id),
+            (to_merchant, to_payer),
+        );
+    }
+
+    pub fn get_stream(env: Env, stream_id: u32) -> Stream {
+        let streams: Map<u32, Stream> = env
+            .storage()
+            .instance()
+            .get(&STRM)
+            .unwrap_or(Map::new(&env));
+        streams.get(stream_id).expect("no stream")
+    }
+
+    pub fn streamed_balance(env: Env, stream_id: u32) -> i128 {
+        let stream = Self::get_stream(env.clone(), stream_id);
+        if !stream.active {
+            return 0;
+        }
+        stream.accrued(&env) - stream.withdrawn
+    }
+
+    fn save_stream(env: &Env, stream_id: u32, stream: &Stream) {
+        let mut streams: Map<u32, Stream> =
+            env.storage().instance().get(&STRM).unwrap_or(Map::new(env));
+        streams.set(stream_id, stream.clone());
+        env.storage().instance().set(&STRM, &streams);
+    }
+
+    pub(crate) fn streams_held(env: &Env) -> i128 {
+        let streams: Map<u32, Stream> =
+            env.storage().instance().get(&STRM).unwrap_or(Map::new(env));
+        let mut total = 0;
+        for (_, stream) in streams.iter() {
+            if stream.active {
+                total += stream.amount - stream.withdrawn;
+            }
+        }
+        total
+    }
+}
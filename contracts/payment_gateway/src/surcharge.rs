@@ -0,0 +1,61 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient};
+
+// Added on top of a link's price and paid by the customer: `flat` plus `bps` of the price.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SurchargePolicy {
+    flat: i128,
+    bps: u32,
+}
+
+const SURCH: Symbol = symbol_short!("SURCH");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_surcharge(env: Env, invoker: Address, flat: i128, bps: u32) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(flat >= 0, "flat>=0");
+        assert!(bps <= 10_000, "bps<=10000");
+        assert!(flat > 0 || bps > 0, "empty surcharge");
+        let mut policies: Map<Address, SurchargePolicy> = env
+            .storage()
+            .instance()
+            .get(&SURCH)
+            .unwrap_or(Map::new(&env));
+        policies.set(invoker.clone(), SurchargePolicy { flat, bps });
+        env.storage().instance().set(&SURCH, &policies);
+        Self::emit(&env, (symbol_short!("SurchSet"),), &invoker);
+    }
+
+    pub fn clear_surcharge(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let mut policies: Map<Address, SurchargePolicy> = env
+            .storage()
+            .instance()
+            .get(&SURCH)
+            .unwrap_or(Map::new(&env));
+        assert!(policies.contains_key(invoker.clone()), "no surcharge");
+        policies.remove(invoker.clone());
+        env.storage().instance().set(&SURCH, &policies);
+        Self::emit(&env, (symbol_short!("SurchClr"),), &invoker);
+    }
+
+    pub fn get_surcharge(env: Env, merchant: Address) -> Option<SurchargePolicy> {
+        let policies: Map<Address, SurchargePolicy> = env
+            .storage()
+            .instance()
+            .get(&SURCH)
+            .unwrap_or(Map::new(&env));
+        policies.get(merchant)
+    }
+
+    pub(crate) fn surcharge_for(env: &Env, merchant: &Address, amount: &i128) -> i128 {
+        match Self::get_surcharge(env.clone(), merchant.clone()) {
+            Some(policy) => policy.flat + Self::bps_of(env, amount, policy.bps),
+            None => 0,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, Vec};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, TaxLine};
+
+// Prices are tax-inclusive: a payment of `amount` carries `amount * bps / (10000 + bps)` of
+// tax. With `remit_to` set that part is paid straight to the tax address instead of the
+// merchant.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TaxRate {
+    bps: u32,
+    jurisdiction: Symbol,
+    remit_to: Option<Address>,
+}
+
+const TAXRATE: Symbol = symbol_short!("TAXRATE");
+
+#[contractimpl]
+impl PaymentGateway {
+    pub fn set_tax_rate(
+        env: Env,
+        invoker: Address,
+        bps: u32,
+        jurisdiction: Symbol,
+        remit_to: Option<Address>,
+    ) {
+        invoker.require_auth();
+        assert!(Self::is_merchant(&env, &invoker), "not authorized");
+        assert!(bps > 0 && bps <= 10_000, "bps 1..=10000");
+        let mut rates: Map<Address, TaxRate> = env
+            .storage()
+            .instance()
+            .get(&TAXRATE)
+            .unwrap_or(Map::new(&env));
+        rates.set(
+            invoker.clone(),
+            TaxRate {
+                bps,
+                jurisdiction: jurisdiction.clone(),
+                remit_to,
+            },
+        );
+        env.storage().instance().set(&TAXRATE, &rates);
+        Self::emit(
+            &env,
+            (symbol_short!("TaxSet"), invoker),
+            (bps, jurisdiction),
+        );
+    }
+
+    pub fn clear_tax_rate(env: Env, invoker: Address) {
+        invoker.require_auth();
+        let mut rates: Map<Address, TaxRate> = env
+            .storage()
+            .instance()
+            .get(&TAXRATE)
+            .unwrap_or(Map::new(&env));
+        assert!(rates.contains_key(invoker.clone()), "no tax rate");
+        rates.remove(invoker.clone());
+        env.storage().instance().set(&TAXRATE, &rates);
+        Self::emit(&env, (symbol_short!("TaxClr"),), &invoker);
+    }
+
+    pub fn get_tax_rate(env: Env, merchant: Address) -> Option<TaxRate> {
+        let rates: Map<Address, TaxRate> = env
+            .storage()
+            .instance()
+            .get(&TAXRATE)
+            .unwrap_or(Map::new(&env));
+        rates.get(merchant)
+    }
+
+    // Like `settle`, first splitting the tax out of `taxable` (part of `amount`) when the
+    // merchant has a rate. Returns the protocol fee and the tax lines for the receipt.
+    pub(crate) fn settle_taxed(
+        env: &Env,
+        spender: &Address,
+        from: &Address,
+        merchant: &Address,
+        amount: &i128,
+        taxable: &i128,
+    ) -> (i128, Vec<TaxLine>) {
+        let (line, remit_to) = match Self::tax_line(env, merchant, taxable) {
+            Some(t) => t,
+            None => {
+                return (
+                    Self::settle(env, spender, from, merchant, amount),
+                    Vec::new(env),
+                )
+            }
+        };
+        let net = match remit_to {
+            Some(to) => {
+                Self::assert_not_paused(env);
+                Self::track_volume(env, &line.amount);
+                Self::track_spending(env, from, &line.amount);
+                Self::transfer_from(env, spender, from, &to, &line.amount);
+                amount - line.amount
+            }
+            None => *amount,
+        };
+        (
+            Self::settle(env, spender, from, merchant, &net),
+            Vec::from_array(env, [line]),
+        )
+    }
+
+    // `settle_taxed` for funds the gateway already holds.
+    pub(crate) fn settle_held_taxed(
+        env: &Env,
+        merchant: &Address,
+        amount: &i128,
+        taxable: &i128,
+    ) -> (i128, Vec<TaxLine>) {
+        let (line, remit_to) = match Self::tax_line(env, merchant, taxable) {
+            Some(t) => t,
+            None => return (Self::settle_held(env, merchant, amount), Vec::new(env)),
+        };
+        let net = match remit_to {
+            Some(to) => {
+                Self::payout(env, &to, &line.amount);
+                amount - line.amount
+            }
+            None => *amount,
+        };
+        (
+            Self::settle_held(env, merchant, &net),
+            Vec::from_array(env, [line]),
+        )
+    }
+
+    fn tax_line(
+        env: &Env,
+        merchant: &Address,
+        taxable: &i128,
+    ) -> Option<(TaxLine, Option<Address>)> {
+        let rate = Self::get_tax_rate(env.clone(), merchant.clone())?;
+        let amount = taxable * rate.bps as i128 / (10_000 + rate.bps as i128);
+        let line = TaxLine {
+            amount,
+            jurisdiction: rate.jurisdiction,
+            remitted: rate.remit_to.is_some(),
+        };
+        Some((line, rate.remit_to))
+    }
+}
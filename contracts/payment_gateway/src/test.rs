@@ -1,21 +1,1344 @@
 #![cfg(test)]
 
-use super::*;
-use soroban_sdk::{vec, Env, String};
+use soroban_sdk::{
+    contracttype, symbol_short,
+    testutils::{Address as _, AuthorizedFunction, Ledger, MockAuth, MockAuthInvoke},
+    token, vec, Address, BytesN, Env, IntoVal, Map, String, Symbol, Timepoint, I256,
+};
+
+use crate::testutils::{
+    MockOracle, MockOracleClient, MockPool, MockPoolClient, MockRouter, MockRouterClient,
+    MockToken, MockTokenClient, Setup,
+};
+use crate::{
+    migrate, AdminAction, Error, LimitWindow, LineItem, PaymentGateway, PaymentLink, RateAction,
+    SubscriptionStatus, LCTR, OWNER, PCTR, PLINK, SPLAN, SUBS, TOKEN,
+};
+
+const PRICE: i128 = 10_000_000;
+const DAY: u32 = 86_400;
+
+#[test]
+fn link_payment_settles_net_of_fee_and_refunds() {
+    let s = Setup::new();
+    let treasury = Address::generate(&s.env);
+    s.gateway.set_fee(&s.owner, &100, &treasury);
+    let merchant = s.merchant();
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+
+    let receipt_id = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    let fee = PRICE / 100;
+    assert_eq!(s.token.balance(&merchant), PRICE - fee);
+    assert_eq!(s.token.balance(&s.gateway.address), fee);
+    assert_eq!(s.gateway.accrued_fees(), fee);
+    assert_eq!(s.gateway.get_receipt(&receipt_id).fee, fee);
+
+    s.gateway
+        .refund_payment(&merchant, &receipt_id, &(PRICE / 4));
+    assert_eq!(s.token.balance(&payer), PRICE / 4);
+    assert_eq!(s.gateway.get_receipt(&receipt_id).refunded, PRICE / 4);
+    assert!(s
+        .gateway
+        .try_refund_payment(&merchant, &receipt_id, &PRICE)
+        .is_err());
+}
 
 #[test]
-fn test() {
+fn payments_settle_against_a_stellar_asset_contract() {
     let env = Env::default();
-    let contract_id = env.register(Contract, ());
-    let client = ContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    let owner = Address::generate(&env);
+    let issuer = Address::generate(&env);
+    let sac = env.register_stellar_asset_contract_v2(issuer);
+    let token = token::Client::new(&env, &sac.address());
+    let gateway = crate::PaymentGatewayClient::new(&env, &env.register(PaymentGateway, ()));
+    gateway.init(&owner, &sac.address());
+    let merchant = Address::generate(&env);
+    gateway.add_merchant(&owner, &merchant);
+    gateway.create_payment_link(&merchant, &PRICE, &String::from_str(&env, "item"));
+    gateway.create_subscription_plan(&merchant, &PRICE, &DAY, &String::from_str(&env, "plan"));
+    let payer = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &sac.address()).mint(&payer, &(2 * PRICE));
+
+    // the payer's own payment needs no allowance
+    gateway.process_payment(&payer, &1, &PRICE, &None, &None);
+    assert_eq!(token.balance(&merchant), PRICE);
+    gateway.subscribe(&payer, &1, &PRICE);
+    assert_eq!(token.balance(&merchant), 2 * PRICE);
+
+    // later charges are pulled against the payer's allowance
+    let keeper = Address::generate(&env);
+    gateway.add_keeper(&owner, &keeper);
+    env.ledger().with_mut(|l| l.timestamp += DAY as u64);
+    let charge = || gateway.try_process_subscription_payment(&keeper, &payer, &1, &None);
+    token::StellarAssetClient::new(&env, &sac.address()).mint(&payer, &PRICE);
+    assert!(charge().is_err());
+    token.approve(
+        &payer,
+        &gateway.address,
+        &PRICE,
+        &(env.ledger().sequence() + 100),
+    );
+    assert!(charge().is_ok());
+    assert_eq!(token.balance(&merchant), 3 * PRICE);
+}
+
+#[test]
+fn invoice_is_paid_once_by_its_addressee() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let customer = s.funded(3 * PRICE);
+    let other = s.funded(3 * PRICE);
+    let item = |quantity, unit_price| LineItem {
+        description: symbol_short!("widget"),
+        quantity,
+        unit_price,
+    };
+    let items = vec![&s.env, item(2, PRICE), item(1, PRICE / 2)];
+    let due = s.env.ledger().timestamp() + DAY as u64;
+    assert!(s
+        .gateway
+        .try_create_invoice(&customer, &customer, &items, &due)
+        .is_err());
+    assert!(s
+        .gateway
+        .try_create_invoice(&merchant, &customer, &vec![&s.env, item(0, PRICE)], &due)
+        .is_err());
+    let invoice = s.gateway.create_invoice(&merchant, &customer, &items, &due);
+
+    assert!(s.gateway.try_pay_invoice(&other, &invoice).is_err());
+    // overdue invoices can still be paid
+    s.advance(DAY as u64 + 1);
+    let receipt_id = s.gateway.pay_invoice(&customer, &invoice);
+    assert_eq!(
+        s.gateway.get_receipt(&receipt_id).amount,
+        2 * PRICE + PRICE / 2
+    );
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE + PRICE / 2);
+    assert!(s.gateway.try_pay_invoice(&customer, &invoice).is_err());
+
+    let voided = s
+        .gateway
+        .create_invoice(&merchant, &customer, &items, &(due + DAY as u64 * 2));
+    s.gateway.void_invoice(&merchant, &voided);
+    assert!(s.gateway.try_pay_invoice(&customer, &voided).is_err());
+}
+
+#[test]
+fn init_cannot_be_called_twice() {
+    let s = Setup::new();
+    let attacker = Address::generate(&s.env);
+    let merchant = s.merchant();
+    s.link(&merchant, PRICE);
+
+    assert!(s.gateway.try_init(&attacker, &attacker).is_err());
+    assert!(s.gateway.try_set_fee(&attacker, &100, &attacker).is_err());
+    assert_eq!(s.link(&merchant, PRICE), 2);
+}
+
+#[test]
+fn retried_order_returns_original_receipt() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let payer = s.funded(2 * PRICE);
+    let link = s.link(&merchant, PRICE);
+    let order = Some(BytesN::from_array(&s.env, &[1; 32]));
+
+    let first = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &order, &None);
+    let again = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &order, &None);
+    assert_eq!(first, again);
+    assert_eq!(s.token.balance(&merchant), PRICE);
+}
+
+#[test]
+fn payer_signature_covers_order_id_and_memo() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+    let none: Option<BytesN<32>> = None;
+    let memo: Option<String> = None;
+    s.env.mock_auths(&[MockAuth {
+        address: &payer,
+        invoke: &MockAuthInvoke {
+            contract: &s.gateway.address,
+            fn_name: "process_payment",
+            args: (&payer, link, PRICE, none.clone(), memo.clone()).into_val(&s.env),
+            sub_invokes: &[MockAuthInvoke {
+                contract: &s.token.address,
+                fn_name: "transfer",
+                args: (&payer, &merchant, PRICE).into_val(&s.env),
+                sub_invokes: &[],
+            }],
+        },
+    }]);
+
+    let order = Some(BytesN::from_array(&s.env, &[1; 32]));
+    assert!(s
+        .gateway
+        .try_process_payment(&payer, &link, &PRICE, &order, &memo)
+        .is_err());
+    let relayed = Some(String::from_str(&s.env, "relayed"));
+    assert!(s
+        .gateway
+        .try_process_payment(&payer, &link, &PRICE, &none, &relayed)
+        .is_err());
+    s.gateway
+        .process_payment(&payer, &link, &PRICE, &none, &memo);
+    assert_eq!(s.token.balance(&merchant), PRICE);
+}
 
-    let words = client.hello(&String::from_str(&env, "Dev"));
+#[test]
+fn failed_receipt_nft_mint_does_not_block_payment() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+    // the token contract has no matching `mint`, so every mint attempt fails
+    s.gateway
+        .set_receipt_nft(&s.owner, &Some(s.token.address.clone()));
+
+    let receipt_id = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    assert!(s.emitted(
+        (symbol_short!("NftFail"), receipt_id),
+        s.token.address.clone()
+    ));
+    assert_eq!(s.token.balance(&merchant), PRICE);
+}
+
+#[test]
+fn private_links_limits_and_blocks_gate_who_can_pay() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let link = s.link(&merchant, PRICE);
+    let listed = s.funded(3 * PRICE);
+    let stranger = s.funded(PRICE);
+    s.gateway.set_link_private(&merchant, &link, &true);
+    s.gateway.allow_payer(&merchant, &link, &listed);
+    s.gateway.set_purchase_limit(&merchant, &link, &2);
+    let pay = |payer: &Address| {
+        s.gateway
+            .try_process_payment(payer, &link, &PRICE, &None, &None)
+            .err()
+    };
+
+    assert_eq!(pay(&stranger), Some(Ok(rejected(Error::PayerNotAllowed))));
+    assert_eq!(pay(&listed), None);
+    assert_eq!(pay(&listed), None);
+    assert_eq!(s.gateway.purchase_count(&link, &listed), 2);
+    assert_eq!(
+        pay(&listed),
+        Some(Ok(rejected(Error::PurchaseLimitReached)))
+    );
+
+    s.gateway.set_purchase_limit(&merchant, &link, &0);
+    s.gateway.block_payer(&merchant, &listed);
+    assert!(pay(&listed).is_some());
+    s.gateway.unblock_payer(&merchant, &listed);
+    assert_eq!(pay(&listed), None);
+
+    s.gateway.disallow_payer(&merchant, &link, &listed);
+    assert!(!s.gateway.is_payer_allowed(&link, &listed));
+    assert!(s
+        .gateway
+        .try_disallow_payer(&merchant, &link, &listed)
+        .is_err());
+}
+
+#[test]
+fn cart_is_paid_in_one_signed_call() {
+    let s = Setup::new();
+    let [m1, m2] = [s.merchant(), s.merchant()];
+    let links = vec![&s.env, s.link(&m1, PRICE), s.link(&m2, PRICE)];
+    let payer = s.funded(2 * PRICE);
+
+    assert!(s
+        .gateway
+        .try_process_payments(&payer, &links, &(2 * PRICE), &true)
+        .is_err());
+    assert!(s
+        .gateway
+        .try_process_payments(&payer, &links, &PRICE, &false)
+        .is_err());
+    let cart = s
+        .gateway
+        .process_payments(&payer, &links, &(2 * PRICE), &false);
+    // the payer's signature also covers the single-merchant flag
+    let (signer, invocation) = s.env.auths().remove(0);
+    assert_eq!(signer, payer);
+    assert_eq!(
+        invocation.function,
+        AuthorizedFunction::Contract((
+            s.gateway.address.clone(),
+            Symbol::new(&s.env, "process_payments"),
+            (payer.clone(), links.clone(), 2 * PRICE, false).into_val(&s.env),
+        ))
+    );
+    assert!(s.gateway.try_get_cart(&cart).is_ok());
+    assert_eq!(s.token.balance(&m1), PRICE);
+    assert_eq!(s.token.balance(&m2), PRICE);
+}
+
+#[test]
+fn marketplace_link_pays_the_seller_its_share() {
+    let s = Setup::new();
+    let platform = s.merchant();
+    let seller = Address::generate(&s.env);
+    let link = s.link(&platform, PRICE);
+    s.gateway.set_marketplace(&platform, &Some(1_000));
+    s.gateway
+        .set_link_seller(&platform, &link, &Some(seller.clone()));
+    let payer = s.funded(2 * PRICE);
+
+    s.gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    s.gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    assert_eq!(s.token.balance(&platform), 2 * PRICE / 10);
+    assert_eq!(s.token.balance(&seller), 2 * PRICE * 9 / 10);
     assert_eq!(
-        words,
-        vec![
+        s.gateway.seller_earnings(&platform, &seller),
+        2 * PRICE * 9 / 10
+    );
+}
+
+#[test]
+fn scheduled_payment_runs_against_a_live_link() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let link = s.link(&merchant, PRICE);
+    let payer = s.funded(2 * PRICE);
+    let now = s.env.ledger().timestamp();
+    assert!(s
+        .gateway
+        .try_schedule_payment(&payer, &(link + 1), &(now + 10), &(now + DAY as u64))
+        .is_err());
+    let first = s
+        .gateway
+        .schedule_payment(&payer, &link, &(now + 10), &(now + DAY as u64));
+    let second = s
+        .gateway
+        .schedule_payment(&payer, &link, &(now + 10), &(now + DAY as u64));
+
+    assert!(s.gateway.try_execute_scheduled_payment(&first).is_err());
+    s.advance(10);
+    s.gateway.execute_scheduled_payment(&first);
+    assert_eq!(s.token.balance(&merchant), PRICE);
+    s.gateway.deactivate_payment_link(&merchant, &link);
+    assert!(s.gateway.try_execute_scheduled_payment(&second).is_err());
+    assert_eq!(s.token.balance(&merchant), PRICE);
+}
+
+#[test]
+fn payment_requests_are_paid_once_by_their_customer() {
+    let s = Setup::new();
+    s.gateway.set_min_price(&s.owner, &100);
+    let merchant = s.merchant();
+    let customer = s.funded(PRICE);
+    let memo = symbol_short!("consult");
+    assert!(s
+        .gateway
+        .try_request_payment(
+            &merchant,
+            &customer,
+            &(PRICE / 10 - 1),
+            &memo,
+            &(DAY as u64)
+        )
+        .is_err());
+    assert!(s
+        .gateway
+        .try_request_payment(&customer, &merchant, &PRICE, &memo, &(DAY as u64))
+        .is_err());
+    let paid = s
+        .gateway
+        .request_payment(&merchant, &customer, &PRICE, &memo, &(DAY as u64));
+    let lapsed = s
+        .gateway
+        .request_payment(&merchant, &customer, &PRICE, &memo, &10);
+
+    assert!(s
+        .gateway
+        .try_approve_payment_request(&merchant, &paid)
+        .is_err());
+    s.gateway.approve_payment_request(&customer, &paid);
+    assert_eq!(s.token.balance(&merchant), PRICE);
+    assert!(s
+        .gateway
+        .try_approve_payment_request(&customer, &paid)
+        .is_err());
+    s.advance(11);
+    assert!(s
+        .gateway
+        .try_approve_payment_request(&customer, &lapsed)
+        .is_err());
+    s.gateway.expire_payment_request(&lapsed);
+}
+
+#[test]
+fn custodial_revenue_is_held_until_withdrawn() {
+    let s = Setup::new();
+    let treasury = Address::generate(&s.env);
+    s.gateway.set_fee(&s.owner, &100, &treasury);
+    let merchant = s.merchant();
+    s.gateway.set_custodial(&merchant, &true);
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+
+    s.gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    assert_eq!(s.gateway.merchant_balance(&merchant), PRICE);
+    assert_eq!(s.token.balance(&merchant), 0);
+
+    let net = s.gateway.withdraw(&merchant, &PRICE);
+    assert_eq!(net, PRICE - PRICE / 100);
+    assert_eq!(s.token.balance(&merchant), net);
+    assert_eq!(s.gateway.merchant_balance(&merchant), 0);
+}
+
+#[test]
+fn subscription_charges_draw_from_deposit() {
+    let s = Setup::new();
+    s.env.mock_all_auths_allowing_non_root_auth();
+    let merchant = s.merchant();
+    let plan = s.plan(&merchant, PRICE, DAY);
+    let payer = s.funded(4 * PRICE);
+
+    s.gateway.subscribe(&payer, &plan, &PRICE);
+    s.gateway
+        .deposit_for_subscription(&payer, &payer, &1, &(2 * PRICE));
+    assert_eq!(s.token.balance(&payer), PRICE);
+    assert_eq!(s.gateway.subscription_deposit(&payer, &1), 2 * PRICE);
+
+    s.advance(DAY as u64);
+    s.gateway
+        .process_subscription_payment(&merchant, &payer, &1, &None);
+    assert_eq!(s.gateway.subscription_deposit(&payer, &1), PRICE);
+    assert_eq!(s.token.balance(&payer), PRICE);
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE);
+
+    // the unspent deposit goes back on cancellation
+    s.gateway.cancel_subscription(&payer, &1);
+    assert_eq!(s.gateway.subscription_deposit(&payer, &1), 0);
+    assert_eq!(s.token.balance(&payer), 2 * PRICE);
+}
+
+#[test]
+fn cancelled_gift_refunds_unstarted_periods() {
+    let s = Setup::new();
+    s.env.mock_all_auths_allowing_non_root_auth();
+    let merchant = s.merchant();
+    s.token.approve(&merchant, &s.gateway.address, &(3 * PRICE));
+    let plan = s.plan(&merchant, PRICE, DAY);
+    let payer = s.funded(3 * PRICE);
+    let friend = Address::generate(&s.env);
+
+    let id = s.gateway.subscribe_for(&payer, &friend, &plan, &3);
+    assert_eq!(s.token.balance(&merchant), 3 * PRICE);
+
+    s.advance(DAY as u64);
+    s.gateway
+        .process_subscription_payment(&merchant, &friend, &id, &None);
+    assert_eq!(s.token.balance(&payer), 0);
+
+    // one prepaid period is left; the beneficiary cancels and the payer gets it back
+    s.gateway.cancel_subscription(&friend, &id);
+    assert_eq!(s.token.balance(&payer), PRICE);
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE);
+}
+
+#[test]
+fn stream_accrues_linearly_and_splits_on_cancel() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let payer = s.funded(PRICE);
+    let start = s.env.ledger().timestamp();
+
+    let id = s
+        .gateway
+        .create_stream(&payer, &merchant, &PRICE, &start, &(start + 100));
+    assert_eq!(s.token.balance(&s.gateway.address), PRICE);
+
+    s.advance(25);
+    assert_eq!(s.gateway.streamed_balance(&id), PRICE / 4);
+    assert_eq!(s.gateway.withdraw_streamed(&merchant, &id), PRICE / 4);
+
+    s.advance(25);
+    s.gateway.cancel_stream(&payer, &id);
+    assert_eq!(s.token.balance(&merchant), PRICE / 2);
+    assert_eq!(s.token.balance(&payer), PRICE / 2);
+    assert_eq!(s.token.balance(&s.gateway.address), 0);
+}
+
+#[test]
+fn milestones_release_and_refund_from_escrow() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let payer = s.funded(PRICE);
+    let names = vec![&s.env, symbol_short!("design"), symbol_short!("build")];
+    let amounts = vec![&s.env, PRICE / 4, PRICE * 3 / 4];
+
+    let id = s
+        .gateway
+        .create_milestone_link(&merchant, &names, &amounts, &None);
+    s.gateway.fund_milestones(&payer, &id);
+    assert_eq!(s.token.balance(&s.gateway.address), PRICE);
+
+    s.gateway.approve_milestone(&payer, &id, &0);
+    assert_eq!(s.token.balance(&merchant), PRICE / 4);
+    assert!(s.gateway.try_approve_milestone(&payer, &id, &0).is_err());
+
+    s.gateway.refund_milestone(&merchant, &id, &1);
+    assert_eq!(s.token.balance(&payer), PRICE * 3 / 4);
+    assert_eq!(s.token.balance(&s.gateway.address), 0);
+}
+
+// Layouts of the original release, which stored amounts as I256, names as Symbols and ids
+// as u32.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct BaselinePaymentLink {
+    merchant: Address,
+    amount: I256,
+    active: bool,
+    description: Symbol,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct BaselineSubscriptionPlan {
+    merchant: Address,
+    amount: I256,
+    interval: u32,
+    active: bool,
+    name: Symbol,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct BaselineSubscription {
+    subscriber: Address,
+    plan_id: u32,
+    start_time: Timepoint,
+    last_payment: Timepoint,
+    active: bool,
+}
+
+// Writes what the original release's `init` and a little use of it leave behind: one
+// merchant with a link and a plan, which `subscriber` joined twice and left once.
+fn baseline(env: &Env, token: &Address, owner: &Address, merchant: &Address, link_price: I256) {
+    let subscriber = Address::generate(env);
+    let storage = env.storage().instance();
+    storage.set(&OWNER, owner);
+    storage.set(&TOKEN, token);
+    storage.set(&symbol_short!("MERCH"), &vec![env, merchant.clone()]);
+    storage.set(&LCTR, &1u32);
+    storage.set(&PCTR, &1u32);
+    storage.set(&symbol_short!("SCTR"), &2u32);
+    let mut links = Map::new(env);
+    links.set(
+        1u32,
+        BaselinePaymentLink {
+            merchant: merchant.clone(),
+            amount: link_price,
+            active: true,
+            description: symbol_short!("item"),
+        },
+    );
+    storage.set(&PLINK, &links);
+    let mut plans = Map::new(env);
+    plans.set(
+        1u32,
+        BaselineSubscriptionPlan {
+            merchant: merchant.clone(),
+            amount: I256::from_i128(env, PRICE),
+            interval: DAY,
+            active: true,
+            name: symbol_short!("gold"),
+        },
+    );
+    storage.set(&SPLAN, &plans);
+    let mut subs = Map::new(env);
+    for (id, active) in [(1u32, false), (2u32, true)] {
+        subs.set(
+            (subscriber.clone(), id),
+            BaselineSubscription {
+                subscriber: subscriber.clone(),
+                plan_id: 1,
+                start_time: Timepoint::from_unix(env, 0),
+                last_payment: Timepoint::from_unix(env, DAY as u64),
+                active,
+            },
+        );
+    }
+    storage.set(&SUBS, &subs);
+}
+
+#[test]
+fn migrate_upgrades_a_baseline_deployment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let token = MockTokenClient::new(&env, &env.register(MockToken, ()));
+    let gateway = crate::PaymentGatewayClient::new(&env, &env.register(PaymentGateway, ()));
+    let description = String::from_str(&env, "item");
+    env.as_contract(&gateway.address, || {
+        baseline(
             &env,
-            String::from_str(&env, "Hello"),
-            String::from_str(&env, "Dev"),
-        ]
+            &token.address,
+            &owner,
+            &merchant,
+            I256::from_i128(&env, PRICE),
+        )
+    });
+    assert!(gateway
+        .try_create_payment_link(&merchant, &PRICE, &description)
+        .is_err());
+
+    gateway.migrate(&owner);
+
+    assert!(gateway.get_merchant_record(&merchant).is_some());
+    assert_eq!(gateway.merchant_links(&merchant), vec![&env, 1u64]);
+    assert_eq!(gateway.merchant_plans(&merchant), vec![&env, 1u64]);
+    assert!(gateway.get_link_code(&1).is_some());
+    let (subscriber, id) = gateway.plan_subscribers(&1).get_unchecked(0);
+    assert_eq!(id, 2);
+    assert_eq!(gateway.plan_subscribers(&1).len(), 1);
+    assert_eq!(gateway.subscription_count(&subscriber), 2);
+    let sub = gateway.get_subscription(&subscriber, &2);
+    assert_eq!(sub.payer, subscriber);
+    assert_eq!(sub.amount, PRICE);
+    assert_eq!(sub.cycles, 2);
+    assert_eq!(sub.status, SubscriptionStatus::Active);
+    assert_eq!(
+        gateway.get_subscription(&subscriber, &1).status,
+        SubscriptionStatus::Canceled
     );
+    env.as_contract(&gateway.address, || {
+        let storage = env.storage().instance();
+        let links: Map<u64, PaymentLink> = storage.get(&PLINK).unwrap();
+        assert_eq!(links.get_unchecked(1).amount, PRICE);
+        assert_eq!(links.get_unchecked(1).description, description);
+        assert!(!storage.has(&symbol_short!("MERCH")));
+        assert!(!storage.has(&symbol_short!("SCTR")));
+        let version: u32 = storage.get(&migrate::SCHEMA).unwrap();
+        assert_eq!(version, migrate::SCHEMA_VERSION);
+    });
+    assert!(gateway.try_migrate(&owner).is_err());
+
+    // the upgraded deployment takes new links and payments
+    gateway.create_payment_link(&merchant, &PRICE, &description);
+    let payer = Address::generate(&env);
+    token.mint(&payer, &PRICE);
+    token.approve(&payer, &gateway.address, &PRICE);
+    gateway.process_payment(&payer, &2, &PRICE, &None, &None);
+    assert_eq!(token.balance(&merchant), PRICE);
+}
+
+#[test]
+fn migrate_rejects_amounts_that_do_not_fit() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let owner = Address::generate(&env);
+    let gateway = crate::PaymentGatewayClient::new(&env, &env.register(PaymentGateway, ()));
+    env.as_contract(&gateway.address, || {
+        let huge = I256::from_i128(&env, i128::MAX).add(&I256::from_i128(&env, 1));
+        let merchant = Address::generate(&env);
+        baseline(&env, &Address::generate(&env), &owner, &merchant, huge);
+    });
+    assert_eq!(
+        gateway.try_migrate(&owner).err(),
+        Some(Ok(rejected(Error::AmountOverflow)))
+    );
+}
+
+#[test]
+fn due_installment_is_charged_without_payer_auth() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let link = s.link(&merchant, 2 * PRICE);
+    s.gateway
+        .enable_installments(&merchant, &link, &2, &DAY, &0);
+    let payer = s.funded(2 * PRICE);
+    let plan = s.gateway.pay_in_installments(&payer, &link);
+    assert_eq!(s.token.balance(&merchant), PRICE);
+
+    s.advance(DAY as u64);
+    let keeper = Address::generate(&s.env);
+    s.env.mock_auths(&[MockAuth {
+        address: &keeper,
+        invoke: &MockAuthInvoke {
+            contract: &s.gateway.address,
+            fn_name: "charge_installment",
+            args: (&keeper, plan).into_val(&s.env),
+            sub_invokes: &[],
+        },
+    }]);
+    s.gateway.charge_installment(&keeper, &plan);
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE);
+    assert_eq!(s.token.balance(&payer), 0);
+}
+
+#[test]
+fn installment_late_fee_needs_past_due_and_uses_the_agreed_policy() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let link = s.link(&merchant, 3 * PRICE);
+    s.gateway
+        .enable_installments(&merchant, &link, &3, &DAY, &DAY);
+    s.gateway
+        .set_late_fee_policy(&merchant, &(PRICE / 10), &0, &0, &PRICE);
+    let payer = s.funded(4 * PRICE);
+    let plan = s.gateway.pay_in_installments(&payer, &link);
+    // raising the fee later does not reach plans already taken out
+    s.gateway
+        .set_late_fee_policy(&merchant, &PRICE, &0, &0, &PRICE);
+
+    s.advance(DAY as u64 + 1);
+    s.gateway.charge_installment(&merchant, &plan);
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE);
+
+    s.advance(DAY as u64 + 1);
+    s.gateway.mark_installment_past_due(&plan);
+    assert!(s.gateway.try_mark_installment_past_due(&plan).is_err());
+    s.gateway.charge_installment(&merchant, &plan);
+    assert_eq!(s.token.balance(&merchant), 3 * PRICE + PRICE / 10);
+}
+
+#[test]
+fn subscription_late_fee_is_only_charged_once_flagged_past_due() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let plan = s.plan(&merchant, PRICE, DAY);
+    s.gateway
+        .set_late_fee_policy(&merchant, &(PRICE / 10), &0, &0, &PRICE);
+    let payer = s.funded(4 * PRICE);
+    s.gateway.subscribe(&payer, &plan, &PRICE);
+
+    s.advance(DAY as u64 + 1);
+    s.gateway
+        .process_subscription_payment(&merchant, &payer, &1, &None);
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE);
+
+    s.advance(DAY as u64 + 1);
+    s.gateway.mark_subscription_past_due(&payer, &1);
+    s.gateway
+        .process_subscription_payment(&merchant, &payer, &1, &None);
+    assert_eq!(s.token.balance(&merchant), 3 * PRICE + PRICE / 10);
+}
+
+#[test]
+fn upheld_fraud_report_counts_against_refunds() {
+    let s = Setup::new();
+    s.gateway.set_merchant_bond(&s.owner, &PRICE, &0);
+    let merchant = s.funded(PRICE);
+    s.gateway.register_merchant(&merchant);
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+    let receipt_id = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    let arbiter = Address::generate(&s.env);
+    s.gateway.register_arbiter(&s.owner, &arbiter);
+
+    let report = s
+        .gateway
+        .file_fraud_report(&payer, &receipt_id, &PRICE, &symbol_short!("fake"));
+    s.gateway.assign_report_arbiter(&s.owner, &report, &arbiter);
+    let paid = s
+        .gateway
+        .adjudicate_report(&arbiter, &report, &true, &(PRICE / 2));
+    assert_eq!(paid, PRICE / 2);
+    assert_eq!(s.token.balance(&payer), PRICE / 2);
+    assert_eq!(s.gateway.get_receipt(&receipt_id).refunded, PRICE / 2);
+
+    s.token.approve(&merchant, &s.gateway.address, &PRICE);
+    assert!(s
+        .gateway
+        .try_refund_payment(&merchant, &receipt_id, &PRICE)
+        .is_err());
+    s.gateway
+        .refund_payment(&merchant, &receipt_id, &(PRICE / 2));
+    assert_eq!(s.token.balance(&payer), PRICE);
+}
+
+#[test]
+fn fraud_reports_are_filed_once_by_the_customer_and_can_be_dismissed() {
+    let s = Setup::new();
+    s.gateway.set_merchant_bond(&s.owner, &PRICE, &0);
+    let merchant = s.funded(PRICE);
+    s.gateway.register_merchant(&merchant);
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+    let receipt_id = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    let reason = symbol_short!("fake");
+
+    assert!(s
+        .gateway
+        .try_file_fraud_report(&merchant, &receipt_id, &PRICE, &reason)
+        .is_err());
+    assert!(s
+        .gateway
+        .try_file_fraud_report(&payer, &receipt_id, &(PRICE + 1), &reason)
+        .is_err());
+    let report = s
+        .gateway
+        .file_fraud_report(&payer, &receipt_id, &PRICE, &reason);
+    assert!(s
+        .gateway
+        .try_file_fraud_report(&payer, &receipt_id, &PRICE, &reason)
+        .is_err());
+    assert!(s
+        .gateway
+        .try_adjudicate_report(&payer, &report, &true, &PRICE)
+        .is_err());
+
+    s.gateway
+        .adjudicate_report(&s.owner, &report, &false, &PRICE);
+    assert_eq!(s.token.balance(&payer), 0);
+    s.gateway.deregister_merchant(&merchant);
+    assert_eq!(s.gateway.withdraw_bond(&merchant), PRICE);
+}
+
+#[test]
+fn bond_stays_locked_while_a_fraud_report_is_open() {
+    let s = Setup::new();
+    s.gateway.set_merchant_bond(&s.owner, &PRICE, &0);
+    let merchant = s.funded(PRICE);
+    s.gateway.register_merchant(&merchant);
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+    let receipt_id = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    let report = s
+        .gateway
+        .file_fraud_report(&payer, &receipt_id, &PRICE, &symbol_short!("fake"));
+    s.gateway.deregister_merchant(&merchant);
+
+    assert!(s.gateway.try_withdraw_bond(&merchant).is_err());
+    s.gateway
+        .adjudicate_report(&s.owner, &report, &true, &(PRICE / 4));
+    assert_eq!(s.gateway.withdraw_bond(&merchant), PRICE - PRICE / 4);
+}
+
+#[test]
+fn only_the_assigned_arbiter_rules_on_a_report_and_is_paid_for_it() {
+    let s = Setup::new();
+    s.gateway.set_merchant_bond(&s.owner, &PRICE, &0);
+    s.gateway.set_arbitration_fee(&s.owner, &1_000);
+    let merchant = s.funded(PRICE);
+    s.gateway.register_merchant(&merchant);
+    let payer = s.funded(2 * PRICE);
+    let link = s.link(&merchant, PRICE);
+    let first = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    let second = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    let arbiter = Address::generate(&s.env);
+    let other = Address::generate(&s.env);
+    s.gateway.register_arbiter(&s.owner, &arbiter);
+    s.gateway.register_arbiter(&s.owner, &other);
+
+    let report = s
+        .gateway
+        .file_fraud_report(&payer, &first, &PRICE, &symbol_short!("fake"));
+    assert!(s
+        .gateway
+        .try_assign_report_arbiter(&s.owner, &report, &payer)
+        .is_err());
+    s.gateway.assign_report_arbiter(&s.owner, &report, &arbiter);
+    assert!(s
+        .gateway
+        .try_adjudicate_report(&other, &report, &true, &PRICE)
+        .is_err());
+    let paid = s
+        .gateway
+        .adjudicate_report(&arbiter, &report, &true, &PRICE);
+    assert_eq!(paid, PRICE - PRICE / 10);
+    assert_eq!(s.token.balance(&arbiter), PRICE / 10);
+    assert_eq!(s.gateway.arbiter_cases(&arbiter), 1);
+    assert!(s
+        .gateway
+        .try_assign_report_arbiter(&s.owner, &report, &other)
+        .is_err());
+
+    let report = s
+        .gateway
+        .file_fraud_report(&payer, &second, &PRICE, &symbol_short!("fake"));
+    s.gateway.assign_report_arbiter(&s.owner, &report, &other);
+    assert_eq!(s.gateway.adjudicate_report(&other, &report, &false, &0), 0);
+    assert_eq!(s.gateway.arbiter_cases(&other), 1);
+    assert!(s
+        .gateway
+        .try_adjudicate_report(&s.owner, &report, &true, &PRICE)
+        .is_err());
+}
+
+#[test]
+fn beneficiary_cancelling_gift_early_charges_payer_exit_fee() {
+    let s = Setup::new();
+    s.env.mock_all_auths_allowing_non_root_auth();
+    let merchant = s.merchant();
+    s.token.approve(&merchant, &s.gateway.address, &(2 * PRICE));
+    let plan = s.plan(&merchant, PRICE, DAY);
+    s.gateway.set_commitment(&merchant, &plan, &6, &PRICE, &0);
+    let payer = s.funded(4 * PRICE);
+    let friend = Address::generate(&s.env);
+
+    let id = s.gateway.subscribe_for(&payer, &friend, &plan, &3);
+    assert!(s
+        .gateway
+        .try_set_commitment(&merchant, &plan, &6, &0, &0)
+        .is_err());
+
+    s.gateway.cancel_subscription(&friend, &id);
+    // the payer pays the exit fee and gets the two unstarted periods back
+    assert_eq!(s.token.balance(&payer), 2 * PRICE);
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE);
+}
+
+#[test]
+fn keeper_charges_subscription_without_payer_auth() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let plan = s.plan(&merchant, PRICE, DAY);
+    let payer = s.funded(2 * PRICE);
+    s.gateway.subscribe(&payer, &plan, &PRICE);
+    let keeper = Address::generate(&s.env);
+    s.gateway.add_keeper(&s.owner, &keeper);
+
+    s.advance(DAY as u64);
+    let memo: Option<String> = None;
+    s.env.mock_auths(&[MockAuth {
+        address: &keeper,
+        invoke: &MockAuthInvoke {
+            contract: &s.gateway.address,
+            fn_name: "process_subscription_payment",
+            args: (&keeper, &payer, 1u64, memo.clone()).into_val(&s.env),
+            sub_invokes: &[],
+        },
+    }]);
+    s.gateway
+        .process_subscription_payment(&keeper, &payer, &1, &memo);
+    assert_eq!(s.token.balance(&merchant), 2 * PRICE);
+    assert_eq!(s.token.balance(&payer), 0);
+}
+
+#[test]
+fn checkout_charges_the_quoted_coupon_price() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let link = s.link(&merchant, PRICE);
+    let payer = s.funded(PRICE);
+    let code = Some(symbol_short!("SPRING"));
+    s.gateway
+        .set_coupon(&merchant, &symbol_short!("SPRING"), &2_500);
+    assert!(s
+        .gateway
+        .try_create_checkout_session(&link, &payer, &u64::MAX, &code)
+        .is_err());
+
+    let session = s
+        .gateway
+        .create_checkout_session(&link, &payer, &600, &code);
+    s.gateway
+        .set_coupon(&merchant, &symbol_short!("SPRING"), &5_000);
+    assert!(s.gateway.try_complete_checkout(&session).is_err());
+
+    s.gateway
+        .set_coupon(&merchant, &symbol_short!("SPRING"), &2_500);
+    s.gateway.complete_checkout(&session);
+    assert_eq!(s.token.balance(&merchant), PRICE * 3 / 4);
+}
+
+#[test]
+fn credit_purchase_earns_no_cashback() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    s.token.approve(&merchant, &s.gateway.address, &PRICE);
+    s.gateway
+        .start_cashback(&merchant, &1_000, &0, &(10 * DAY as u64), &PRICE);
+    let payer = s.funded(PRICE);
+    let link = s.link(&merchant, PRICE);
+
+    let receipt_id = s
+        .gateway
+        .process_payment(&payer, &link, &PRICE, &None, &None);
+    assert_eq!(s.token.balance(&payer), PRICE / 10);
+
+    s.gateway.refund_to_credit(&merchant, &receipt_id, &PRICE);
+    s.gateway.pay_with_credit(&payer, &link, &PRICE);
+    assert_eq!(s.token.balance(&payer), PRICE / 10);
+    assert_eq!(s.gateway.store_credit(&payer, &merchant), 0);
+}
+
+#[test]
+fn seats_are_locked_while_gifted_periods_remain() {
+    let s = Setup::new();
+    s.env.mock_all_auths_allowing_non_root_auth();
+    let merchant = s.merchant();
+    let plan = s.plan(&merchant, PRICE, DAY);
+    let payer = s.funded(4 * PRICE);
+    let friend = Address::generate(&s.env);
+
+    let id = s.gateway.subscribe_for(&payer, &friend, &plan, &2);
+    assert!(s.gateway.try_set_seats(&friend, &id, &3).is_err());
+
+    s.advance(DAY as u64);
+    s.gateway
+        .process_subscription_payment(&merchant, &friend, &id, &None);
+    // the last gifted period has started; seats can change and the payer covers them
+    s.gateway.set_seats(&friend, &id, &2);
+    assert_eq!(s.token.balance(&merchant), 3 * PRICE);
+}
+
+#[test]
+fn seat_totals_that_overflow_are_rejected() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let price = i128::MAX / 2;
+    let plan = s.plan(&merchant, price, DAY);
+    let payer = s.funded(price);
+    s.gateway.subscribe(&payer, &plan, &price);
+
+    assert_eq!(
+        s.gateway.try_set_seats(&payer, &1, &3).err(),
+        Some(Ok(rejected(Error::AmountOverflow)))
+    );
+}
+
+#[test]
+fn deposits_count_against_the_volume_breaker() {
+    let s = Setup::new();
+    s.gateway
+        .set_volume_limits(&s.owner, &None, &Some(3 * PRICE));
+    let merchant = s.merchant();
+    let plan = s.plan(&merchant, PRICE, DAY);
+    let payer = s.funded(4 * PRICE);
+
+    s.gateway.subscribe(&payer, &plan, &PRICE);
+    s.gateway
+        .deposit_for_subscription(&payer, &payer, &1, &(2 * PRICE));
+    assert_eq!(s.gateway.current_volume().1, 3 * PRICE);
+    assert!(s
+        .gateway
+        .try_deposit_for_subscription(&payer, &payer, &1, &PRICE)
+        .is_err());
+}
+
+#[test]
+fn escrow_funding_counts_against_spending_limit() {
+    let s = Setup::new();
+    let merchant = s.merchant();
+    let payer = s.funded(3 * PRICE);
+    s.gateway
+        .set_spending_limit(&payer, &(2 * PRICE), &LimitWindow::Daily);
+    s.advance(DAY as u64);
+    let (start, end) = (DAY as u64, 2 * DAY as u64);
+
+    s.gateway
+        .create_stream(&payer, &merchant, &(2 * PRICE), &start, &end);
+    assert_eq!(s.gateway.remaining_budget(&payer), Some(0));
+    assert!(s
+        .gateway
+        .try_create_stream(&payer, &merchant, &PRICE, &start, &end)
+        .is_err());
+}
+
+#[test]
+fn gifting_shares_the_subscribe_rate_limit() {
+    let s = Setup::new();
+    s.gateway
+        .set_rate_limit(&s.owner, &RateAction::Subscribe, &1, &(DAY as u64));
+    let merchant = s.merchant();
+    let plan = s.plan(&merchant, PRICE, DAY);
+    let payer = s.funded(2 * PRICE);
+
+    s.gateway
+        .subscribe_for(&payer, &Address::generate(&s.env), &plan, &1);
+    assert!(s
+        .gateway
+        .try_subscribe_for(&payer, &Address::generate(&s.env), &plan, &1)
+        .is_err());
+    // the count starts over with the next window
+    s.advance(DAY as u64);
+    s.gateway
+        .subscribe_for(&payer, &Address::generate(&s.env), &plan, &1);
+}
+
+#[test]
+fn removed_admins_no_longer_count_towards_a_proposal() {
+    let s = Setup::new();
+    let [a, b, c] = [0; 3].map(|_| Address::generate(&s.env));
+    s.gateway
+        .configure_multisig(&s.owner, &vec![&s.env, a.clone(), b.clone(), c.clone()], &2);
+    assert!(s.gateway.try_set_paused(&s.owner, &true).is_err());
+    let treasury = Address::generate(&s.env);
+    let fee = s.gateway.propose(&a, &AdminAction::SetFee(100, treasury));
+    let drop_a = s.gateway.propose(
+        &b,
+        &AdminAction::SetAdmins(vec![&s.env, b.clone(), c.clone()], 2),
+    );
+    s.gateway.approve(&c, &drop_a);
+
+    // a's approval lapsed with its seat, so c alone does not reach the threshold
+    s.gateway.approve(&c, &fee);
+    assert_eq!(s.gateway.get_fee_bps(), 0);
+    s.gateway.approve(&b, &fee);
+    assert_eq!(s.gateway.get_fee_bps(), 100);
+}
+
+#[test]
+fn idle_funds_earn_yield_and_come_back_for_payouts() {
+    let s = Setup::new();
+    s.gateway.set_merchant_bond(&s.owner, &PRICE, &0);
+    let merchant = s.funded(PRICE);
+    s.gateway.register_merchant(&merchant);
+    let pool = MockPoolClient::new(&s.env, &s.env.register(MockPool, (&s.token.address,)));
+    assert!(s
+        .gateway
+        .try_set_yield_config(&merchant, &pool.address, &2_000, &0)
+        .is_err());
+    assert!(s
+        .gateway
+        .try_set_yield_config(&s.owner, &pool.address, &10_001, &0)
+        .is_err());
+    s.gateway
+        .set_yield_config(&s.owner, &pool.address, &2_000, &0);
+
+    // a fifth of the bond stays liquid
+    assert_eq!(s.gateway.supply_idle(), PRICE - PRICE / 5);
+    assert_eq!(s.token.balance(&s.gateway.address), PRICE / 5);
+    assert_eq!(s.gateway.supply_idle(), 0);
+    assert_eq!(s.gateway.rescuable_balance(&s.token.address), 0);
+
+    s.token.mint(&pool.address, &(PRICE / 10));
+    pool.accrue(&s.gateway.address, &(PRICE / 10));
+    assert_eq!(s.gateway.harvest_yield(), PRICE / 10);
+    assert_eq!(s.gateway.accrued_fees(), PRICE / 10);
+
+    // only as much principal as the bond needs is pulled back; the rest still backs the fees
+    s.gateway.deregister_merchant(&merchant);
+    assert_eq!(s.gateway.withdraw_bond(&merchant), PRICE);
+    assert_eq!(s.token.balance(&merchant), PRICE);
+    assert_eq!(s.gateway.supplied(), PRICE / 10);
+}
+
+#[test]
+fn rescue_sweeps_only_stranded_tokens() {
+    let s = Setup::new();
+    s.gateway.set_merchant_bond(&s.owner, &PRICE, &0);
+    let merchant = s.funded(PRICE);
+    s.gateway.register_merchant(&merchant);
+    let stray = MockTokenClient::new(&s.env, &s.env.register(MockToken, ()));
+    stray.mint(&s.gateway.address, &PRICE);
+    s.token.mint(&s.gateway.address, &(PRICE / 2));
+    let to = Address::generate(&s.env);
+
+    // the bond is owed to the merchant; only what was sent by mistake can leave
+    assert_eq!(s.gateway.rescuable_balance(&s.token.address), PRICE / 2);
+    assert!(s
+        .gateway
+        .try_rescue_tokens(&s.owner, &s.token.address, &to, &PRICE)
+        .is_err());
+    assert!(s
+        .gateway
+        .try_rescue_tokens(&merchant, &s.token.address, &to, &(PRICE / 2))
+        .is_err());
+    s.gateway
+        .rescue_tokens(&s.owner, &s.token.address, &to, &(PRICE / 2));
+    assert_eq!(s.token.balance(&to), PRICE / 2);
+    assert_eq!(s.token.balance(&s.gateway.address), PRICE);
+
+    // with a timelock set the sweep waits out the delay
+    s.gateway.set_timelock_delay(&s.owner, &(DAY as u64));
+    s.gateway
+        .rescue_tokens(&s.owner, &stray.address, &to, &PRICE);
+    assert_eq!(stray.balance(&to), 0);
+    s.advance(DAY as u64);
+    s.gateway.execute_op(&1);
+    assert_eq!(stray.balance(&to), PRICE);
+}
+
+#[test]
+fn fee_increase_waits_out_the_timelock() {
+    let s = Setup::new();
+    let treasury = Address::generate(&s.env);
+    s.gateway.set_fee(&s.owner, &100, &treasury);
+    s.gateway.set_timelock_delay(&s.owner, &(DAY as u64));
+
+    assert!(s.gateway.try_set_fee(&s.owner, &200, &treasury).is_err());
+    assert!(s.gateway.try_set_timelock_delay(&s.owner, &0).is_err());
+    s.gateway.set_fee(&s.owner, &50, &treasury);
+    let op = s
+        .gateway
+        .schedule_op(&s.owner, &AdminAction::SetFee(200, treasury.clone()));
+    assert!(s.gateway.try_execute_op(&op).is_err());
+    s.advance(DAY as u64);
+    s.gateway.execute_op(&op);
+    assert_eq!(s.gateway.get_fee_bps(), 200);
+    assert!(s.gateway.try_execute_op(&op).is_err());
+
+    let op = s
+        .gateway
+        .schedule_op(&s.owner, &AdminAction::SetFee(300, treasury));
+    s.gateway.cancel_op(&s.owner, &op);
+    s.advance(DAY as u64);
+    assert!(s.gateway.try_execute_op(&op).is_err());
+    assert_eq!(s.gateway.get_fee_bps(), 200);
+}
+
+#[test]
+fn timelocked_proposal_rechecks_approvals_when_it_comes_due() {
+    let s = Setup::new();
+    s.gateway.set_timelock_delay(&s.owner, &(DAY as u64));
+    let [a, b, c] = [0; 3].map(|_| Address::generate(&s.env));
+    s.gateway
+        .configure_multisig(&s.owner, &vec![&s.env, a.clone(), b.clone(), c.clone()], &2);
+    let token = Address::generate(&s.env);
+    let swap = s.gateway.propose(&a, &AdminAction::SetToken(token));
+    s.gateway.approve(&b, &swap);
+    let op = 1;
+    let drop_a = s.gateway.propose(
+        &b,
+        &AdminAction::SetAdmins(vec![&s.env, b.clone(), c.clone()], 2),
+    );
+    s.gateway.approve(&c, &drop_a);
+
+    s.advance(DAY as u64);
+    assert!(s.gateway.try_execute_op(&op).is_err());
+    s.gateway.cancel_op(&c, &op);
+}
+
+#[test]
+fn multisig_cannot_execute_a_governed_change_proposed_before_the_governor() {
+    let s = Setup::new();
+    let [a, b] = [0; 2].map(|_| Address::generate(&s.env));
+    s.gateway
+        .configure_multisig(&s.owner, &vec![&s.env, a.clone(), b.clone()], &2);
+    let treasury = Address::generate(&s.env);
+    let fee = s.gateway.propose(&a, &AdminAction::SetFee(100, treasury));
+    let governor = Address::generate(&s.env);
+    let hand_over = s
+        .gateway
+        .propose(&a, &AdminAction::SetGovernor(Some(governor.clone())));
+    s.gateway.approve(&b, &hand_over);
+    assert_eq!(s.gateway.get_governor(), Some(governor));
+
+    assert!(s.gateway.try_approve(&b, &fee).is_err());
+    assert_eq!(s.gateway.get_fee_bps(), 0);
+}
+
+#[test]
+fn governor_takes_over_the_insurance_share() {
+    let s = Setup::new();
+    s.gateway.set_insurance(&s.owner, &1_000, &PRICE);
+    let governor = Address::generate(&s.env);
+    s.gateway.set_governor(&s.owner, &governor);
+
+    assert!(s
+        .gateway
+        .try_set_insurance(&s.owner, &5_000, &PRICE)
+        .is_err());
+    s.gateway
+        .execute_param_change(&AdminAction::SetInsurance(5_000, PRICE));
+    assert!(s.emitted((symbol_short!("InsCfg"),), (5_000u32, PRICE)));
+}
+
+fn rejected(error: Error) -> soroban_sdk::Error {
+    soroban_sdk::Error::from_contract_error(error as u32)
+}
+
+// A second token priced like the gateway's, with the oracle and router wired up.
+fn conversion(
+    s: &Setup,
+) -> (
+    MockTokenClient<'static>,
+    MockOracleClient<'static>,
+    MockRouterClient<'static>,
+) {
+    let other = MockTokenClient::new(&s.env, &s.env.register(MockToken, ()));
+    let oracle = MockOracleClient::new(&s.env, &s.env.register(MockOracle, ()));
+    let router = MockRouterClient::new(&s.env, &s.env.register(MockRouter, ()));
+    let now = s.env.ledger().timestamp();
+    oracle.set_price(&s.token.address, &1_000, &now);
+    oracle.set_price(&other.address, &1_000, &now);
+    s.gateway
+        .set_conversion_config(&s.owner, &router.address, &oracle.address, &300, &100);
+    (other, oracle, router)
+}
+
+#[test]
+fn swap_payment_settles_and_returns_surplus() {
+    let s = Setup::new();
+    s.env.mock_all_auths_allowing_non_root_auth();
+    let merchant = s.merchant();
+    let link = s.link(&merchant, PRICE);
+    let (other, _, _) = conversion(&s);
+    let payer = Address::generate(&s.env);
+    other.mint(&payer, &(2 * PRICE));
+
+    let max_in = PRICE + PRICE / 10;
+    let receipt_id =
+        s.gateway
+            .pay_link_with_swap(&payer, &link, &PRICE, &other.address, &max_in, &PRICE);
+    assert_eq!(s.gateway.get_receipt(&receipt_id).amount, PRICE);
+    assert_eq!(s.token.balance(&merchant), PRICE);
+    assert_eq!(s.token.balance(&payer), PRICE / 10);
+    assert_eq!(other.balance(&payer), 2 * PRICE - max_in);
+}
+
+#[test]
+fn swap_payment_rejects_slippage_stale_prices_and_deviation() {
+    let s = Setup::new();
+    s.env.mock_all_auths_allowing_non_root_auth();
+    let merchant = s.merchant();
+    let link = s.link(&merchant, PRICE);
+    let (other, oracle, router) = conversion(&s);
+    let payer = Address::generate(&s.env);
+    other.mint(&payer, &(4 * PRICE));
+    let pay = |max_in: i128, min_out: i128| match s.gateway.try_pay_link_with_swap(
+        &payer,
+        &link,
+        &PRICE,
+        &other.address,
+        &max_in,
+        &min_out,
+    ) {
+        Err(Ok(e)) => Some(e),
+        _ => None,
+    };
+
+    assert_eq!(
+        pay(PRICE, PRICE + 1),
+        Some(rejected(Error::SlippageExceeded))
+    );
+    assert_eq!(pay(PRICE - 1, 0), Some(rejected(Error::SlippageExceeded)));
+
+    // the router pays 5% under the oracle rate, past the 1% tolerance
+    router.set_rate(&9_500);
+    assert_eq!(pay(2 * PRICE, PRICE), Some(rejected(Error::PriceDeviation)));
+    router.set_rate(&10_000);
+
+    s.advance(301);
+    assert_eq!(pay(PRICE, PRICE), Some(rejected(Error::StalePrice)));
+    oracle.set_price(&s.token.address, &1_000, &s.env.ledger().timestamp());
+    assert_eq!(pay(PRICE, PRICE), Some(rejected(Error::StalePrice)));
+    oracle.set_price(&other.address, &1_000, &s.env.ledger().timestamp());
+    assert_eq!(pay(PRICE, PRICE), None);
 }
@@ -0,0 +1,306 @@
+use soroban_sdk::{
+    contract, contractimpl, symbol_short,
+    testutils::{Address as _, Events, Ledger},
+    Address, Env, IntoVal, Map, String, Symbol, TryFromVal, Val, Vec,
+};
+
+use crate::{OracleAsset, PaymentGateway, PaymentGatewayClient, PriceData, LCTR, PCTR};
+
+const BALS: Symbol = symbol_short!("BALS");
+const ALLOW: Symbol = symbol_short!("ALLOW");
+const PRICES: Symbol = symbol_short!("PRICES");
+const RATE: Symbol = symbol_short!("RATE");
+
+// Minimal token: just enough of the SEP-41 surface (`decimals`, `balance`, `transfer`,
+// `transfer_from`, `approve`) plus an unauthenticated `mint`.
+#[contract]
+pub struct MockToken;
+
+#[contractimpl]
+impl MockToken {
+    pub fn decimals(_env: Env) -> u32 {
+        7
+    }
+
+    pub fn mint(env: Env, to: Address, amount: i128) {
+        let balance = Self::balance(env.clone(), to.clone());
+        Self::set_balance(&env, &to, &(balance + amount));
+    }
+
+    pub fn balance(env: Env, id: Address) -> i128 {
+        let balances: Map<Address, i128> = env
+            .storage()
+            .instance()
+            .get(&BALS)
+            .unwrap_or(Map::new(&env));
+        balances.get(id).unwrap_or(0)
+    }
+
+    pub fn allowance(env: Env, from: Address, spender: Address) -> i128 {
+        let allowances: Map<(Address, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&ALLOW)
+            .unwrap_or(Map::new(&env));
+        allowances.get((from, spender)).unwrap_or(0)
+    }
+
+    pub fn approve(env: Env, from: Address, spender: Address, amount: i128) {
+        from.require_auth();
+        Self::set_allowance(&env, from, spender, amount);
+    }
+
+    pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+        Self::move_balance(&env, &from, &to, &amount);
+    }
+
+    pub fn transfer_from(env: Env, spender: Address, from: Address, to: Address, amount: i128) {
+        spender.require_auth();
+        let allowance = Self::allowance(env.clone(), from.clone(), spender.clone());
+        assert!(allowance >= amount, "insufficient allowance");
+        Self::set_allowance(&env, from.clone(), spender, allowance - amount);
+        Self::move_balance(&env, &from, &to, &amount);
+    }
+
+    fn move_balance(env: &Env, from: &Address, to: &Address, amount: &i128) {
+        let from_balance = Self::balance(env.clone(), from.clone());
+        assert!(from_balance >= *amount, "insufficient balance");
+        Self::set_balance(env, from, &(from_balance - amount));
+        let to_balance = Self::balance(env.clone(), to.clone());
+        Self::set_balance(env, to, &(to_balance + amount));
+    }
+
+    fn set_allowance(env: &Env, from: Address, spender: Address, amount: i128) {
+        let mut allowances: Map<(Address, Address), i128> = env
+            .storage()
+            .instance()
+            .get(&ALLOW)
+            .unwrap_or(Map::new(env));
+        allowances.set((from, spender), amount);
+        env.storage().instance().set(&ALLOW, &allowances);
+    }
+
+    fn set_balance(env: &Env, id: &Address, amount: &i128) {
+        let mut balances: Map<Address, i128> =
+            env.storage().instance().get(&BALS).unwrap_or(Map::new(env));
+        balances.set(id.clone(), *amount);
+        env.storage().instance().set(&BALS, &balances);
+    }
+}
+
+// SEP-40 `lastprice` over prices set by hand.
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_price(env: Env, asset: Address, price: i128, timestamp: u64) {
+        let mut prices: Map<Address, PriceData> = env
+            .storage()
+            .instance()
+            .get(&PRICES)
+            .unwrap_or(Map::new(&env));
+        prices.set(asset, PriceData { price, timestamp });
+        env.storage().instance().set(&PRICES, &prices);
+    }
+
+    pub fn lastprice(env: Env, asset: OracleAsset) -> Option<PriceData> {
+        let prices: Map<Address, PriceData> = env
+            .storage()
+            .instance()
+            .get(&PRICES)
+            .unwrap_or(Map::new(&env));
+        match asset {
+            OracleAsset::Stellar(token) => prices.get(token),
+            OracleAsset::Other(_) => None,
+        }
+    }
+}
+
+// Swaps `MockToken`s at a fixed rate, minting the output. It ignores `min_out`, like a
+// router that has been sandwiched or is simply hostile.
+#[contract]
+pub struct MockRouter;
+
+#[contractimpl]
+impl MockRouter {
+    // Output per 10_000 units of input.
+    pub fn set_rate(env: Env, rate_bps: i128) {
+        env.storage().instance().set(&RATE, &rate_bps);
+    }
+
+    pub fn swap(
+        env: Env,
+        from: Address,
+        token_in: Address,
+        token_out: Address,
+        amount_in: i128,
+        _min_out: i128,
+    ) {
+        let rate: i128 = env.storage().instance().get(&RATE).unwrap_or(10_000);
+        MockTokenClient::new(&env, &token_in).transfer(
+            &from,
+            &env.current_contract_address(),
+            &amount_in,
+        );
+        MockTokenClient::new(&env, &token_out).mint(&from, &(amount_in * rate / 10_000));
+    }
+}
+
+// In its own module, as its `balance` would clash with `MockToken`'s generated items.
+pub use pool::{MockPool, MockPoolClient};
+
+mod pool {
+    use soroban_sdk::{contract, contractimpl, symbol_short, Address, Env, Map, Symbol};
+
+    use super::{MockTokenClient, BALS};
+
+    const POOLTKN: Symbol = symbol_short!("POOLTKN");
+
+    // Lending pool holding `MockToken` deposits. Interest is whatever `accrue` credits; the
+    // tokens backing it have to be minted to the pool separately.
+    #[contract]
+    pub struct MockPool;
+
+    #[contractimpl]
+    impl MockPool {
+        pub fn __constructor(env: Env, token: Address) {
+            env.storage().instance().set(&POOLTKN, &token);
+        }
+
+        pub fn supply(env: Env, from: Address, amount: i128) {
+            from.require_auth();
+            Self::token(&env).transfer(&from, &env.current_contract_address(), &amount);
+            Self::accrue(env.clone(), from.clone(), amount);
+        }
+
+        pub fn withdraw(env: Env, to: Address, amount: i128) {
+            to.require_auth();
+            let balance = Self::balance(env.clone(), to.clone());
+            assert!(balance >= amount, "insufficient balance");
+            Self::set_balance(&env, &to, &(balance - amount));
+            Self::token(&env).transfer(&env.current_contract_address(), &to, &amount);
+        }
+
+        pub fn balance(env: Env, id: Address) -> i128 {
+            let balances: Map<Address, i128> = env
+                .storage()
+                .instance()
+                .get(&BALS)
+                .unwrap_or(Map::new(&env));
+            balances.get(id).unwrap_or(0)
+        }
+
+        pub fn accrue(env: Env, id: Address, amount: i128) {
+            let balance = Self::balance(env.clone(), id.clone());
+            Self::set_balance(&env, &id, &(balance + amount));
+        }
+
+        fn token(env: &Env) -> MockTokenClient<'_> {
+            MockTokenClient::new(env, &env.storage().instance().get(&POOLTKN).unwrap())
+        }
+
+        fn set_balance(env: &Env, id: &Address, amount: &i128) {
+            let mut balances: Map<Address, i128> =
+                env.storage().instance().get(&BALS).unwrap_or(Map::new(env));
+            balances.set(id.clone(), *amount);
+            env.storage().instance().set(&BALS, &balances);
+        }
+    }
+}
+
+// A gateway initialized against a fresh `MockToken`, with all auths mocked.
+pub struct Setup<'a> {
+    pub env: Env,
+    pub owner: Address,
+    pub gateway: PaymentGatewayClient<'a>,
+    pub token: MockTokenClient<'a>,
+}
+
+impl Setup<'_> {
+    pub fn new() -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+        let owner = Address::generate(&env);
+        let token = MockTokenClient::new(&env, &env.register(MockToken, ()));
+        let gateway = PaymentGatewayClient::new(&env, &env.register(PaymentGateway, ()));
+        gateway.init(&owner, &token.address);
+        Setup {
+            env,
+            owner,
+            gateway,
+            token,
+        }
+    }
+
+    pub fn merchant(&self) -> Address {
+        let merchant = Address::generate(&self.env);
+        self.gateway.add_merchant(&self.owner, &merchant);
+        merchant
+    }
+
+    // A new account holding `amount` and allowing the gateway to pull all of it.
+    pub fn funded(&self, amount: i128) -> Address {
+        let account = Address::generate(&self.env);
+        self.token.mint(&account, &amount);
+        self.token.approve(&account, &self.gateway.address, &amount);
+        account
+    }
+
+    // Creates a link for `merchant` and returns its id.
+    pub fn link(&self, merchant: &Address, amount: i128) -> u64 {
+        self.gateway
+            .create_payment_link(merchant, &amount, &String::from_str(&self.env, "item"));
+        self.env.as_contract(&self.gateway.address, || {
+            self.env.storage().instance().get(&LCTR).unwrap()
+        })
+    }
+
+    // Creates a subscription plan for `merchant` and returns its id.
+    pub fn plan(&self, merchant: &Address, amount: i128, interval: u32) -> u64 {
+        self.gateway.create_subscription_plan(
+            merchant,
+            &amount,
+            &interval,
+            &String::from_str(&self.env, "plan"),
+        );
+        self.env.as_contract(&self.gateway.address, || {
+            self.env.storage().instance().get(&PCTR).unwrap()
+        })
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.env.ledger().with_mut(|l| {
+            l.timestamp += seconds;
+            l.sequence_number += 1;
+        });
+    }
+
+    // Whether the last invocation emitted `data` under `topics` from the gateway. Gateway
+    // events carry a sequence number alongside the payload; only the payload is compared.
+    pub fn emitted<T, D>(&self, topics: T, data: D) -> bool
+    where
+        T: IntoVal<Env, Vec<Val>>,
+        D: IntoVal<Env, Val>,
+    {
+        let env = &self.env;
+        let topics: Vec<Val> = topics.into_val(env);
+        let expected = Vec::from_array(env, [data.into_val(env)]);
+        env.events().all().iter().any(|(contract, t, d)| {
+            if contract != self.gateway.address || t != topics {
+                return false;
+            }
+            match <(u64, Val)>::try_from_val(env, &d) {
+                Ok((_, payload)) => Vec::from_array(env, [payload]) == expected,
+                Err(_) => false,
+            }
+        })
+    }
+}
+
+impl Default for Setup<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
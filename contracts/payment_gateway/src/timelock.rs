@@ -0,0 +1,163 @@
+use soroban_sdk::{contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol};
+
+use crate::{AdminAction, PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, TOKEN};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingOp {
+    action: AdminAction,
+    eta: u64,
+    // multisig proposal that approved it, if any
+    proposal: Option<u32>,
+}
+
+const TLDELAY: Symbol = symbol_short!("TLDELAY");
+const OPCTR: Symbol = symbol_short!("OPCTR");
+const TLOPS: Symbol = symbol_short!("TLOPS");
+
+#[contractimpl]
+impl PaymentGateway {
+    // Raising the delay applies at once; lowering it has to wait out the current one.
+    pub fn set_timelock_delay(env: Env, invoker: Address, seconds: u64) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        Self::assert_not_timelocked(&env, &AdminAction::SetTimelockDelay(seconds));
+        Self::store_delay(&env, seconds);
+    }
+
+    pub fn get_timelock_delay(env: Env) -> u64 {
+        env.storage().instance().get(&TLDELAY).unwrap_or(0)
+    }
+
+    pub fn schedule_op(env: Env, invoker: Address, action: AdminAction) -> u32 {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        Self::assert_not_governed(&env, &action);
+        Self::queue_op(&env, action, None)
+    }
+
+    // Anyone may run an operation once its delay has passed. One approved by the multisig still
+    // needs its approvals to reach the threshold among the admins at that point, and must not
+    // touch a parameter a governor has taken over in the meantime.
+    pub fn execute_op(env: Env, op_id: u32) {
+        let op = Self::get_op(env.clone(), op_id);
+        assert!(env.ledger().timestamp() >= op.eta, "timelock active");
+        if let Some(proposal_id) = op.proposal {
+            Self::assert_quorum(&env, proposal_id);
+            Self::assert_not_governed(&env, &op.action);
+        }
+        Self::remove_op(&env, op_id);
+        Self::emit(&env, (symbol_short!("OpExec"), op_id), op.action.clone());
+        Self::run_action(&env, op.action);
+    }
+
+    // The owner, or any multisig admin once one is configured, may call off a pending operation.
+    pub fn cancel_op(env: Env, invoker: Address, op_id: u32) {
+        let (admins, _) = Self::get_admins(env.clone());
+        if admins.is_empty() {
+            Self::only_owner(&env, &invoker);
+        } else {
+            invoker.require_auth();
+            assert!(admins.contains(&invoker), "not admin");
+        }
+        Self::get_op(env.clone(), op_id);
+        Self::remove_op(&env, op_id);
+        Self::emit(&env, (symbol_short!("OpCancel"), op_id), &invoker);
+    }
+
+    pub fn get_op(env: Env, op_id: u32) -> PendingOp {
+        let ops: Map<u32, PendingOp> = env
+            .storage()
+            .instance()
+            .get(&TLOPS)
+            .unwrap_or(Map::new(&env));
+        ops.get(op_id).expect("no op")
+    }
+
+    // Sensitive actions are queued behind the delay; the rest take effect immediately.
+    pub(crate) fn run_or_schedule(env: &Env, action: AdminAction, proposal: Option<u32>) {
+        if Self::is_timelocked(env, &action) {
+            Self::queue_op(env, action, proposal);
+        } else {
+            Self::run_action(env, action);
+        }
+    }
+
+    pub(crate) fn has_pending_ops(env: &Env) -> bool {
+        let ops: Map<u32, PendingOp> = env
+            .storage()
+            .instance()
+            .get(&TLOPS)
+            .unwrap_or(Map::new(env));
+        !ops.is_empty()
+    }
+
+    pub(crate) fn assert_not_timelocked(env: &Env, action: &AdminAction) {
+        assert!(!Self::is_timelocked(env, action), "timelocked");
+    }
+
+    pub(crate) fn set_token(env: &Env, token: Address) {
+        env.storage().instance().set(&TOKEN, &token);
+        Self::emit(env, (symbol_short!("TokenSet"),), token);
+    }
+
+    pub(crate) fn store_delay(env: &Env, seconds: u64) {
+        env.storage().instance().set(&TLDELAY, &seconds);
+        Self::emit(env, (symbol_short!("TlDelay"),), seconds);
+    }
+
+    // Token, governor and code changes, token rescues, fee increases, removing a merchant the
+    // gateway still holds funds for, and shortening the delay itself.
+    fn is_timelocked(env: &Env, action: &AdminAction) -> bool {
+        let delay = Self::get_timelock_delay(env.clone());
+        if delay == 0 {
+            return false;
+        }
+        match action {
+            AdminAction::SetToken(_)
+            | AdminAction::Upgrade(_)
+            | AdminAction::RescueTokens(..)
+            | AdminAction::SetGovernor(_) => true,
+            AdminAction::SetFee(fee_bps, _) => *fee_bps > Self::get_fee_bps(env.clone()),
+            AdminAction::RemoveMerchant(merchant) => {
+                Self::merchant_balance(env.clone(), merchant.clone()) > 0
+            }
+            AdminAction::SetTimelockDelay(seconds) => *seconds < delay,
+            AdminAction::SetAdmins(..)
+            | AdminAction::SetFeeShares(_)
+            | AdminAction::SetPaused(_)
+            | AdminAction::SetInsurance(..) => false,
+        }
+    }
+
+    fn queue_op(env: &Env, action: AdminAction, proposal: Option<u32>) -> u32 {
+        let ctr = Self::next_id(env, &OPCTR);
+        let eta = env.ledger().timestamp() + Self::get_timelock_delay(env.clone());
+        let mut ops: Map<u32, PendingOp> = env
+            .storage()
+            .instance()
+            .get(&TLOPS)
+            .unwrap_or(Map::new(env));
+        ops.set(
+            ctr,
+            PendingOp {
+                action: action.clone(),
+                eta,
+                proposal,
+            },
+        );
+        env.storage().instance().set(&TLOPS, &ops);
+        Self::emit(env, (symbol_short!("OpSched"), ctr), (action, eta));
+        ctr
+    }
+
+    fn remove_op(env: &Env, op_id: u32) {
+        let mut ops: Map<u32, PendingOp> = env
+            .storage()
+            .instance()
+            .get(&TLOPS)
+            .unwrap_or(Map::new(env));
+        ops.remove(op_id);
+        env.storage().instance().set(&TLOPS, &ops);
+    }
+}
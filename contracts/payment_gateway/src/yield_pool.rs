@@ -0,0 +1,198 @@
+use soroban_sdk::{
+    auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation},
+    contractimpl, contracttype, symbol_short, vec, Address, Env, IntoVal, Symbol, Vec,
+};
+
+use crate::{PaymentGateway, PaymentGatewayArgs, PaymentGatewayClient, TOKEN};
+
+// Idle funds are supplied to a lending pool exposing `supply(from, amount)`,
+// `withdraw(to, amount)` and `balance(id) -> i128`. At least `reserve_bps` of everything the
+// gateway holds stays liquid; harvested yield goes `merchant_bps` to custodial merchants, pro
+// rata to their balances, and the rest to protocol fees.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct YieldConfig {
+    pool: Address,
+    reserve_bps: u32,
+    merchant_bps: u32,
+}
+
+const YIELDCFG: Symbol = symbol_short!("YIELDCFG");
+// principal currently supplied to the pool
+const YSUPP: Symbol = symbol_short!("YSUPP");
+
+#[contractimpl]
+impl PaymentGateway {
+    // The pool can only change once everything has been withdrawn from the previous one.
+    pub fn set_yield_config(
+        env: Env,
+        invoker: Address,
+        pool: Address,
+        reserve_bps: u32,
+        merchant_bps: u32,
+    ) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        assert!(reserve_bps <= 10_000, "bps<=10000");
+        assert!(merchant_bps <= 10_000, "bps<=10000");
+        if let Some(cfg) = Self::get_yield_config(env.clone()) {
+            assert!(
+                cfg.pool == pool || Self::supplied(env.clone()) == 0,
+                "pool in use"
+            );
+        }
+        env.storage().instance().set(
+            &YIELDCFG,
+            &YieldConfig {
+                pool: pool.clone(),
+                reserve_bps,
+                merchant_bps,
+            },
+        );
+        Self::emit(
+            &env,
+            (symbol_short!("YieldCfg"), pool),
+            (reserve_bps, merchant_bps),
+        );
+    }
+
+    // Pulls all principal back before switching the integration off.
+    pub fn disable_yield(env: Env, invoker: Address) {
+        Self::only_owner(&env, &invoker);
+        Self::assert_single_key(&env);
+        let cfg = Self::get_yield_config(env.clone()).expect("yield off");
+        let supplied = Self::supplied(env.clone());
+        if supplied > 0 {
+            Self::withdraw_supply(&env, &cfg, &supplied);
+        }
+        env.storage().instance().remove(&YIELDCFG);
+        Self::emit(&env, (symbol_short!("YieldOff"),), cfg.pool);
+    }
+
+    pub fn get_yield_config(env: Env) -> Option<YieldConfig> {
+        env.storage().instance().get(&YIELDCFG)
+    }
+
+    pub fn supplied(env: Env) -> i128 {
+        env.storage().instance().get(&YSUPP).unwrap_or(0)
+    }
+
+    // Permissionless, so a keeper can run it. Returns the amount newly supplied.
+    pub fn supply_idle(env: Env) -> i128 {
+        let cfg = Self::get_yield_config(env.clone()).expect("yield off");
+        let reserve = Self::bps_of(&env, &Self::held_total(&env), cfg.reserve_bps);
+        // only funds the gateway owes someone are supplied, never unaccounted balance
+        let owed = Self::held_total(&env) - Self::supplied(env.clone());
+        let mut amount = owed - reserve;
+        let liquid = Self::liquid_balance(&env);
+        if amount > liquid {
+            amount = liquid;
+        }
+        if amount <= 0 {
+            return 0;
+        }
+        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        let gateway = env.current_contract_address();
+        env.authorize_as_current_contract(vec![
+            &env,
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: token,
+                    fn_name: Symbol::new(&env, "transfer"),
+                    args: (gateway.clone(), cfg.pool.clone(), amount).into_val(&env),
+                },
+                sub_invocations: Vec::new(&env),
+            }),
+        ]);
+        Self::invoke_token(
+            &env,
+            &cfg.pool,
+            "supply",
+            Vec::from_array(&env, [gateway.to_val(), amount.into_val(&env)]),
+        );
+        Self::set_supplied(&env, &(Self::supplied(env.clone()) + amount));
+        Self::emit(&env, (symbol_short!("Supplied"), cfg.pool), amount);
+        amount
+    }
+
+    // Withdraws whatever the pool holds above the principal and splits it. Returns the yield.
+    pub fn harvest_yield(env: Env) -> i128 {
+        let cfg = Self::get_yield_config(env.clone()).expect("yield off");
+        let in_pool: i128 = env.invoke_contract(
+            &cfg.pool,
+            &Symbol::new(&env, "balance"),
+            Vec::from_array(&env, [env.current_contract_address().to_val()]),
+        );
+        let earned = in_pool - Self::supplied(env.clone());
+        if earned <= 0 {
+            return 0;
+        }
+        Self::invoke_token(
+            &env,
+            &cfg.pool,
+            "withdraw",
+            Vec::from_array(
+                &env,
+                [
+                    env.current_contract_address().to_val(),
+                    earned.into_val(&env),
+                ],
+            ),
+        );
+        let merchants = Self::bps_of(&env, &earned, cfg.merchant_bps);
+        let shared = Self::share_with_custodial(&env, &merchants);
+        Self::add_fees(&env, &(earned - shared));
+        Self::emit(&env, (symbol_short!("Harvest"), cfg.pool), (earned, shared));
+        earned
+    }
+
+    // Called before every outgoing transfer so releases and refunds never wait on the pool.
+    pub(crate) fn ensure_liquid(env: &Env, amount: &i128) {
+        let cfg = match Self::get_yield_config(env.clone()) {
+            Some(cfg) => cfg,
+            None => return,
+        };
+        let supplied = Self::supplied(env.clone());
+        if supplied <= 0 {
+            return;
+        }
+        let mut shortfall = amount - Self::liquid_balance(env);
+        if shortfall <= 0 {
+            return;
+        }
+        if shortfall > supplied {
+            shortfall = supplied;
+        }
+        Self::withdraw_supply(env, &cfg, &shortfall);
+    }
+
+    fn withdraw_supply(env: &Env, cfg: &YieldConfig, amount: &i128) {
+        Self::invoke_token(
+            env,
+            &cfg.pool,
+            "withdraw",
+            Vec::from_array(
+                env,
+                [
+                    env.current_contract_address().to_val(),
+                    amount.into_val(env),
+                ],
+            ),
+        );
+        Self::set_supplied(env, &(Self::supplied(env.clone()) - amount));
+        Self::emit(env, (symbol_short!("Withdrawn"), cfg.pool.clone()), *amount);
+    }
+
+    pub(crate) fn liquid_balance(env: &Env) -> i128 {
+        let token: Address = env.storage().instance().get(&TOKEN).expect("Token");
+        env.invoke_contract(
+            &token,
+            &Symbol::new(env, "balance"),
+            Vec::from_array(env, [env.current_contract_address().to_val()]),
+        )
+    }
+
+    fn set_supplied(env: &Env, amount: &i128) {
+        env.storage().instance().set(&YSUPP, amount);
+    }
+}
@@ -0,0 +1,235 @@
+#![no_std]
+// Types and client for calling the payment gateway from other contracts. The client is
+// generated from `PaymentGatewayInterface`, which covers the entrypoints integrations need to
+// take and inspect payments; the structs are the ones the gateway itself stores.
+use soroban_sdk::{
+    contractclient, contracttype, Address, BytesN, Env, String, Symbol, Timepoint, Vec,
+};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentLink {
+    pub merchant: Address,
+    pub amount: i128,
+    pub active: bool,
+    pub description: String,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionPlan {
+    pub merchant: Address,
+    pub amount: i128,
+    pub interval: u32,
+    pub active: bool,
+    pub name: String,
+    pub donation: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Subscription {
+    pub subscriber: Address,
+    pub payer: Address,
+    pub plan_id: u64,
+    pub amount: i128,
+    pub start_time: Timepoint,
+    pub last_payment: Timepoint,
+    pub prepaid: u32,
+    // billing periods started so far, including the first and any gifted ones
+    pub cycles: u32,
+    // receipt covering the period in progress
+    pub last_receipt: u32,
+    pub status: SubscriptionStatus,
+    // seats billed each period; the recurring charge is `amount * quantity`
+    pub quantity: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SubscriptionStatus {
+    Trialing,
+    Active,
+    Paused,
+    PastDue,
+    Canceled,
+    Expired,
+}
+
+impl SubscriptionStatus {
+    // Canceled and Expired are terminal.
+    pub fn can_become(self, next: SubscriptionStatus) -> bool {
+        use SubscriptionStatus::*;
+        matches!(
+            (self, next),
+            (Trialing, Active | Canceled | Expired)
+                | (Active, Paused | PastDue | Canceled | Expired)
+                | (Paused, Active | Canceled)
+                | (PastDue, Active | Canceled | Expired)
+        )
+    }
+
+    pub fn is_live(self) -> bool {
+        !matches!(
+            self,
+            SubscriptionStatus::Canceled | SubscriptionStatus::Expired
+        )
+    }
+
+    pub fn is_billable(self) -> bool {
+        matches!(
+            self,
+            SubscriptionStatus::Active | SubscriptionStatus::PastDue
+        )
+    }
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PaymentSource {
+    Link(u64),
+    Subscription(Address, u64),
+    Invoice(u32),
+    Request(u32),
+    // store credit issued against the refunded receipt
+    StoreCredit(u32),
+    LinkBundle(u32),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Receipt {
+    pub source: PaymentSource,
+    pub merchant: Address,
+    pub payer: Address,
+    pub customer: Address,
+    pub amount: i128,
+    pub late_fee: i128,
+    // paid by the customer on top of `amount`
+    pub surcharge: i128,
+    // tax included in `amount`, empty when untaxed
+    pub tax: Vec<TaxLine>,
+    pub fee: i128,
+    pub refunded: i128,
+    pub timestamp: Timepoint,
+    // hash of the off-chain order the payment fulfilled, unique per merchant
+    pub order_id: Option<BytesN<32>>,
+    pub memo: Option<String>,
+}
+
+impl Receipt {
+    // Every other field starts at zero, the timestamp at the current ledger.
+    pub fn new(
+        env: &Env,
+        source: PaymentSource,
+        merchant: Address,
+        payer: Address,
+        customer: Address,
+        amount: i128,
+    ) -> Self {
+        Receipt {
+            source,
+            merchant,
+            payer,
+            customer,
+            amount,
+            late_fee: 0,
+            surcharge: 0,
+            tax: Vec::new(env),
+            fee: 0,
+            refunded: 0,
+            timestamp: Timepoint::from_unix(env, env.ledger().timestamp()),
+            order_id: None,
+            memo: None,
+        }
+    }
+}
+
+// Payload of every event that moves a payment from a payer to a merchant.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentEvent {
+    pub receipt_id: u32,
+    pub source: PaymentSource,
+    pub payer: Address,
+    pub customer: Address,
+    pub merchant: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub late_fee: i128,
+    pub surcharge: i128,
+    pub tax: Vec<TaxLine>,
+    pub fee: i128,
+    pub memo: Option<String>,
+    pub timestamp: Timepoint,
+}
+
+// Tax carried by a payment, part of its `amount`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TaxLine {
+    pub amount: i128,
+    pub jurisdiction: Symbol,
+    pub remitted: bool,
+}
+
+#[contractclient(name = "PaymentGatewayClient")]
+pub trait PaymentGatewayInterface {
+    // The payer authorizes `(link_id, amount)`. Retrying with an `order_id` that already paid
+    // returns the original receipt id.
+    fn process_payment(
+        env: Env,
+        invoker: Address,
+        link_id: u64,
+        amount: i128,
+        order_id: Option<BytesN<32>>,
+        memo: Option<String>,
+    ) -> u32;
+
+    // The sponsor pays and authorizes `(link_id, amount, beneficiary)`; the beneficiary is
+    // recorded as the customer.
+    fn process_payment_for(
+        env: Env,
+        sponsor: Address,
+        beneficiary: Address,
+        link_id: u64,
+        amount: i128,
+        order_id: Option<BytesN<32>>,
+        memo: Option<String>,
+    ) -> u32;
+
+    fn get_order_receipt(env: Env, merchant: Address, order_id: BytesN<32>) -> Option<u32>;
+
+    // The payer or merchant attaches an order hash to a receipt that has none.
+    fn attach_order(env: Env, invoker: Address, receipt_id: u32, order_id: BytesN<32>);
+
+    fn get_receipt(env: Env, receipt_id: u32) -> Receipt;
+
+    fn subscribe(env: Env, invoker: Address, plan_id: u64, amount: i128);
+
+    fn subscribe_for(
+        env: Env,
+        payer: Address,
+        beneficiary: Address,
+        plan_id: u64,
+        periods: u32,
+    ) -> u64;
+
+    fn cancel_subscription(env: Env, invoker: Address, subscription_id: u64);
+
+    fn get_subscription(env: Env, subscriber: Address, subscription_id: u64) -> Subscription;
+
+    fn get_subscription_status(
+        env: Env,
+        subscriber: Address,
+        subscription_id: u64,
+    ) -> SubscriptionStatus;
+
+    fn next_charge_at(env: Env, subscriber: Address, subscription_id: u64) -> Timepoint;
+
+    fn is_due(env: Env, subscriber: Address, subscription_id: u64) -> bool;
+
+    fn subscription_count(env: Env, subscriber: Address) -> u64;
+
+    fn is_paused(env: Env) -> bool;
+}
@@ -0,0 +1,118 @@
+#![no_std]
+use soroban_sdk::{
+    contract, contractimpl, contractmeta, contracttype, symbol_short, Address, Env, IntoVal,
+    Symbol, Timepoint, Val,
+};
+
+contractmeta!(key = "name", val = "receipt-nft");
+contractmeta!(key = "version", val = "0.1.0");
+contractmeta!(
+    key = "repo",
+    val = "https://github.com/Adi9876/Stellar-Contract"
+);
+
+// Proof of purchase minted by the gateway, one per receipt. There is no transfer entrypoint.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReceiptToken {
+    owner: Address,
+    merchant: Address,
+    // payment link the purchase went through, if any
+    link: Option<u64>,
+    amount: i128,
+    timestamp: Timepoint,
+}
+
+// Storage Keys (all <=9 chars)
+const GATEWAY: Symbol = symbol_short!("GATEWAY");
+// (TOKENS, token_id) -> ReceiptToken and (BAL, owner) -> u32, in persistent storage
+const TOKENS: Symbol = symbol_short!("TOKENS");
+const BAL: Symbol = symbol_short!("BAL");
+
+// kept alive for about a month past the last read or mint
+const RECORD_TTL: u32 = 535_680;
+const RECORD_TTL_THRESHOLD: u32 = RECORD_TTL / 2;
+
+#[contract]
+pub struct ReceiptNft;
+
+#[contractimpl]
+impl ReceiptNft {
+    pub fn init(env: Env, gateway: Address) {
+        assert!(!env.storage().instance().has(&GATEWAY), "already init");
+        env.storage().instance().set(&GATEWAY, &gateway);
+    }
+
+    pub fn gateway(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&GATEWAY)
+            .expect("GATEWAY not set")
+    }
+
+    // Token ids are the gateway's receipt ids.
+    pub fn mint(
+        env: Env,
+        receipt_id: u32,
+        owner: Address,
+        merchant: Address,
+        link: Option<u64>,
+        amount: i128,
+        timestamp: Timepoint,
+    ) {
+        Self::gateway(env.clone()).require_auth();
+        let key = (TOKENS, receipt_id);
+        assert!(!env.storage().persistent().has(&key), "already minted");
+        Self::put(
+            &env,
+            &key,
+            &ReceiptToken {
+                owner: owner.clone(),
+                merchant,
+                link,
+                amount,
+                timestamp,
+            },
+        );
+        let n = Self::balance(env.clone(), owner.clone());
+        Self::put(&env, &(BAL, owner.clone()), &(n + 1));
+        env.events()
+            .publish((symbol_short!("Mint"), owner), receipt_id);
+    }
+
+    pub fn owner_of(env: Env, token_id: u32) -> Address {
+        Self::get_token(env, token_id).owner
+    }
+
+    pub fn get_token(env: Env, token_id: u32) -> ReceiptToken {
+        let key = (TOKENS, token_id);
+        let token = env.storage().persistent().get(&key).expect("no token");
+        Self::bump(&env, &key);
+        token
+    }
+
+    pub fn balance(env: Env, owner: Address) -> u32 {
+        let key = (BAL, owner);
+        match env.storage().persistent().get(&key) {
+            Some(n) => {
+                Self::bump(&env, &key);
+                n
+            }
+            None => 0,
+        }
+    }
+
+    fn put<K: IntoVal<Env, Val>, V: IntoVal<Env, Val>>(env: &Env, key: &K, value: &V) {
+        env.storage().persistent().set(key, value);
+        Self::bump(env, key);
+    }
+
+    fn bump<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        env.storage()
+            .persistent()
+            .extend_ttl(key, RECORD_TTL_THRESHOLD, RECORD_TTL);
+    }
+}
+
+#[cfg(test)]
+mod test;
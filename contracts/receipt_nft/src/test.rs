@@ -0,0 +1,71 @@
+#![cfg(test)]
+
+use soroban_sdk::{
+    testutils::{Address as _, MockAuth, MockAuthInvoke},
+    Address, Env, IntoVal, Timepoint,
+};
+
+use crate::{ReceiptNft, ReceiptNftClient};
+
+fn setup() -> (Env, Address, ReceiptNftClient<'static>) {
+    let env = Env::default();
+    let gateway = Address::generate(&env);
+    let nft = ReceiptNftClient::new(&env, &env.register(ReceiptNft, ()));
+    nft.init(&gateway);
+    (env, gateway, nft)
+}
+
+#[test]
+fn gateway_mints_one_token_per_receipt() {
+    let (env, _, nft) = setup();
+    env.mock_all_auths();
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let at = Timepoint::from_unix(&env, 1_000);
+    nft.mint(&1, &owner, &merchant, &Some(7), &500, &at);
+    nft.mint(&2, &owner, &merchant, &None, &250, &at);
+    assert_eq!(nft.owner_of(&1), owner);
+    assert_eq!(nft.get_token(&2).amount, 250);
+    assert_eq!(nft.get_token(&1).link, Some(7));
+    assert_eq!(nft.balance(&owner), 2);
+    assert_eq!(nft.balance(&merchant), 0);
+    // a receipt id is only ever minted once
+    assert!(nft
+        .try_mint(&1, &merchant, &merchant, &None, &1, &at)
+        .is_err());
+    assert_eq!(nft.balance(&merchant), 0);
+    assert!(nft.try_get_token(&3).is_err());
+    assert!(nft.try_init(&owner).is_err());
+}
+
+#[test]
+fn only_the_gateway_can_mint() {
+    let (env, gateway, nft) = setup();
+    let owner = Address::generate(&env);
+    let at = Timepoint::from_unix(&env, 1_000);
+    let args = (
+        1u32,
+        owner.clone(),
+        owner.clone(),
+        None::<u64>,
+        500i128,
+        at.clone(),
+    );
+    let invoke = MockAuthInvoke {
+        contract: &nft.address,
+        fn_name: "mint",
+        args: args.into_val(&env),
+        sub_invokes: &[],
+    };
+    let signed_by = |who| MockAuth {
+        address: who,
+        invoke: &invoke,
+    };
+    assert!(nft
+        .mock_auths(&[signed_by(&owner)])
+        .try_mint(&1, &owner, &owner, &None, &500, &at)
+        .is_err());
+    nft.mock_auths(&[signed_by(&gateway)])
+        .mint(&1, &owner, &owner, &None, &500, &at);
+    assert_eq!(nft.balance(&owner), 1);
+}